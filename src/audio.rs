@@ -0,0 +1,402 @@
+use bevy::{
+    audio::{AddAudioSource, Decodable, PlaybackMode, Source, Volume},
+    prelude::*,
+    utils::HashMap,
+};
+use rand::Rng;
+use std::f32::consts::TAU;
+use std::time::Duration;
+
+use crate::coins::CoinCollectedEvent;
+use crate::enemy::PlayerHitEvent;
+use crate::finish_area::FinishEvent;
+use crate::player::player_attack::{EnemyKilledEvent, PlayerAttackEvent};
+use crate::walls::WallBumpEvent;
+use crate::save_data::SaveData;
+use crate::settings::Settings;
+
+const SAMPLE_RATE: u32 = 44_100;
+// Fade in at the start of each tone, so it does not click
+const ATTACK_SECONDS: f32 = 0.005;
+// Cutoff of muffled sounds, heard through walls
+const MUFFLED_CUTOFF: f32 = 350.;
+
+// Volume setting a sound follows
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AudioBus {
+    Music,
+    Sfx,
+}
+
+impl AudioBus {
+    pub fn volume(self, settings: &Settings) -> f32 {
+        match self {
+            AudioBus::Music => settings.music_volume,
+            AudioBus::Sfx => settings.sfx_volume,
+        }
+    }
+}
+
+// Sound playing on a bus, at its own loudness relative to the bus volume
+#[derive(Component)]
+pub struct OnAudioBus {
+    pub bus: AudioBus,
+    pub gain: f32,
+}
+
+impl OnAudioBus {
+    pub fn volume(&self, settings: &Settings) -> f32 {
+        self.bus.volume(settings) * self.gain
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Sfx {
+    SwordSwing,
+    Hit,
+    Coin,
+    WallBump,
+    Death,
+    Win,
+    UiClick,
+}
+
+impl Sfx {
+    pub const ALL: [Sfx; 7] = [Sfx::SwordSwing, Sfx::Hit, Sfx::Coin, Sfx::WallBump, Sfx::Death, Sfx::Win, Sfx::UiClick];
+
+    // How many of the same sound may play at once, more of them only turn into noise
+    fn max_instances(self) -> usize {
+        match self {
+            Sfx::SwordSwing => 3,
+            Sfx::Hit | Sfx::Coin => 4,
+            Sfx::UiClick => 2,
+            Sfx::WallBump | Sfx::Death | Sfx::Win => 1,
+        }
+    }
+
+    // Largest change of playback speed either way, so repeated sounds do not all sound the same
+    fn pitch_variation(self) -> f32 {
+        match self {
+            Sfx::SwordSwing | Sfx::Hit | Sfx::WallBump => 0.1,
+            Sfx::Coin | Sfx::UiClick => 0.04,
+            Sfx::Death | Sfx::Win => 0.,
+        }
+    }
+
+    fn gain(self) -> f32 {
+        match self {
+            Sfx::WallBump => 0.6,
+            Sfx::UiClick => 0.4,
+            _ => 0.8,
+        }
+    }
+
+    fn sound(self) -> SynthSound {
+        let tones = match self {
+            Sfx::SwordSwing => vec![Tone::new(Wave::Noise, 0., 0., 0.12, 0.35)],
+            Sfx::Hit => vec![
+                Tone::new(Wave::Noise, 0., 0., 0.03, 0.5),
+                Tone::new(Wave::Square, 220., 110., 0.1, 0.4),
+            ],
+            Sfx::Coin => vec![
+                Tone::new(Wave::Sine, 988., 988., 0.06, 0.6),
+                Tone::new(Wave::Sine, 1319., 1319., 0.18, 0.6),
+            ],
+            Sfx::WallBump => vec![Tone::new(Wave::Sine, 90., 60., 0.08, 0.8)],
+            Sfx::Death => vec![Tone::new(Wave::Square, 440., 110., 0.6, 0.35)],
+            Sfx::Win => vec![
+                Tone::new(Wave::Sine, 523., 523., 0.12, 0.5),
+                Tone::new(Wave::Sine, 659., 659., 0.12, 0.5),
+                Tone::new(Wave::Sine, 784., 784., 0.12, 0.5),
+                Tone::new(Wave::Sine, 1047., 1047., 0.3, 0.5),
+            ],
+            Sfx::UiClick => vec![Tone::new(Wave::Square, 1200., 1200., 0.03, 0.3)],
+        };
+
+        SynthSound::new(tones)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Wave {
+    Sine,
+    Square,
+    Noise,
+}
+
+// Sweeps from one frequency to another while fading out, noise ignores the frequency
+#[derive(Clone, Copy, Debug)]
+struct Tone {
+    wave: Wave,
+    from: f32,
+    to: f32,
+    seconds: f32,
+    volume: f32,
+}
+
+impl Tone {
+    fn new(wave: Wave, from: f32, to: f32, seconds: f32, volume: f32) -> Tone {
+        Tone { wave, from, to, seconds, volume }
+    }
+
+    fn samples(&self) -> u32 {
+        (self.seconds * SAMPLE_RATE as f32) as u32
+    }
+}
+
+// Sound effects and stings are synthesized from a few tones played one after another, instead of shipping a file for each
+#[derive(Asset, TypePath, Clone, Debug)]
+pub struct SynthSound {
+    tones: Vec<Tone>,
+    // Cutoff frequency of a low-pass filter over the whole sound
+    low_pass: Option<f32>,
+}
+
+impl SynthSound {
+    fn new(tones: Vec<Tone>) -> SynthSound {
+        SynthSound { tones, low_pass: None }
+    }
+
+    // Low double beat with a rest after it, loops under the music while enemies are close
+    pub fn heartbeat() -> SynthSound {
+        SynthSound::new(vec![
+            Tone::new(Wave::Sine, 70., 45., 0.14, 0.9),
+            Tone::new(Wave::Sine, 0., 0., 0.08, 0.),
+            Tone::new(Wave::Sine, 65., 40., 0.16, 0.7),
+            Tone::new(Wave::Sine, 0., 0., 0.42, 0.),
+        ])
+    }
+
+    pub fn victory_sting() -> SynthSound {
+        SynthSound::new(vec![
+            Tone::new(Wave::Square, 392., 392., 0.15, 0.25),
+            Tone::new(Wave::Square, 523., 523., 0.15, 0.25),
+            Tone::new(Wave::Square, 659., 659., 0.15, 0.25),
+            Tone::new(Wave::Square, 784., 784., 0.9, 0.25),
+        ])
+    }
+
+    pub fn defeat_sting() -> SynthSound {
+        SynthSound::new(vec![
+            Tone::new(Wave::Square, 311., 311., 0.3, 0.25),
+            Tone::new(Wave::Square, 294., 294., 0.3, 0.25),
+            Tone::new(Wave::Square, 277., 262., 1.1, 0.25),
+        ])
+    }
+
+    // Two scuffs and a rest, loops for as long as an enemy walks
+    pub fn footsteps() -> SynthSound {
+        SynthSound::new(vec![
+            Tone::new(Wave::Noise, 0., 0., 0.05, 0.6),
+            Tone::new(Wave::Sine, 0., 0., 0.25, 0.),
+            Tone::new(Wave::Noise, 0., 0., 0.05, 0.5),
+            Tone::new(Wave::Sine, 0., 0., 0.3, 0.),
+        ])
+    }
+
+    pub fn growl() -> SynthSound {
+        SynthSound {
+            tones: vec![
+                Tone::new(Wave::Square, 90., 110., 0.15, 0.4),
+                Tone::new(Wave::Square, 110., 65., 0.45, 0.4),
+            ],
+            low_pass: Some(1200.),
+        }
+    }
+
+    // The same sound as heard from the other side of a wall
+    pub fn muffled(self) -> SynthSound {
+        SynthSound {
+            low_pass: Some(self.low_pass.map_or(MUFFLED_CUTOFF, |cutoff| cutoff.min(MUFFLED_CUTOFF))),
+            ..self
+        }
+    }
+}
+
+impl Decodable for SynthSound {
+    type DecoderItem = f32;
+    type Decoder = SynthDecoder;
+
+    fn decoder(&self) -> Self::Decoder {
+        // One pole filter, each sample moves this share of the way towards the unfiltered one
+        let smoothing = self.low_pass.map_or(1., |cutoff| 1. - (-TAU * cutoff / SAMPLE_RATE as f32).exp());
+
+        SynthDecoder {
+            tones: self.tones.clone(),
+            tone: 0,
+            sample: 0,
+            phase: 0.,
+            noise: 0x9e37_79b9,
+            smoothing,
+            filtered: 0.,
+        }
+    }
+}
+
+pub struct SynthDecoder {
+    tones: Vec<Tone>,
+    tone: usize,
+    sample: u32,
+    phase: f32,
+    noise: u32,
+    smoothing: f32,
+    filtered: f32,
+}
+
+impl Iterator for SynthDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let tone = *self.tones.get(self.tone)?;
+        let samples = tone.samples().max(1);
+        let progress = self.sample as f32 / samples as f32;
+
+        let frequency = tone.from + (tone.to - tone.from) * progress;
+        self.phase = (self.phase + frequency / SAMPLE_RATE as f32).fract();
+
+        let value = match tone.wave {
+            Wave::Sine => (self.phase * TAU).sin(),
+            Wave::Square => if self.phase < 0.5 { 1. } else { -1. },
+            Wave::Noise => {
+                // Xorshift, random enough for noise and the same every time the sound plays
+                self.noise ^= self.noise << 13;
+                self.noise ^= self.noise >> 17;
+                self.noise ^= self.noise << 5;
+                self.noise as f32 / u32::MAX as f32 * 2. - 1.
+            }
+        };
+
+        let attack = (self.sample as f32 / (ATTACK_SECONDS * SAMPLE_RATE as f32)).min(1.);
+        let envelope = attack * (1. - progress);
+
+        self.sample += 1;
+        if self.sample >= samples {
+            self.tone += 1;
+            self.sample = 0;
+        }
+
+        self.filtered += (value * envelope * tone.volume - self.filtered) * self.smoothing;
+        Some(self.filtered)
+    }
+}
+
+impl Source for SynthDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        Some(Duration::from_secs_f32(self.tones.iter().map(|tone| tone.seconds).sum()))
+    }
+}
+
+#[derive(Resource)]
+pub struct SfxBank(HashMap<Sfx, Handle<SynthSound>>);
+
+impl FromWorld for SfxBank {
+    fn from_world(world: &mut World) -> Self {
+        let mut sounds = world.resource_mut::<Assets<SynthSound>>();
+
+        SfxBank(Sfx::ALL.into_iter().map(|sfx| (sfx, sounds.add(sfx.sound()))).collect())
+    }
+}
+
+// Asks for a sound effect to be played, gameplay events are turned into these by play_gameplay_sfx
+#[derive(Event)]
+pub struct SfxEvent(pub Sfx);
+
+// Marks a sound effect while it plays, so the number playing at once can be limited
+#[derive(Component)]
+pub struct PlayingSfx(pub Sfx);
+
+pub struct SfxPlugin;
+
+impl Plugin for SfxPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_audio_source::<SynthSound>()
+            .add_event::<SfxEvent>()
+            .add_event::<CoinCollectedEvent>()
+            .add_event::<PlayerAttackEvent>()
+            .add_event::<WallBumpEvent>()
+            .init_resource::<SfxBank>()
+            .add_systems(Update, (play_gameplay_sfx, play_ui_click_sfx, play_sfx).chain());
+    }
+}
+
+pub fn play_gameplay_sfx(
+    mut sfx_events: EventWriter<SfxEvent>,
+    mut attack_events: EventReader<PlayerAttackEvent>,
+    mut killed_events: EventReader<EnemyKilledEvent>,
+    mut coin_events: EventReader<CoinCollectedEvent>,
+    mut bump_events: EventReader<WallBumpEvent>,
+    mut hit_events: EventReader<PlayerHitEvent>,
+    mut finish_events: EventReader<FinishEvent>,
+) {
+    for (count, sfx) in [
+        (bump_events.read().count(), Sfx::WallBump),
+        (attack_events.read().count(), Sfx::SwordSwing),
+        (killed_events.read().count(), Sfx::Hit),
+        (coin_events.read().count(), Sfx::Coin),
+        (hit_events.read().count(), Sfx::Death),
+        (finish_events.read().count(), Sfx::Win),
+    ] {
+        sfx_events.send_batch((0..count).map(|_| SfxEvent(sfx)));
+    }
+}
+
+pub fn play_ui_click_sfx(
+    mut sfx_events: EventWriter<SfxEvent>,
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<Button>)>,
+) {
+    for interaction in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            sfx_events.send(SfxEvent(Sfx::UiClick));
+        }
+    }
+}
+
+pub fn play_sfx(
+    mut commands: Commands,
+    mut sfx_events: EventReader<SfxEvent>,
+    sfx_bank: Res<SfxBank>,
+    save_data: Res<SaveData>,
+    playing_query: Query<&PlayingSfx>,
+) {
+    let mut playing: HashMap<Sfx, usize> = HashMap::new();
+    for PlayingSfx(sfx) in &playing_query {
+        *playing.entry(*sfx).or_default() += 1;
+    }
+
+    let mut rng = rand::thread_rng();
+
+    for SfxEvent(sfx) in sfx_events.read() {
+        let count = playing.entry(*sfx).or_default();
+        if *count >= sfx.max_instances() { continue };
+        *count += 1;
+
+        let bus = OnAudioBus { bus: AudioBus::Sfx, gain: sfx.gain() };
+        let variation = sfx.pitch_variation();
+
+        commands.spawn((
+            AudioSourceBundle {
+                source: sfx_bank.0[sfx].clone(),
+                settings: PlaybackSettings {
+                    mode: PlaybackMode::Despawn,
+                    volume: Volume::new(bus.volume(&save_data.settings)),
+                    speed: 1. + rng.gen_range(-variation..=variation),
+                    ..default()
+                },
+            },
+            bus,
+            PlayingSfx(*sfx),
+        ));
+    }
+}
@@ -0,0 +1,60 @@
+use std::{env, fs, path::Path, process::ExitCode};
+
+use labyrinth_bevy::level::{Level, Levels};
+use labyrinth_bevy::level_export::{ExportFormat, export_level};
+use labyrinth_bevy::maze_generator::generate_level;
+use labyrinth_bevy::save_data::SaveData;
+
+const USAGE: &str = "Usage: export_maze <level.ron|level id|generated> <output.svg|output.png> [--solution]";
+
+// "generated" exports the maze the custom maze settings currently produce
+fn load_level(source: &str) -> Result<Level, String> {
+    if source == "generated" {
+        return Ok(generate_level(&SaveData::load().settings.maze));
+    }
+
+    if Path::new(source).is_file() {
+        let contents = fs::read_to_string(source).map_err(|error| error.to_string())?;
+        return Level::from_ron(&contents).map_err(|error| error.to_string());
+    }
+
+    Levels::load()
+        .get(source)
+        .map(|entry| entry.level.clone())
+        .ok_or(format!("No level file or level id {source}"))
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let solution = args.iter().any(|arg| arg == "--solution");
+    let paths: Vec<&String> = args.iter().filter(|arg| !arg.starts_with("--")).collect();
+
+    let (Some(source), Some(output)) = (paths.first(), paths.get(1)) else {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    };
+
+    let format = match Path::new(output).extension().and_then(|extension| extension.to_str()) {
+        Some("svg") => ExportFormat::Svg,
+        Some("png") => ExportFormat::Png,
+        _ => {
+            eprintln!("{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let result = load_level(source)
+        .and_then(|level| export_level(&level, format, solution))
+        .and_then(|contents| fs::write(output, contents).map_err(|error| error.to_string()));
+
+    match result {
+        Ok(()) => {
+            println!("Exported {source} to {output}");
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            eprintln!("Could not export {source}: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use std::{env, fs, path::Path, process::ExitCode};
+
+use labyrinth_bevy::level_import::{import_ascii, import_svg};
+
+// Usage: import_maze <maze.svg|maze.txt> <level.ron> [name]
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let (Some(input), Some(output)) = (args.first(), args.get(1)) else {
+        eprintln!("Usage: import_maze <maze.svg|maze.txt> <level.ron> [name]");
+        return ExitCode::FAILURE;
+    };
+
+    let name = args.get(2).cloned().unwrap_or_else(|| {
+        Path::new(input).file_stem().map_or("Imported".to_string(), |stem| stem.to_string_lossy().to_string())
+    });
+
+    let is_svg = Path::new(input).extension().is_some_and(|extension| extension.eq_ignore_ascii_case("svg"));
+
+    let result = fs::read_to_string(input)
+        .map_err(|error| error.to_string())
+        .and_then(|contents| if is_svg { import_svg(&contents, &name) } else { import_ascii(&contents, &name) })
+        .and_then(|level| level.to_ron().map_err(|error| error.to_string()))
+        .and_then(|contents| fs::write(output, contents).map_err(|error| error.to_string()));
+
+    match result {
+        Ok(()) => {
+            println!("Imported {input} into {output}");
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            eprintln!("Could not import {input}: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use std::{env, fs, process::ExitCode};
+
+use labyrinth_bevy::level::Level;
+use labyrinth_bevy::level_validator::validate_level;
+
+// Usage: validate_level <level.ron>...
+fn main() -> ExitCode {
+    let paths: Vec<String> = env::args().skip(1).collect();
+
+    if paths.is_empty() {
+        eprintln!("Usage: validate_level <level.ron>...");
+        return ExitCode::FAILURE;
+    }
+
+    let mut valid = true;
+
+    for path in &paths {
+        let contents = fs::read_to_string(path).map_err(|error| error.to_string());
+        let level = match contents.and_then(|contents| Level::from_ron(&contents).map_err(|error| error.to_string())) {
+            Ok(level) => level,
+            Err(error) => {
+                println!("{path}: could not read level: {error}");
+                valid = false;
+                continue;
+            }
+        };
+
+        let problems = validate_level(&level);
+
+        if problems.is_empty() {
+            println!("{path}: ok");
+            continue;
+        }
+
+        valid = false;
+        println!("{path}: {} problem(s)", problems.len());
+        for problem in problems {
+            println!("  {problem}");
+        }
+    }
+
+    if valid { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+}
@@ -1,14 +1,140 @@
-use bevy::prelude::*;
+use bevy::{
+    prelude::*,
+    render::{camera::{ClearColorConfig, Viewport}, view::RenderLayers},
+    window::PrimaryWindow,
+};
+use rand::Rng;
 
-use crate::player::player::Player;
+use crate::enemy::PlayerHitEvent;
+use crate::game_state::{GameState, PauseState};
+use crate::level::{CurrentLevel, LevelCamera};
+use crate::player::player::{Player, PlayerFacingDirection};
+use crate::player::player_attack::EnemyKilledEvent;
+use crate::walls::WallBumpEvent;
+use crate::save_data::SaveData;
+use crate::settings::SplitScreen;
 
 const CAM_LERP_FACTOR: f32 = 2.;
+// Each press of + or - zooms by this factor
+const CAM_ZOOM_STEP: f32 = 1.25;
+// Room left around the maze in the overview
+const CAM_OVERVIEW_MARGIN: f32 = 1.1;
+// Trauma added by each kind of hit, the shake grows with the square of the trauma and fades at a steady rate
+const PLAYER_HIT_TRAUMA: f32 = 0.6;
+const ENEMY_KILL_TRAUMA: f32 = 0.35;
+const WALL_BUMP_TRAUMA: f32 = 0.15;
+const TRAUMA_DECAY: f32 = 1.5;
+const SHAKE_MAX_OFFSET: f32 = 8.;
+const SHAKE_MAX_ROLL: f32 = 0.03;
+// Sword hits slow the game to a crawl for a moment of real time
+const HIT_STOP_SECONDS: f32 = 0.08;
+const HIT_STOP_SPEED: f32 = 0.05;
 
-pub fn setup_camera(mut commands: Commands) {
+// Zoom and overview picked by the players, together with the level's camera settings they apply to
+#[derive(Resource)]
+pub struct CameraView {
+    pub zoom: f32,
+    pub overview: bool,
+    pub settings: LevelCamera,
+    pub bounds: (Vec2, Vec2),
+}
+
+impl Default for CameraView {
+    fn default() -> Self {
+        CameraView {
+            zoom: 1.,
+            overview: false,
+            settings: LevelCamera::default(),
+            bounds: (Vec2::ZERO, Vec2::ZERO),
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct CameraShake {
+    pub trauma: f32,
+}
+
+// Real seconds left of the current hit-stop
+#[derive(Resource, Default)]
+pub struct HitStop {
+    pub remaining: f32,
+}
+
+/*
+ * The camera spawned at startup follows the first player in the game and every state draws its UI
+ * through it. Co-op games add a second one for the other player for as long as the game lasts.
+ */
+#[derive(Component)]
+pub struct GameCamera {
+    pub player: usize,
+    // Where the camera is before any shake is added on top
+    pub position: Vec2,
+}
+
+// Cameras that only exist during a co-op game
+#[derive(Component)]
+struct CoopCamera;
+
+// Spawns the camera once at startup and sets it up for each state, instead of each state spawning its own
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PlayerHitEvent>()
+            .add_event::<EnemyKilledEvent>()
+            .add_event::<WallBumpEvent>()
+            .init_resource::<CameraView>()
+            .init_resource::<CameraShake>()
+            .init_resource::<HitStop>()
+            .add_systems(Startup, spawn_camera)
+            .add_systems(OnEnter(GameState::Menu), reset_menu_camera)
+            .add_systems(OnEnter(GameState::Editor), focus_editor_camera)
+            .add_systems(OnEnter(GameState::Game), spawn_coop_cameras.before(reset_camera))
+            .add_systems(OnExit(GameState::Game), despawn_coop_cameras)
+            .add_systems(Update, zoom_camera.run_if(in_state(PauseState::Running)))
+            .add_systems(Update, (update_hit_stop, split_screen));
+    }
+}
+
+pub fn spawn_camera(mut commands: Commands) {
+    commands.spawn((GameCamera { player: 0, position: Vec2::ZERO }, IsDefaultUiCamera, Camera2dBundle::default()));
+}
+
+/*
+ * The second player gets a camera of their own, and the UI moves to a camera that draws nothing
+ * else on top of both halves, so it spans the whole window instead of the first half.
+ */
+fn spawn_coop_cameras(
+    mut commands: Commands,
+    save_data: Res<SaveData>,
+    camera_query: Query<Entity, (With<GameCamera>, Without<CoopCamera>)>,
+) {
+    if save_data.settings.player_count() < 2 { return };
+
+    for camera in &camera_query {
+        commands.entity(camera).remove::<IsDefaultUiCamera>();
+    }
+
+    commands.spawn((
+        GameCamera { player: 1, position: Vec2::ZERO },
+        CoopCamera,
+        Camera2dBundle {
+            camera: Camera {
+                order: 1,
+                ..default()
+            },
+            ..default()
+        },
+    ));
     commands.spawn((
+        CoopCamera,
+        IsDefaultUiCamera,
+        RenderLayers::none(),
         Camera2dBundle {
             camera: Camera {
-                hdr: true,
+                order: 2,
+                clear_color: ClearColorConfig::None,
                 ..default()
             },
             ..default()
@@ -16,24 +142,239 @@ pub fn setup_camera(mut commands: Commands) {
     ));
 }
 
-pub fn update_camera(
-    mut camera: Query<&mut Transform, (With<Camera2d>, Without<Player>)>,
-    player: Query<&Transform, (With<Player>, Without<Camera2d>)>,
-    time: Res<Time>,
+#[allow(clippy::type_complexity)]
+fn despawn_coop_cameras(
+    mut commands: Commands,
+    coop_camera_query: Query<Entity, With<CoopCamera>>,
+    mut camera_query: Query<(Entity, &mut Camera), (With<GameCamera>, Without<CoopCamera>)>,
 ) {
-    let Ok(mut camera) = camera.get_single_mut() else {
+    if coop_camera_query.is_empty() { return };
+
+    for camera in &coop_camera_query {
+        commands.entity(camera).despawn_recursive();
+    }
+
+    for (entity, mut camera) in &mut camera_query {
+        camera.viewport = None;
+        commands.entity(entity).insert(IsDefaultUiCamera);
+    }
+}
+
+// Gives each follow camera its half of the window while there is more than one
+pub fn split_screen(
+    save_data: Res<SaveData>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    mut camera_query: Query<(&GameCamera, &mut Camera)>,
+) {
+    let Ok(window) = window_query.get_single() else {
         return;
     };
 
-    let Ok(player) = player.get_single() else {
-        return;
+    let split = camera_query.iter().count() > 1;
+    let size = window.physical_size();
+    let half = match save_data.settings.split_screen {
+        SplitScreen::Vertical => UVec2::new(size.x / 2, size.y),
+        SplitScreen::Horizontal => UVec2::new(size.x, size.y / 2),
+    };
+
+    for (game_camera, mut camera) in &mut camera_query {
+        let viewport = (split && half.min_element() > 0).then(|| Viewport {
+            physical_position: match save_data.settings.split_screen {
+                SplitScreen::Vertical => UVec2::new(half.x * game_camera.player as u32, 0),
+                SplitScreen::Horizontal => UVec2::new(0, half.y * game_camera.player as u32),
+            },
+            physical_size: half,
+            ..default()
+        });
+
+        if camera.viewport.as_ref().map(|viewport| (viewport.physical_position, viewport.physical_size))
+            != viewport.as_ref().map(|viewport| (viewport.physical_position, viewport.physical_size))
+        {
+            camera.viewport = viewport;
+        }
+    }
+}
+
+// Menus only show UI, but the next level or the editor should not start out zoomed or shaken
+pub fn reset_menu_camera(
+    mut camera_query: Query<(&mut Transform, &mut OrthographicProjection), With<GameCamera>>,
+) {
+    for (mut camera_transform, mut projection) in &mut camera_query {
+        *camera_transform = Transform::from_translation(Vec3::Z * camera_transform.translation.z);
+        projection.scale = 1.;
+    }
+}
+
+// The editor shows the whole level at the default zoom, whatever the last level played left behind
+pub fn focus_editor_camera(
+    current_level: Res<CurrentLevel>,
+    mut camera_query: Query<(&mut Transform, &mut OrthographicProjection), With<GameCamera>>,
+) {
+    let (min, max) = current_level.level.bounds();
+
+    for (mut camera_transform, mut projection) in &mut camera_query {
+        camera_transform.translation = min.midpoint(max).extend(camera_transform.translation.z);
+        camera_transform.rotation = Quat::IDENTITY;
+        projection.scale = 1.;
+    }
+}
+
+// Starts every level at its own zoom, looking at each player wherever a resumed level put them
+pub fn reset_camera(
+    mut camera_view: ResMut<CameraView>,
+    mut camera_shake: ResMut<CameraShake>,
+    current_level: Res<CurrentLevel>,
+    mut camera_query: Query<(&mut GameCamera, &mut Transform, &mut OrthographicProjection), Without<Player>>,
+    player_query: Query<(&Transform, &Player), Without<GameCamera>>,
+) {
+    let level = &current_level.level;
+
+    *camera_view = CameraView {
+        zoom: level.camera.zoom,
+        overview: false,
+        settings: level.camera,
+        bounds: level.bounds(),
     };
+    camera_shake.trauma = 0.;
+
+    for (mut game_camera, mut camera_transform, mut projection) in &mut camera_query {
+        let start = player_query
+            .iter()
+            .find(|(_, player)| player.index == game_camera.player)
+            .map_or(level.player_start, |(player_transform, _)| player_transform.translation.truncate());
+
+        game_camera.position = start;
+        camera_transform.translation = start.extend(camera_transform.translation.z);
+        camera_transform.rotation = Quat::IDENTITY;
+        projection.scale = level.camera.zoom;
+    }
+}
+
+pub fn zoom_camera(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut camera_view: ResMut<CameraView>,
+) {
+    let settings = camera_view.settings;
+
+    if keyboard_input.any_just_pressed([KeyCode::Equal, KeyCode::NumpadAdd]) {
+        camera_view.zoom = (camera_view.zoom / CAM_ZOOM_STEP).clamp(settings.min_zoom, settings.max_zoom);
+    }
+    if keyboard_input.any_just_pressed([KeyCode::Minus, KeyCode::NumpadSubtract]) {
+        camera_view.zoom = (camera_view.zoom * CAM_ZOOM_STEP).clamp(settings.min_zoom, settings.max_zoom);
+    }
+    if keyboard_input.just_pressed(KeyCode::Tab) {
+        camera_view.overview = !camera_view.overview;
+    }
+}
+
+// Moves each axis only as far as needed to bring the target back inside the dead zone around the focus
+fn follow(focus: Vec2, target: Vec2, dead_zone: Vec2) -> Vec2 {
+    let offset = target - focus;
+    focus + offset - offset.clamp(-dead_zone, dead_zone)
+}
+
+// Keeps the view inside the bounds, or centered on them along an axis where they are smaller than the view
+fn clamp_to_bounds(focus: Vec2, half_view: Vec2, (min, max): (Vec2, Vec2)) -> Vec2 {
+    let low = min + half_view;
+    let high = max - half_view;
+
+    Vec2::select(low.cmple(high), focus.clamp(low.min(high), high.max(low)), min.midpoint(max))
+}
 
-    let Vec3 { x, y, .. } = player.translation;
-    let direction = Vec3::new(x, y, camera.translation.z);
+pub fn add_camera_trauma(
+    mut camera_shake: ResMut<CameraShake>,
+    mut hit_events: EventReader<PlayerHitEvent>,
+    mut killed_events: EventReader<EnemyKilledEvent>,
+    mut bump_events: EventReader<WallBumpEvent>,
+) {
+    let trauma = hit_events.read().count() as f32 * PLAYER_HIT_TRAUMA
+        + killed_events.read().count() as f32 * ENEMY_KILL_TRAUMA
+        + bump_events.read().count() as f32 * WALL_BUMP_TRAUMA;
 
-    camera.translation = camera
-        .translation
-        .lerp(direction, time.delta_seconds() * CAM_LERP_FACTOR);
+    if trauma > 0. {
+        camera_shake.trauma = (camera_shake.trauma + trauma).min(1.);
+    }
 }
 
+// Runs on real time, since the time it slows down would otherwise stretch it as well
+pub fn update_hit_stop(
+    mut hit_stop: ResMut<HitStop>,
+    mut killed_events: EventReader<EnemyKilledEvent>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+    real_time: Res<Time<Real>>,
+    save_data: Res<SaveData>,
+) {
+    let hit = !killed_events.is_empty();
+    killed_events.clear();
+
+    if hit && !save_data.settings.reduced_motion {
+        hit_stop.remaining = HIT_STOP_SECONDS;
+        virtual_time.set_relative_speed(HIT_STOP_SPEED);
+        return;
+    }
+
+    if hit_stop.remaining <= 0. { return };
+
+    hit_stop.remaining -= real_time.delta_seconds();
+    if hit_stop.remaining <= 0. || save_data.settings.reduced_motion {
+        hit_stop.remaining = 0.;
+        virtual_time.set_relative_speed(1.);
+    }
+}
+
+// Each follow camera eases after its own player, a caught player's camera stays where they were caught
+pub fn update_camera(
+    mut camera_query: Query<(&mut GameCamera, &mut Transform, &mut OrthographicProjection), Without<Player>>,
+    player_query: Query<(&Transform, &Player), Without<GameCamera>>,
+    time: Res<Time>,
+    save_data: Res<SaveData>,
+    camera_view: Res<CameraView>,
+    mut camera_shake: ResMut<CameraShake>,
+) {
+    let settings = &camera_view.settings;
+    let (min, max) = camera_view.bounds;
+
+    camera_shake.trauma = (camera_shake.trauma - time.delta_seconds() * TRAUMA_DECAY).max(0.);
+    // Reduced motion keeps the camera steady however hard the hit
+    let shake = if save_data.settings.reduced_motion { 0. } else { camera_shake.trauma.powi(2) };
+    let mut rng = rand::thread_rng();
+
+    for (mut game_camera, mut camera, mut projection) in &mut camera_query {
+        let Some((player_transform, player)) = player_query.iter().find(|(_, player)| player.index == game_camera.player) else {
+            continue;
+        };
+
+        // Size of the view at a zoom of one, the projection area already has the current zoom applied
+        let view = projection.area.size() / projection.scale;
+
+        let (focus, scale) = if camera_view.overview && view.min_element() > 0. {
+            (min.midpoint(max), ((max - min) / view).max_element() * CAM_OVERVIEW_MARGIN)
+        } else {
+            let facing = match player.player_facing_direction {
+                PlayerFacingDirection::Left => Vec2::NEG_X,
+                PlayerFacingDirection::Right => Vec2::X,
+                PlayerFacingDirection::Up => Vec2::Y,
+                PlayerFacingDirection::Down => Vec2::NEG_Y,
+            };
+            let target = player_transform.translation.truncate() + facing * settings.look_ahead;
+
+            (follow(game_camera.position, target, settings.dead_zone), camera_view.zoom)
+        };
+
+        // Reduced motion snaps to the player instead of easing after them
+        let factor = if save_data.settings.reduced_motion { 1. } else { (time.delta_seconds() * CAM_LERP_FACTOR).min(1.) };
+        projection.scale = projection.scale.lerp(scale, factor);
+        let mut position = game_camera.position.lerp(focus, factor);
+
+        // Clamped at the zoom actually shown, so easing out never uncovers what lies beyond the walls
+        if settings.clamp_to_bounds && view.min_element() > 0. {
+            position = clamp_to_bounds(position, view * projection.scale / 2., camera_view.bounds);
+        }
+        game_camera.position = position;
+
+        let offset = Vec2::new(rng.gen_range(-1.0..=1.), rng.gen_range(-1.0..=1.)) * SHAKE_MAX_OFFSET * shake;
+
+        camera.translation = (position + offset).extend(camera.translation.z);
+        camera.rotation = Quat::from_rotation_z(rng.gen_range(-1.0..=1.) * SHAKE_MAX_ROLL * shake);
+    }
+}
@@ -3,7 +3,12 @@ use bevy::{
     prelude::*,
 };
 
+use crate::floors::{CurrentFloor, Floor};
+use crate::fog::HiddenInFog;
+use crate::game_state::OnGameScreen;
 use crate::player::player::Player;
+use crate::save_data::SaveData;
+use crate::settings::CoopScoring;
 
 #[derive(Component)]
 pub struct Coin;
@@ -14,23 +19,27 @@ pub struct Score(pub usize);
 #[derive(Component)]
 pub struct ScoreboardUi;
 
-const COIN_SIZE: Vec2 = Vec2::new(10., 10.);
-const COIN_COLOR: Color = Color::srgb(120., 120., 0.);
+#[derive(Event, Default)]
+pub struct CoinCollectedEvent;
+
+pub const COIN_SIZE: Vec2 = Vec2::new(10., 10.);
+pub const COIN_COLOR: Color = Color::srgb(120., 120., 0.);
 
 pub fn spawn_coins(
     commands: &mut Commands,
+    positions: &[Vec2],
+    floor: usize,
 ) {
-    spawn_coin(commands, 80., 80.);
-    spawn_coin(commands, 112., 120.);
-    spawn_coin(commands, 257., 340.);
-    spawn_coin(commands, 440., 440.);
-    spawn_coin(commands, 80., 440.);
+    for position in positions {
+        spawn_coin(commands, position.x, position.y, floor);
+    }
 }
 
-fn spawn_coin(
+pub fn spawn_coin(
     commands: &mut Commands,
     x_position: f32,
     y_position: f32,
+    floor: usize,
 ) {
     commands.spawn((
         SpriteBundle {
@@ -46,39 +55,55 @@ fn spawn_coin(
             ..default()
         },
         Coin,
+        Floor(floor),
+        HiddenInFog,
+        OnGameScreen,
     ));
 }
 
+// The score counts every coin, each player also keeps count of their own for competitive co-op
 pub fn check_for_player_collisions_with_coins(
     mut commands: Commands,
     mut score: ResMut<Score>,
-    mut player_query: Query<&Transform, With<Player>>,
-    coins_query: Query<(Entity, &Transform), With<Coin>>,
+    mut player_query: Query<(&mut Player, &Transform)>,
+    coins_query: Query<(Entity, &Transform, &Floor), With<Coin>>,
+    current_floor: Res<CurrentFloor>,
+    mut collected_events: EventWriter<CoinCollectedEvent>,
 ) {
-    let player_transform = player_query.single_mut();
-    let player_bounding_box = Aabb2d::new(
-        player_transform.translation.truncate(),
-        player_transform.scale.truncate() / 2.,
-    );
-
-    for (coin_entity, coin_transform) in &coins_query {
+    for (coin_entity, coin_transform, _) in coins_query.iter().filter(|(_, _, floor)| current_floor.contains(floor)) {
         let coin_bounding_box = Aabb2d::new(
             coin_transform.translation.truncate(),
             coin_transform.scale.truncate() / 2.,
         );
 
-        if coin_bounding_box.intersects(&player_bounding_box) {
+        // When both players touch a coin at once it goes to whoever comes first
+        let collector = player_query.iter_mut().find(|(player, player_transform)| {
+            player.alive
+                && coin_bounding_box.intersects(&Aabb2d::new(
+                    player_transform.translation.truncate(),
+                    player_transform.scale.truncate() / 2.,
+                ))
+        });
+
+        if let Some((mut player, _)) = collector {
+            player.coins += 1;
             **score += 1;
             commands.entity(coin_entity).despawn();
+            collected_events.send_default();
         }
     }
 }
 
+pub fn reset_score(mut score: ResMut<Score>) {
+    **score = 0;
+}
+
 pub fn spawn_scoreboard(
     commands: &mut Commands,
 ) {
    commands.spawn((
         ScoreboardUi,
+        OnGameScreen,
         TextBundle::from_sections([
             TextSection::new(
                 "Score: ",
@@ -107,9 +132,26 @@ pub fn spawn_scoreboard(
 
 pub fn update_scoreboard(
     score: Res<Score>,
+    save_data: Res<SaveData>,
+    player_query: Query<&Player>,
     mut query: Query<&mut Text, With<ScoreboardUi>>,
 ) {
-    let mut text = query.single_mut();
-    text.sections[1].value = score.to_string();
+    let competitive = save_data.settings.coop_scoring == CoopScoring::Competitive && player_query.iter().count() > 1;
+
+    let value = if competitive {
+        let mut players: Vec<&Player> = player_query.iter().collect();
+        players.sort_by_key(|player| player.index);
+        players
+            .iter()
+            .map(|player| format!("P{} {}", player.index + 1, player.coins))
+            .collect::<Vec<String>>()
+            .join("  ")
+    } else {
+        score.to_string()
+    };
+
+    for mut text in &mut query {
+        text.sections[1].value.clone_from(&value);
+    }
 }
 
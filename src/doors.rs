@@ -0,0 +1,326 @@
+use bevy::{
+    math::bounding::{Aabb2d, IntersectsVolume},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::collider::Collider;
+use crate::floors::{CurrentFloor, Floor};
+use crate::fog::HiddenInFog;
+use crate::game_state::OnGameScreen;
+use crate::level::{LevelDoor, LevelGate, LevelKey, LevelSwitch};
+use crate::maze::MazeTopology;
+use crate::player::player::Player;
+use crate::walls::{Wall, wall_push_direction, wall_transform};
+
+const KEY_SIZE: Vec2 = Vec2::new(9., 9.);
+const SWITCH_SIZE: Vec2 = Vec2::new(14., 14.);
+const SWITCH_COLOR: Color = Color::srgb(0.45, 0.45, 0.5);
+const SWITCH_PRESSED_COLOR: Color = Color::srgb(0.25, 0.25, 0.3);
+const GATE_CLOSED_COLOR: Color = Color::srgb(0.6, 0.6, 0.7);
+// Open gates stay faintly visible so the player knows where they close again
+const GATE_OPEN_COLOR: Color = Color::srgba(0.6, 0.6, 0.7, 0.15);
+const KEY_TEXT_COLOR: Color = Color::srgb(80., 80., 80.);
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum KeyColor {
+    Red,
+    Blue,
+    Purple,
+}
+
+impl KeyColor {
+    pub const ALL: [KeyColor; 3] = [KeyColor::Red, KeyColor::Blue, KeyColor::Purple];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            KeyColor::Red => "Red",
+            KeyColor::Blue => "Blue",
+            KeyColor::Purple => "Purple",
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            KeyColor::Red => Color::srgb(0.9, 0.2, 0.2),
+            KeyColor::Blue => Color::srgb(0.2, 0.4, 1.),
+            KeyColor::Purple => Color::srgb(0.7, 0.3, 0.9),
+        }
+    }
+
+    // Bit of this color in a set of held keys
+    pub fn bit(&self) -> u8 {
+        1 << *self as u8
+    }
+}
+
+// Index of the key, door or gate in the level, so a save slot can tell which ones are left
+#[derive(Component)]
+pub struct Key {
+    pub index: usize,
+    pub color: KeyColor,
+}
+
+#[derive(Component)]
+pub struct Door {
+    pub index: usize,
+    pub color: KeyColor,
+}
+
+#[derive(Component)]
+pub struct PressureSwitch {
+    pub index: usize,
+    // Set while the player stands on it, so it only toggles once per step
+    pub pressed: bool,
+}
+
+// Closed gates carry a Wall so everything collides with them like with any other wall
+#[derive(Component)]
+pub struct Gate {
+    pub index: usize,
+    pub switch: usize,
+    pub open: bool,
+}
+
+// Keys picked up this run, they open every door of their color
+#[derive(Resource, Default)]
+pub struct HeldKeys(pub Vec<KeyColor>);
+
+impl HeldKeys {
+    pub fn contains(&self, color: KeyColor) -> bool {
+        self.0.contains(&color)
+    }
+}
+
+#[derive(Component)]
+pub struct KeyUi;
+
+pub fn spawn_keys(commands: &mut Commands, keys: &[LevelKey]) {
+    for (index, key) in keys.iter().enumerate() {
+        commands.spawn((
+            SpriteBundle {
+                transform: Transform {
+                    translation: key.position.extend(0.),
+                    // Turned on its corner to tell it apart from coins
+                    rotation: Quat::from_rotation_z(std::f32::consts::FRAC_PI_4),
+                    scale: KEY_SIZE.extend(1.),
+                },
+                sprite: Sprite {
+                    color: key.color.color(),
+                    ..default()
+                },
+                ..default()
+            },
+            Key { index, color: key.color },
+            Floor(key.floor),
+            HiddenInFog,
+            OnGameScreen,
+        ));
+    }
+}
+
+pub fn spawn_doors(commands: &mut Commands, topology: MazeTopology, doors: &[LevelDoor]) {
+    for (index, door) in doors.iter().enumerate() {
+        let [start_x, start_y, end_x, end_y] = door.segment;
+
+        commands.spawn((
+            SpriteBundle {
+                transform: wall_transform(topology, start_x, start_y, end_x, end_y),
+                sprite: Sprite {
+                    color: door.color.color(),
+                    ..default()
+                },
+                ..default()
+            },
+            Door { index, color: door.color },
+            Wall,
+            Collider,
+            Floor(door.floor),
+            OnGameScreen,
+        ));
+    }
+}
+
+pub fn spawn_switches(commands: &mut Commands, switches: &[LevelSwitch]) {
+    for (index, switch) in switches.iter().enumerate() {
+        commands.spawn((
+            SpriteBundle {
+                transform: Transform {
+                    // Drawn underneath the player and enemies walking over it
+                    translation: switch.position.extend(-0.5),
+                    scale: SWITCH_SIZE.extend(1.),
+                    ..default()
+                },
+                sprite: Sprite {
+                    color: SWITCH_COLOR,
+                    ..default()
+                },
+                ..default()
+            },
+            PressureSwitch { index, pressed: false },
+            Floor(switch.floor),
+            OnGameScreen,
+        ));
+    }
+}
+
+pub fn spawn_gates(commands: &mut Commands, topology: MazeTopology, gates: &[LevelGate]) {
+    for (index, gate) in gates.iter().enumerate() {
+        let [start_x, start_y, end_x, end_y] = gate.segment;
+
+        let mut entity = commands.spawn((
+            SpriteBundle {
+                transform: wall_transform(topology, start_x, start_y, end_x, end_y),
+                sprite: Sprite {
+                    color: gate_color(gate.open),
+                    ..default()
+                },
+                ..default()
+            },
+            Gate { index, switch: gate.switch, open: gate.open },
+            Collider,
+            Floor(gate.floor),
+            OnGameScreen,
+        ));
+
+        if !gate.open {
+            entity.insert(Wall);
+        }
+    }
+}
+
+fn gate_color(open: bool) -> Color {
+    if open { GATE_OPEN_COLOR } else { GATE_CLOSED_COLOR }
+}
+
+// Only levels with keys show the ones the player holds
+pub fn spawn_key_ui(commands: &mut Commands, keys: &[LevelKey]) {
+    if keys.is_empty() { return };
+
+    commands.spawn((
+        KeyUi,
+        OnGameScreen,
+        TextBundle::from_section(
+            "Keys:",
+            TextStyle {
+                font_size: 20.,
+                color: KEY_TEXT_COLOR,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(80.),
+            left: Val::Px(5.),
+            ..default()
+        }),
+    ));
+}
+
+pub fn reset_held_keys(mut held_keys: ResMut<HeldKeys>) {
+    *held_keys = HeldKeys::default();
+}
+
+fn player_bounding_box(player_transform: &Transform) -> Aabb2d {
+    Aabb2d::new(player_transform.translation.truncate(), player_transform.scale.truncate() / 2.)
+}
+
+// Keys are shared, whichever player picks one up it opens its doors for both
+pub fn pick_up_keys(
+    mut commands: Commands,
+    mut held_keys: ResMut<HeldKeys>,
+    player_query: Query<&Transform, With<Player>>,
+    keys_query: Query<(Entity, &Transform, &Key, &Floor)>,
+    current_floor: Res<CurrentFloor>,
+) {
+    for (key_entity, key_transform, key, _) in keys_query.iter().filter(|(_, _, _, floor)| current_floor.contains(floor)) {
+        let key_bounding_box = Aabb2d::new(key_transform.translation.truncate(), KEY_SIZE / 2.);
+
+        if player_query.iter().any(|player_transform| key_bounding_box.intersects(&player_bounding_box(player_transform))) {
+            if !held_keys.contains(key.color) {
+                held_keys.0.push(key.color);
+            }
+            commands.entity(key_entity).despawn();
+        }
+    }
+}
+
+// Runs before wall collisions, while the player still overlaps the door they walked into
+pub fn open_doors(
+    mut commands: Commands,
+    held_keys: Res<HeldKeys>,
+    player_query: Query<&Transform, With<Player>>,
+    doors_query: Query<(Entity, &Transform, &Door, &Floor), Without<Player>>,
+    current_floor: Res<CurrentFloor>,
+) {
+    for (door_entity, door_transform, door, _) in doors_query.iter().filter(|(_, _, _, floor)| current_floor.contains(floor)) {
+        let walked_into = player_query
+            .iter()
+            .any(|player_transform| wall_push_direction(&player_bounding_box(player_transform), door_transform).is_some());
+
+        if held_keys.contains(door.color) && walked_into {
+            commands.entity(door_entity).despawn();
+        }
+    }
+}
+
+// A switch stays pressed while either player stands on it
+pub fn press_switches(
+    player_query: Query<&Transform, With<Player>>,
+    mut switches_query: Query<(&Transform, &mut PressureSwitch, &mut Sprite, &Floor), Without<Gate>>,
+    mut gates_query: Query<&mut Gate>,
+    current_floor: Res<CurrentFloor>,
+) {
+    for (switch_transform, mut switch, mut switch_sprite, _) in switches_query.iter_mut().filter(|(_, _, _, floor)| current_floor.contains(floor)) {
+        let switch_bounding_box = Aabb2d::new(switch_transform.translation.truncate(), SWITCH_SIZE / 2.);
+        let touching = player_query
+            .iter()
+            .any(|player_transform| switch_bounding_box.intersects(&player_bounding_box(player_transform)));
+
+        if touching && !switch.pressed {
+            for mut gate in gates_query.iter_mut().filter(|gate| gate.switch == switch.index) {
+                gate.open = !gate.open;
+            }
+        }
+
+        switch.pressed = touching;
+        switch_sprite.color = if touching { SWITCH_PRESSED_COLOR } else { SWITCH_COLOR };
+    }
+}
+
+// Gates turn into walls when they close and stop being ones when they open
+pub fn update_gates(
+    mut commands: Commands,
+    mut gates_query: Query<(Entity, &Gate, &mut Sprite), Changed<Gate>>,
+) {
+    for (gate_entity, gate, mut gate_sprite) in &mut gates_query {
+        gate_sprite.color = gate_color(gate.open);
+
+        if gate.open {
+            commands.entity(gate_entity).remove::<Wall>();
+        } else {
+            commands.entity(gate_entity).insert(Wall);
+        }
+    }
+}
+
+pub fn update_key_ui(
+    held_keys: Res<HeldKeys>,
+    mut key_ui_query: Query<&mut Text, With<KeyUi>>,
+) {
+    if !held_keys.is_changed() { return };
+
+    for mut text in &mut key_ui_query {
+        text.sections.truncate(1);
+        text.sections.extend(held_keys.0.iter().map(|color| {
+            TextSection::new(
+                format!(" {}", color.name()),
+                TextStyle {
+                    font_size: 20.,
+                    color: color.color(),
+                    ..default()
+                },
+            )
+        }));
+    }
+}
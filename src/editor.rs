@@ -0,0 +1,478 @@
+use bevy::{
+    prelude::*,
+    window::PrimaryWindow,
+};
+
+use crate::game_state::GameState;
+use crate::level::{
+    CurrentLevel,
+    Level,
+    LevelEntry,
+    Levels,
+    custom_level_path,
+};
+use crate::level_validator::validate_level;
+use crate::maze::{CellMaze, MazeGrid, MAZE_CELL_SIZE};
+use crate::save_data::storage;
+
+const GRID_COLOR: Color = Color::srgba(1., 1., 1., 0.08);
+const EDITOR_WALL_COLOR: Color = Color::srgb(0.8, 0.8, 0.8);
+const HOVERED_EDGE_COLOR: Color = Color::srgb(1., 0.85, 0.2);
+const PLAYER_START_COLOR: Color = Color::srgb(0.3, 0.55, 1.);
+const EDITOR_FINISH_AREA_COLOR: Color = Color::srgb(0., 0.8, 0.);
+const EDITOR_COIN_COLOR: Color = Color::srgb(0.9, 0.8, 0.);
+const EDITOR_ENEMY_COLOR: Color = Color::srgb(0.9, 0.2, 0.2);
+const EDITOR_SWITCH_COLOR: Color = Color::srgb(0.9, 0.6, 0.1);
+
+// How close the cursor has to be to grab an item or toggle a wall, in world units
+const ITEM_GRAB_DISTANCE: f32 = 12.;
+const EDGE_GRAB_DISTANCE: f32 = MAZE_CELL_SIZE / 4.;
+const NEW_LEVEL_SIZE: usize = 20;
+
+#[derive(Component)]
+pub struct OnEditorScreen;
+
+#[derive(Component)]
+pub struct EditorStatusUi;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EditorItem {
+    PlayerStart,
+    FinishArea,
+    Coin(usize),
+    Enemy(usize),
+}
+
+
+// The level being edited, saved as a custom level under its id
+#[derive(Resource)]
+pub struct EditorLevel {
+    pub id: String,
+    pub level: Level,
+    pub dragging: Option<EditorItem>,
+    pub status: String,
+    // Why the level can only be looked at, edits to it would break what the editor does not show
+    pub read_only: Option<String>,
+}
+
+impl EditorLevel {
+    fn new(id: String, level: Level) -> EditorLevel {
+        let mut editor = EditorLevel {
+            status: String::new(),
+            id,
+            level: empty_level(""),
+            dragging: None,
+            read_only: None,
+        };
+        editor.set_level(level, "Editing");
+        editor
+    }
+
+    fn set_level(&mut self, level: Level, action: &str) {
+        self.read_only = uneditable_reason(&level);
+        self.status = match &self.read_only {
+            Some(reason) => format!("{reason}, press N for a new level"),
+            None => format!("{action} {}", self.id),
+        };
+        self.level = level;
+        self.dragging = None;
+    }
+
+    // Shows why nothing happened, true when the level can be changed
+    fn check_editable(&mut self) -> bool {
+        if let Some(reason) = &self.read_only {
+            self.status = format!("{reason}, press N for a new level");
+        }
+        self.read_only.is_none()
+    }
+
+    fn item_position(&self, item: EditorItem) -> Option<Vec2> {
+        match item {
+            EditorItem::PlayerStart => Some(self.level.player_start),
+            EditorItem::FinishArea => Some(self.level.finish_area_center()),
+            EditorItem::Coin(index) => self.level.coins.get(index).copied(),
+            EditorItem::Enemy(index) => self.level.enemies.get(index).copied(),
+        }
+    }
+
+    fn set_item_position(&mut self, item: EditorItem, position: Vec2) {
+        match item {
+            EditorItem::PlayerStart => self.level.player_start = position,
+            EditorItem::FinishArea => {
+                self.level.finish_area_location = position - self.level.finish_area_size / 2.;
+            }
+            EditorItem::Coin(index) => self.level.coins[index] = position,
+            EditorItem::Enemy(index) => self.level.enemies[index] = position,
+        }
+    }
+
+    fn item_at(&self, position: Vec2) -> Option<EditorItem> {
+        let coins = (0..self.level.coins.len()).map(EditorItem::Coin);
+        let enemies = (0..self.level.enemies.len()).map(EditorItem::Enemy);
+
+        // Small items first so they can still be picked up when lying on the start or finish
+        coins
+            .chain(enemies)
+            .chain([EditorItem::PlayerStart, EditorItem::FinishArea])
+            .find(|item| {
+                self.item_position(*item)
+                    .is_some_and(|item_position| item_position.distance(position) < ITEM_GRAB_DISTANCE)
+            })
+    }
+
+    // Edges are numbered as in the level's CellMaze, which works for every topology
+    fn toggle_wall(&mut self, edge: usize) {
+        let mut maze = self.level.cell_maze();
+
+        maze.set_wall(edge, !maze.edges()[edge].wall);
+        self.level.walls = maze.segments();
+    }
+
+    fn remove_item(&mut self, item: EditorItem) {
+        match item {
+            EditorItem::Coin(index) => {
+                self.level.coins.remove(index);
+            }
+            EditorItem::Enemy(index) => {
+                self.level.enemies.remove(index);
+            }
+            // Every level needs a start and a finish, they can only be moved
+            EditorItem::PlayerStart | EditorItem::FinishArea => {}
+        }
+    }
+}
+
+// The editor changes the ground floor's walls and items only, moving them around a lock could seal it off
+fn uneditable_reason(level: &Level) -> Option<String> {
+    if level.floor_count() > 1 {
+        return Some(format!("{} has {} floors, only single floor levels can be edited", level.name, level.floor_count()));
+    }
+
+    let locked = !level.keys.is_empty() || !level.doors.is_empty() || !level.switches.is_empty() || !level.gates.is_empty();
+    locked.then(|| format!("{} has doors or gates, only levels without locks can be edited", level.name))
+}
+
+// Levels from the campaign are never overwritten, edits to them are saved as a custom copy
+fn editable_id(entry: Option<&LevelEntry>, id: &str) -> String {
+    match entry {
+        Some(entry) if entry.campaign => format!("{id}_custom"),
+        _ => id.to_string(),
+    }
+}
+
+fn empty_level(name: &str) -> Level {
+    let mut grid = MazeGrid::new(NEW_LEVEL_SIZE, NEW_LEVEL_SIZE);
+    grid.close_border();
+
+    Level::from_grid(name, &grid, UVec2::ZERO, UVec2::splat(NEW_LEVEL_SIZE as u32 - 1))
+}
+
+fn snap_to_cell(level: &Level, position: Vec2) -> Vec2 {
+    let maze = level.cell_maze();
+    let cell = maze.cell_at(position).unwrap_or_else(|| maze.nearest_cell(position));
+
+    maze.cell_center(cell)
+}
+
+// The cell edge closest to the cursor, if it is close enough to one
+fn edge_at(maze: &CellMaze, position: Vec2) -> Option<usize> {
+    let distance = |[start_x, start_y, end_x, end_y]: [f32; 4]| {
+        let (start, end) = (Vec2::new(start_x, start_y), Vec2::new(end_x, end_y));
+        let along = (position - start).dot(end - start) / start.distance_squared(end);
+        position.distance(start.lerp(end, along.clamp(0., 1.)))
+    };
+
+    (0..maze.edges().len())
+        .map(|edge| (edge, distance(maze.edges()[edge].segment)))
+        .filter(|(_, distance)| *distance <= EDGE_GRAB_DISTANCE)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(edge, _)| edge)
+}
+
+fn cursor_world_position(
+    window_query: &Query<&Window, With<PrimaryWindow>>,
+    camera_query: &Query<(&Camera, &GlobalTransform)>,
+) -> Option<Vec2> {
+    let cursor = window_query.get_single().ok()?.cursor_position()?;
+    let (camera, camera_transform) = camera_query.get_single().ok()?;
+
+    camera.viewport_to_world_2d(camera_transform, cursor)
+}
+
+pub fn editor_setup(
+    mut commands: Commands,
+    current_level: Res<CurrentLevel>,
+    levels: Res<Levels>,
+) {
+    let id = editable_id(levels.get(&current_level.id), &current_level.id);
+
+    commands.insert_resource(EditorLevel::new(id, current_level.level.clone()));
+
+    commands.spawn((
+        OnEditorScreen,
+        TextBundle::from_section(
+            "Click a cell edge to toggle a wall, drag the start, finish, coins and enemies.\n\
+             C: add coin, E: add enemy, right click: remove, V: validate, S: save, L: load, N: new, Escape: back",
+            TextStyle {
+                font_size: 18.,
+                color: Color::srgb(80., 80., 80.),
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(5.),
+            left: Val::Px(5.),
+            ..default()
+        }),
+    ));
+
+    commands.spawn((
+        OnEditorScreen,
+        EditorStatusUi,
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 20.,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(5.),
+            left: Val::Px(5.),
+            ..default()
+        }),
+    ));
+}
+
+pub fn editor_cleanup(mut commands: Commands) {
+    commands.remove_resource::<EditorLevel>();
+}
+
+pub fn editor_mouse_input(
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    mut editor: ResMut<EditorLevel>,
+) {
+    let Some(cursor) = cursor_world_position(&window_query, &camera_query) else {
+        return;
+    };
+
+    if mouse_input.any_just_pressed([MouseButton::Left, MouseButton::Right]) && !editor.check_editable() {
+        return;
+    }
+
+    if mouse_input.just_pressed(MouseButton::Left) {
+        if let Some(item) = editor.item_at(cursor) {
+            editor.dragging = Some(item);
+        } else if let Some(edge) = edge_at(&editor.level.cell_maze(), cursor) {
+            editor.toggle_wall(edge);
+        }
+    }
+
+    if let Some(item) = editor.dragging {
+        if mouse_input.pressed(MouseButton::Left) {
+            editor.set_item_position(item, cursor);
+        } else {
+            // Dropped items land in the middle of the cell under the cursor
+            let snapped = snap_to_cell(&editor.level, cursor);
+            editor.set_item_position(item, snapped);
+            editor.dragging = None;
+        }
+    }
+
+    if mouse_input.just_pressed(MouseButton::Right) && editor.dragging.is_none() {
+        if let Some(item) = editor.item_at(cursor) {
+            editor.remove_item(item);
+        }
+    }
+}
+
+pub fn editor_keyboard_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    mut editor: ResMut<EditorLevel>,
+    mut levels: ResMut<Levels>,
+    mut current_level: ResMut<CurrentLevel>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    let cursor = cursor_world_position(&window_query, &camera_query);
+    // Nothing gets added to or saved from a level that cannot be edited
+    let editing = keyboard_input.any_just_pressed([KeyCode::KeyC, KeyCode::KeyE, KeyCode::KeyS]);
+    let editable = !editing || editor.check_editable();
+
+    if let Some(cursor) = cursor.filter(|_| editable) {
+        if keyboard_input.just_pressed(KeyCode::KeyC) {
+            let position = snap_to_cell(&editor.level, cursor);
+            editor.level.coins.push(position);
+        }
+
+        if keyboard_input.just_pressed(KeyCode::KeyE) {
+            let position = snap_to_cell(&editor.level, cursor);
+            editor.level.enemies.push(position);
+        }
+    }
+
+    if keyboard_input.just_pressed(KeyCode::KeyV) {
+        let problems: Vec<String> = validate_level(&editor.level)
+            .iter()
+            .map(|problem| problem.to_string())
+            .collect();
+
+        editor.status = if problems.is_empty() {
+            "No problems found".to_string()
+        } else {
+            problems.join(", ")
+        };
+    }
+
+    if keyboard_input.just_pressed(KeyCode::KeyS) && editable {
+        let result = editor
+            .level
+            .to_ron()
+            .map_err(|error| error.to_string())
+            .and_then(|contents| storage::write(&custom_level_path(&editor.id), &contents));
+
+        editor.status = match result {
+            Ok(()) => {
+                let entry = LevelEntry {
+                    id: editor.id.clone(),
+                    level: editor.level.clone(),
+                    campaign: false,
+                };
+                *current_level = CurrentLevel::from(&entry);
+                levels.insert(entry);
+                format!("Saved {}", editor.id)
+            }
+            Err(error) => format!("Could not save {}: {error}", editor.id),
+        };
+    }
+
+    if keyboard_input.just_pressed(KeyCode::KeyL) {
+        let contents = storage::read(&custom_level_path(&editor.id));
+
+        match contents.map(|contents| Level::from_ron(&contents)) {
+            Some(Ok(level)) => editor.set_level(level, "Loaded"),
+            Some(Err(error)) => editor.status = format!("Could not read {}: {error}", editor.id),
+            None => editor.status = format!("{} has not been saved yet", editor.id),
+        };
+    }
+
+    if keyboard_input.just_pressed(KeyCode::KeyN) {
+        let id = (1..)
+            .map(|number| format!("custom_{number}"))
+            .find(|id| levels.get(id).is_none())
+            .unwrap_or_default();
+
+        editor.id = id.clone();
+        editor.set_level(empty_level(&id), "New level");
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        game_state.set(GameState::Menu);
+    }
+}
+
+pub fn draw_editor(
+    mut gizmos: Gizmos,
+    editor: Res<EditorLevel>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+) {
+    let level = &editor.level;
+    let maze = level.cell_maze();
+
+    // Every cell edge faintly, so open edges of any topology can be found and clicked
+    for edge in maze.edges() {
+        let [start_x, start_y, end_x, end_y] = edge.segment;
+        gizmos.line_2d(Vec2::new(start_x, start_y), Vec2::new(end_x, end_y), GRID_COLOR);
+    }
+
+    for [start_x, start_y, end_x, end_y] in &level.walls {
+        gizmos.line_2d(Vec2::new(*start_x, *start_y), Vec2::new(*end_x, *end_y), EDITOR_WALL_COLOR);
+    }
+
+    gizmos.rect_2d(level.finish_area_center(), 0., level.finish_area_size, EDITOR_FINISH_AREA_COLOR);
+    gizmos.circle_2d(level.player_start, 6., PLAYER_START_COLOR);
+
+    for coin in &level.coins {
+        gizmos.circle_2d(*coin, 4., EDITOR_COIN_COLOR);
+    }
+
+    for enemy in &level.enemies {
+        gizmos.rect_2d(*enemy, 0., Vec2::splat(10.), EDITOR_ENEMY_COLOR);
+    }
+
+    // Locks only show which level is read only and why, they cannot be changed here
+    for key in level.keys.iter().filter(|key| key.floor == 0) {
+        gizmos.circle_2d(key.position, 5., key.color.color());
+    }
+
+    for door in level.doors.iter().filter(|door| door.floor == 0) {
+        let [start_x, start_y, end_x, end_y] = door.segment;
+        gizmos.line_2d(Vec2::new(start_x, start_y), Vec2::new(end_x, end_y), door.color.color());
+    }
+
+    for switch in level.switches.iter().filter(|switch| switch.floor == 0) {
+        gizmos.rect_2d(switch.position, 0., Vec2::splat(8.), EDITOR_SWITCH_COLOR);
+    }
+
+    for gate in level.gates.iter().filter(|gate| gate.floor == 0) {
+        let [start_x, start_y, end_x, end_y] = gate.segment;
+        gizmos.line_2d(Vec2::new(start_x, start_y), Vec2::new(end_x, end_y), EDITOR_SWITCH_COLOR);
+    }
+
+    let hovered_edge = cursor_world_position(&window_query, &camera_query)
+        .filter(|cursor| editor.dragging.is_none() && editor.item_at(*cursor).is_none())
+        .and_then(|cursor| edge_at(&maze, cursor));
+
+    if let Some(edge) = hovered_edge {
+        let [start_x, start_y, end_x, end_y] = maze.edges()[edge].segment;
+        gizmos.line_2d(Vec2::new(start_x, start_y), Vec2::new(end_x, end_y), HOVERED_EDGE_COLOR);
+    }
+}
+
+pub fn update_editor_status_ui(
+    editor: Res<EditorLevel>,
+    mut status_query: Query<&mut Text, With<EditorStatusUi>>,
+) {
+    if !editor.is_changed() { return };
+
+    for mut text in &mut status_query {
+        text.sections[0].value = format!("{} ({}): {}", editor.level.name, editor.id, editor.status);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::{LevelFloor, LevelSwitch};
+
+    #[test]
+    fn levels_with_upper_floors_are_read_only() {
+        let mut level = empty_level("tower");
+        assert_eq!(EditorLevel::new("tower".to_string(), level.clone()).read_only, None);
+
+        level.upper_floors.push(LevelFloor::default());
+        let mut editor = EditorLevel::new("tower".to_string(), level);
+        let walls = editor.level.walls.clone();
+
+        assert!(editor.read_only.is_some());
+        assert!(!editor.check_editable());
+        assert!(editor.status.contains("2 floors"));
+        assert_eq!(editor.level.walls, walls);
+    }
+
+    #[test]
+    fn levels_with_locks_are_read_only() {
+        let mut level = empty_level("vault");
+        level.switches.push(LevelSwitch { position: level.player_start, floor: 0 });
+        let editor = EditorLevel::new("vault".to_string(), level);
+
+        assert!(editor.read_only.is_some());
+        assert!(editor.status.contains("doors or gates"));
+    }
+}
@@ -8,16 +8,23 @@ use rand::{
 };
 
 use crate::collider::Collision;
-use crate::walls::{Wall, wall_collision};
+use crate::fog::HiddenInFog;
+use crate::floors::{CurrentFloor, Floor};
+use crate::game_state::OnGameScreen;
+use crate::walls::{Wall, collision_side, wall_push_direction};
 use crate::player::player::Player;
 
 #[derive(Component)]
 pub struct Enemy;
 
 #[derive(Component, Deref, DerefMut)]
-pub struct Velocity(Vec2);
+pub struct Velocity(pub Vec2);
 
-const ENEMY_SIZE: Vec2 = Vec2::new(10., 10.,);
+// Sent once when an enemy catches the player
+#[derive(Event, Default)]
+pub struct PlayerHitEvent;
+
+pub const ENEMY_SIZE: Vec2 = Vec2::new(10., 10.,);
 const INITIAL_ENEMY_DIRECTION: Vec2 = Vec2::new(1., 0.);
 const ENEMY_SPEED: f32 = 50.;
 
@@ -47,15 +54,19 @@ pub fn apply_enemy_velocity(mut query: Query<(&mut Transform, &Velocity), With<E
     }
 }
 
-fn spawn_enemy(
+pub fn spawn_enemy(
     commands: &mut Commands,
     asset_server: &Res<AssetServer>,
     x_position: f32,
-    y_position: f32
+    y_position: f32,
+    velocity: Vec2,
+    floor: usize,
 ) {
+    let texture = if velocity.y > 0. { "enemy-back.png" } else { "enemy.png" };
+
     commands.spawn((
         SpriteBundle {
-            texture: asset_server.load("enemy.png"),
+            texture: asset_server.load(texture),
             transform: Transform {
                 translation: Vec3::new(x_position, y_position, 0.),
                 scale: ENEMY_SIZE.extend(1.0),
@@ -63,65 +74,51 @@ fn spawn_enemy(
             },
             sprite: Sprite {
                 custom_size: Some(Vec2::new(2., 2.)),
+                flip_x: velocity.x < 0.,
                 ..default()
             },
             ..default()
         },
         Enemy,
-        Velocity(Vec2::new(INITIAL_ENEMY_DIRECTION.x * ENEMY_SPEED, INITIAL_ENEMY_DIRECTION.y * ENEMY_SPEED)),
+        Velocity(velocity),
+        Floor(floor),
+        HiddenInFog,
+        OnGameScreen,
     ));
 }
 
 pub fn spawn_enemies(
     commands: &mut Commands,
     asset_server: &Res<AssetServer>,
+    positions: &[Vec2],
+    floor: usize,
 ) {
-    spawn_enemy(commands, asset_server, 80., 80.);
-    spawn_enemy(commands, asset_server, 120., 120.);
-    spawn_enemy(commands, asset_server, 250., 340.);
-    spawn_enemy(commands, asset_server, 440., 440.);
-    spawn_enemy(commands, asset_server, 80., 440.);
+    let velocity = INITIAL_ENEMY_DIRECTION * ENEMY_SPEED;
+
+    for position in positions {
+        spawn_enemy(commands, asset_server, position.x, position.y, velocity, floor);
+    }
 }
 
+#[allow(clippy::type_complexity, clippy::neg_multiply)]
 pub fn update_enemy_movement(
-    mut enemy_query: Query<(&mut Transform, &mut Velocity, &mut Handle<Image>, &mut Sprite), With<Enemy>>,
+    mut enemy_query: Query<(&mut Transform, &mut Velocity, &mut Handle<Image>, &mut Sprite, &Floor), With<Enemy>>,
     asset_server: Res<AssetServer>,
-    wall_collider_query: Query<&Transform, (With<Wall>, Without<Enemy>)>,
+    wall_collider_query: Query<(&Transform, &Floor), (With<Wall>, Without<Enemy>)>,
 ) {
-    for (mut enemy_transform, mut enemy_velocity, mut enemy_texture, mut enemy_sprite) in &mut enemy_query {
+    for (mut enemy_transform, mut enemy_velocity, mut enemy_texture, mut enemy_sprite, enemy_floor) in &mut enemy_query {
 
         let enemy_bounding_box = Aabb2d::new(
             enemy_transform.translation.truncate(), 
             enemy_transform.scale.truncate() / 2.,
         );
 
-        for wall_transform in &wall_collider_query {
-            let collision = wall_collision(
-                &enemy_bounding_box,
-                Aabb2d::new(
-                    wall_transform.translation.truncate(),
-                    wall_transform.scale.truncate() / 2.,
-                ),
-            );
-
-            if let Some(collision) = collision {
-                let mut reflect_x = 0.;
-                let mut reflect_y = 0.;
-
-                match collision {
-                    Collision::Left => reflect_x = -7.9,
-                    Collision::Right => reflect_x = 7.9,
-                    Collision::Top => reflect_y = 7.9,
-                    Collision::Bottom => reflect_y = -7.9,
-                }
-
-                if reflect_x != 0. {
-                    enemy_transform.translation.x = enemy_transform.translation.x + reflect_x;
-                }
+        // Enemies keep walking on every floor, but only ever bump into the walls of their own
+        for (wall_transform, _) in wall_collider_query.iter().filter(|(_, wall_floor)| *wall_floor == enemy_floor) {
+            if let Some(direction) = wall_push_direction(&enemy_bounding_box, wall_transform) {
+                let collision = collision_side(direction);
 
-                if reflect_y != 0. {
-                    enemy_transform.translation.y = enemy_transform.translation.y + reflect_y;
-                }
+                enemy_transform.translation += (direction * 7.9).extend(0.);
 
                 // Set velocity by random direction, but discourage going in the same direction & backtracking based collision
                 let random_direction: Direction = rand::random();
@@ -224,45 +221,59 @@ pub fn update_enemy_movement(
     }
 }
 
+// In co-op a caught player stays behind while the other plays on, it is game over once both are caught
 pub fn check_for_player_collisions_with_enemies(
     mut commands: Commands,
-    mut player_query: Query<(&mut Player, &Transform), With<Player>>,
-    enemies_query: Query<&Transform, With<Enemy>>,
+    mut player_query: Query<(&mut Player, &Transform)>,
+    enemies_query: Query<(&Transform, &Floor), With<Enemy>>,
+    current_floor: Res<CurrentFloor>,
+    mut hit_events: EventWriter<PlayerHitEvent>,
 ) {
-    let (mut player, player_transform) = player_query.single_mut();
-    let player_bounding_box = Aabb2d::new(
-        player_transform.translation.truncate(),
-        player_transform.scale.truncate() / 2.,
-    );
+    let mut caught = false;
 
-    for enemy_transform in &enemies_query {
-        let enemy_bounding_box = Aabb2d::new(
-            enemy_transform.translation.truncate(),
-            enemy_transform.scale.truncate() / 2.,
+    for (mut player, player_transform) in &mut player_query {
+        if !player.alive { continue };
+
+        let player_bounding_box = Aabb2d::new(
+            player_transform.translation.truncate(),
+            player_transform.scale.truncate() / 2.,
         );
 
-        if enemy_bounding_box.intersects(&player_bounding_box) {
-            player.alive = false;
+        let hit = enemies_query
+            .iter()
+            .filter(|(_, floor)| current_floor.contains(floor))
+            .any(|(enemy_transform, _)| {
+                Aabb2d::new(enemy_transform.translation.truncate(), enemy_transform.scale.truncate() / 2.)
+                    .intersects(&player_bounding_box)
+            });
 
-            commands.spawn((
-                TextBundle::from_sections([
-                    TextSection::new(
-                        "Game Over",
-                        TextStyle {
-                            font_size: 40.,
-                            color: Color::srgb(120., 0., 0.),
-                            ..default()
-                        },
-                    ),
-                ])
-                .with_style(Style {
-                    position_type: PositionType::Absolute,
-                    top: Val::Px(5.),
-                    right: Val::Px(5.),
-                    ..default()
-                }),
-            ));
+        if hit {
+            player.alive = false;
+            caught = true;
+            hit_events.send_default();
         }
     }
+
+    if caught && player_query.iter().all(|(player, _)| !player.alive) {
+        commands.spawn((
+            OnGameScreen,
+            TextBundle::from_sections([
+                TextSection::new(
+                    "Game Over",
+                    TextStyle {
+                        font_size: 40.,
+                        color: Color::srgb(120., 0., 0.),
+                        ..default()
+                    },
+                ),
+            ])
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(5.),
+                right: Val::Px(5.),
+                ..default()
+            }),
+        ));
+    }
 }
 
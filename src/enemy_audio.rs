@@ -0,0 +1,250 @@
+use bevy::{
+    audio::{PlaybackMode, SpatialScale, Volume},
+    prelude::*,
+};
+use rand::Rng;
+
+use crate::audio::{AudioBus, OnAudioBus, SfxPlugin, SynthSound};
+use crate::doors::{Door, Gate};
+use crate::enemy::Enemy;
+use crate::floors::{CurrentFloor, Floor};
+use crate::fog::segments_cross;
+use crate::game_state::{GameState, OnGameScreen};
+use crate::level::CurrentLevel;
+use crate::player::player::Player;
+use crate::save_data::SaveData;
+
+// Enemies are heard at full volume up to this many pixels away, four times quieter at twice the distance
+const HEARING_DISTANCE: f32 = 60.;
+// Distance between the listener's ears in pixels, sets how strongly sounds are panned
+const EAR_GAP: f32 = 24.;
+const FOOTSTEPS_GAIN: f32 = 0.5;
+const GROWL_GAIN: f32 = 0.8;
+// Muffled sounds are also quieter than the clear ones
+const MUFFLED_GAIN: f32 = 0.6;
+// How fast a voice changes between clear and muffled, in full changes per second
+const OCCLUSION_SPEED: f32 = 4.;
+const GROWL_SECONDS: std::ops::Range<f32> = 4.0..10.0;
+
+// Hears the enemies for the first player still in the game
+#[derive(Component)]
+pub struct EnemyAudioListener;
+
+// How much the walls between an enemy and the listener muffle it, from 0 for clear to 1 for muffled
+#[derive(Component)]
+pub struct EnemyHearing {
+    occlusion: f32,
+    growl_timer: Timer,
+}
+
+// Footsteps an enemy keeps making, each enemy has a clear and a muffled one and only the right one is heard
+#[derive(Component)]
+pub struct EnemyVoice {
+    muffled: bool,
+}
+
+#[derive(Resource)]
+pub struct EnemyAudioBank {
+    footsteps: Handle<SynthSound>,
+    muffled_footsteps: Handle<SynthSound>,
+    growl: Handle<SynthSound>,
+    muffled_growl: Handle<SynthSound>,
+}
+
+impl FromWorld for EnemyAudioBank {
+    fn from_world(world: &mut World) -> Self {
+        let mut sounds = world.resource_mut::<Assets<SynthSound>>();
+
+        EnemyAudioBank {
+            footsteps: sounds.add(SynthSound::footsteps()),
+            muffled_footsteps: sounds.add(SynthSound::footsteps().muffled()),
+            growl: sounds.add(SynthSound::growl()),
+            muffled_growl: sounds.add(SynthSound::growl().muffled()),
+        }
+    }
+}
+
+// Positional footsteps and growls for every enemy
+pub struct EnemyAudioPlugin;
+
+impl Plugin for EnemyAudioPlugin {
+    fn build(&self, app: &mut App) {
+        assert!(app.is_plugin_added::<SfxPlugin>(), "EnemyAudioPlugin plays synthesized sounds, add SfxPlugin before it");
+
+        app.init_resource::<EnemyAudioBank>()
+            .add_systems(OnEnter(GameState::Game), spawn_enemy_audio_listener)
+            .add_systems(
+                Update,
+                (add_enemy_voices, move_enemy_audio_listener, update_enemy_voices, growl)
+                    .chain()
+                    .run_if(in_state(GameState::Game)),
+            );
+    }
+}
+
+fn spatial_settings(mode: PlaybackMode, volume: f32) -> PlaybackSettings {
+    PlaybackSettings {
+        mode,
+        volume: Volume::new(volume),
+        spatial: true,
+        spatial_scale: Some(SpatialScale::new_2d(1. / HEARING_DISTANCE)),
+        ..default()
+    }
+}
+
+// A listener of its own, since the players are scaled up and that would spread their ears apart as well
+pub fn spawn_enemy_audio_listener(mut commands: Commands) {
+    commands.spawn((
+        EnemyAudioListener,
+        SpatialListener::new(EAR_GAP),
+        TransformBundle::default(),
+        OnGameScreen,
+    ));
+}
+
+pub fn add_enemy_voices(
+    mut commands: Commands,
+    enemy_audio_bank: Res<EnemyAudioBank>,
+    enemy_query: Query<Entity, Added<Enemy>>,
+) {
+    let mut rng = rand::thread_rng();
+
+    for enemy in &enemy_query {
+        commands
+            .entity(enemy)
+            .insert(EnemyHearing {
+                occlusion: 0.,
+                growl_timer: Timer::from_seconds(rng.gen_range(GROWL_SECONDS), TimerMode::Once),
+            })
+            .with_children(|parent| {
+                for (muffled, footsteps) in [
+                    (false, &enemy_audio_bank.footsteps),
+                    (true, &enemy_audio_bank.muffled_footsteps),
+                ] {
+                    parent.spawn((
+                        AudioSourceBundle {
+                            source: footsteps.clone(),
+                            settings: spatial_settings(PlaybackMode::Loop, 0.),
+                        },
+                        SpatialBundle::default(),
+                        EnemyVoice { muffled },
+                        OnAudioBus { bus: AudioBus::Sfx, gain: 0. },
+                    ));
+                }
+            });
+    }
+}
+
+// Stays where the last player was caught once nobody is left
+pub fn move_enemy_audio_listener(
+    player_query: Query<(&Transform, &Player), Without<EnemyAudioListener>>,
+    mut listener_query: Query<&mut Transform, With<EnemyAudioListener>>,
+) {
+    let Some((player_transform, _)) = player_query
+        .iter()
+        .filter(|(_, player)| player.alive)
+        .min_by_key(|(_, player)| player.index)
+    else {
+        return;
+    };
+
+    for mut listener_transform in &mut listener_query {
+        listener_transform.translation = player_transform.translation.truncate().extend(0.);
+    }
+}
+
+// Whether a wall, or a door or gate still closed, of the floor stands between the listener and the position
+fn behind_wall(current_level: &CurrentLevel, locks: &[(usize, [f32; 4])], floor: usize, listener: Vec2, position: Vec2) -> bool {
+    let lock_segments = locks.iter().filter(|(lock_floor, _)| *lock_floor == floor).map(|(_, segment)| segment);
+
+    current_level.level.floor_walls(floor).iter().chain(lock_segments).any(|[start_x, start_y, end_x, end_y]| {
+        segments_cross(listener, position, Vec2::new(*start_x, *start_y), Vec2::new(*end_x, *end_y))
+    })
+}
+
+// Enemies on other floors are not heard at all, those behind a wall fade over to their muffled voice
+#[allow(clippy::too_many_arguments)]
+pub fn update_enemy_voices(
+    time: Res<Time>,
+    save_data: Res<SaveData>,
+    current_level: Res<CurrentLevel>,
+    current_floor: Res<CurrentFloor>,
+    listener_query: Query<&Transform, With<EnemyAudioListener>>,
+    door_query: Query<&Door>,
+    gate_query: Query<&Gate>,
+    mut enemy_query: Query<(&Transform, &Floor, &mut EnemyHearing, &Children), With<Enemy>>,
+    mut voice_query: Query<(&EnemyVoice, &mut OnAudioBus, Option<&SpatialAudioSink>)>,
+) {
+    let Ok(listener_transform) = listener_query.get_single() else {
+        return;
+    };
+    let listener = listener_transform.translation.truncate();
+    let step = time.delta_seconds() * OCCLUSION_SPEED;
+
+    // Opened doors are despawned, opened gates stay around
+    let level = &current_level.level;
+    let locks: Vec<(usize, [f32; 4])> = door_query
+        .iter()
+        .filter_map(|door| level.doors.get(door.index).map(|door| (door.floor, door.segment)))
+        .chain(
+            gate_query
+                .iter()
+                .filter(|gate| !gate.open)
+                .filter_map(|gate| level.gates.get(gate.index).map(|gate| (gate.floor, gate.segment))),
+        )
+        .collect();
+
+    for (enemy_transform, enemy_floor, mut hearing, children) in &mut enemy_query {
+        let heard = current_floor.contains(enemy_floor);
+        let target = if heard && behind_wall(&current_level, &locks, **enemy_floor, listener, enemy_transform.translation.truncate()) { 1. } else { 0. };
+        hearing.occlusion += (target - hearing.occlusion).clamp(-step, step);
+
+        let mut voices = voice_query.iter_many_mut(children);
+        while let Some((voice, mut bus, sink)) = voices.fetch_next() {
+            let share = if voice.muffled { hearing.occlusion * MUFFLED_GAIN } else { 1. - hearing.occlusion };
+            bus.gain = if heard { share * FOOTSTEPS_GAIN } else { 0. };
+
+            if let Some(sink) = sink {
+                sink.set_volume(bus.volume(&save_data.settings));
+            }
+        }
+    }
+}
+
+// Every enemy growls now and then, muffled when it is behind a wall at the time. Growls are short
+// enough to stay where the enemy was when it started.
+pub fn growl(
+    mut commands: Commands,
+    time: Res<Time>,
+    save_data: Res<SaveData>,
+    enemy_audio_bank: Res<EnemyAudioBank>,
+    current_floor: Res<CurrentFloor>,
+    mut enemy_query: Query<(&Transform, &Floor, &mut EnemyHearing), With<Enemy>>,
+) {
+    let mut rng = rand::thread_rng();
+
+    for (enemy_transform, enemy_floor, mut hearing) in &mut enemy_query {
+        hearing.growl_timer.tick(time.delta());
+        if !hearing.growl_timer.finished() { continue };
+        hearing.growl_timer = Timer::from_seconds(rng.gen_range(GROWL_SECONDS), TimerMode::Once);
+
+        if !current_floor.contains(enemy_floor) { continue };
+
+        let muffled = hearing.occlusion > 0.5;
+        let bus = OnAudioBus {
+            bus: AudioBus::Sfx,
+            gain: GROWL_GAIN * if muffled { MUFFLED_GAIN } else { 1. },
+        };
+        let source = if muffled { &enemy_audio_bank.muffled_growl } else { &enemy_audio_bank.growl };
+
+        commands.spawn((
+            AudioSourceBundle {
+                source: source.clone(),
+                settings: spatial_settings(PlaybackMode::Despawn, bus.volume(&save_data.settings)),
+            },
+            SpatialBundle::from_transform(Transform::from_translation(enemy_transform.translation)),
+            bus,
+            OnGameScreen,
+        ));
+    }
+}
@@ -2,23 +2,34 @@ use bevy::{
     math::bounding::{Aabb2d, IntersectsVolume},
     prelude::*,
 };
+use std::cmp::Ordering;
 
 use crate::player::player::Player;
 use crate::collider::Collider;
+use crate::floors::{CurrentFloor, Floor};
+use crate::game_state::OnGameScreen;
+use crate::save_data::SaveData;
+use crate::settings::{CoopFinish, CoopScoring};
+
+pub const FINISH_AREA_COLOR: Color = Color::srgb(0., 115., 0.);
 
-const FINISH_AREA_COLOR: Color = Color::srgb(0., 115., 0.);
 
 #[derive(Component)]
-struct WinningMessage;
+pub struct WinningMessage;
 
 #[derive(Component)]
 pub struct FinishArea;
 
+#[derive(Event, Default)]
+pub struct FinishEvent;
+
 #[derive(Bundle)]
 pub struct FinishAreaBundle {
     sprite_bundle: SpriteBundle,
     collider: Collider,
     finish_area: FinishArea,
+    floor: Floor,
+    on_game_screen: OnGameScreen,
 }
 
 impl FinishAreaBundle {
@@ -27,6 +38,7 @@ impl FinishAreaBundle {
         location_start_y: f32,
         size_x: f32,
         size_y: f32,
+        floor: usize,
     ) -> FinishAreaBundle {
         
         /*
@@ -51,37 +63,86 @@ impl FinishAreaBundle {
                 ..default()
             },
             finish_area: FinishArea,
+            floor: Floor(floor),
             collider: Collider,
+            on_game_screen: OnGameScreen,
         }
     }
 }
 
-pub fn finish_area_collision(
-    commands: &mut Commands,
-    player_bounding_box: &Aabb2d,
-    finish_area_collider_query: &Query<&Transform, (With<FinishArea>, Without<Player>)>
+/*
+ * Marks every player that reaches the finish area. The level is won once the co-op finish rule is
+ * met, which with a single player is as soon as they get there.
+ */
+#[allow(clippy::type_complexity)]
+pub fn check_for_players_in_finish_area(
+    mut commands: Commands,
+    mut player_query: Query<(&mut Player, &Transform)>,
+    finish_area_collider_query: Query<(&Transform, &Floor), (With<FinishArea>, Without<Player>)>,
+    winning_message_query: Query<(), With<WinningMessage>>,
+    current_floor: Res<CurrentFloor>,
+    save_data: Res<SaveData>,
+    mut finish_events: EventWriter<FinishEvent>,
 ) {
-    for finish_area_transform in finish_area_collider_query {
-        let finish_area_bounding_box = Aabb2d::new(
-            finish_area_transform.translation.truncate(),
-            finish_area_transform.scale.truncate() / 2.,
+    if !winning_message_query.is_empty() { return };
+
+    for (mut player, player_transform) in &mut player_query {
+        if !player.alive || player.finished { continue };
+
+        let player_bounding_box = Aabb2d::new(
+            player_transform.translation.truncate(),
+            player_transform.scale.truncate() / 2.,
         );
-        if player_bounding_box.intersects(&finish_area_bounding_box) {
-            spawn_winning_message(commands);
-        }
+
+        player.finished = finish_area_collider_query
+            .iter()
+            .filter(|(_, floor)| current_floor.contains(floor))
+            .any(|(finish_area_transform, _)| {
+                Aabb2d::new(finish_area_transform.translation.truncate(), finish_area_transform.scale.truncate() / 2.)
+                    .intersects(&player_bounding_box)
+            });
     }
+
+    let settings = &save_data.settings;
+    let anyone_finished = player_query.iter().any(|(player, _)| player.finished);
+    let won = match settings.coop_finish {
+        CoopFinish::First => anyone_finished,
+        // Players who were caught no longer hold up the others
+        CoopFinish::Both => anyone_finished && player_query.iter().all(|(player, _)| player.finished || !player.alive),
+    };
+
+    if !won { return };
+
+    finish_events.send_default();
+
+    let message = if settings.coop_scoring == CoopScoring::Competitive && player_query.iter().count() > 1 {
+        let mut coins = [0; 2];
+        for (player, _) in &player_query {
+            coins[player.index.min(1)] = player.coins;
+        }
+
+        match coins[0].cmp(&coins[1]) {
+            Ordering::Greater => "Player 1 Wins!",
+            Ordering::Less => "Player 2 Wins!",
+            Ordering::Equal => "It's a Draw!",
+        }
+    } else {
+        "You Win!"
+    };
+    spawn_winning_message(&mut commands, message);
 }
 
-pub fn spawn_finish_area(commands: &mut Commands) {
-    commands.spawn(FinishAreaBundle::new(575., 580., 25., 25.));
+pub fn spawn_finish_area(commands: &mut Commands, location: Vec2, size: Vec2, floor: usize) {
+    commands.spawn(FinishAreaBundle::new(location.x, location.y, size.x, size.y, floor));
 }
 
-fn spawn_winning_message(commands: &mut Commands) {
+fn spawn_winning_message(commands: &mut Commands, message: &str) {
     commands.spawn((
         WinningMessage,
+        OnGameScreen,
         TextBundle::from_sections([
             TextSection::new(
-                "You Win!",
+                message,
                 TextStyle {
                     font_size: 40.,
                     color: Color::srgb(0., 120., 0.),
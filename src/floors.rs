@@ -0,0 +1,157 @@
+use bevy::{
+    math::bounding::{Aabb2d, IntersectsVolume},
+    prelude::*,
+};
+
+use crate::fog::{FogOfWar, HiddenInFog};
+use crate::game_state::OnGameScreen;
+use crate::level::{CurrentLevel, Stairs};
+use crate::player::player::Player;
+
+const STAIRS_UP_COLOR: Color = Color::srgb(0.55, 0.35, 0.15);
+const STAIRS_DOWN_COLOR: Color = Color::srgb(0.3, 0.2, 0.1);
+const STAIRS_SIZE: Vec2 = Vec2::new(16., 16.);
+const FLOOR_TEXT_COLOR: Color = Color::srgb(80., 80., 80.);
+
+// Floor an entity belongs to, it is only drawn and collided with while the player is on it
+#[derive(Component, Deref, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Floor(pub usize);
+
+#[derive(Resource, Default)]
+pub struct CurrentFloor {
+    pub floor: usize,
+    // Set while the player stands on the stairs they arrived by, so they do not take them straight back
+    pub on_stairs: bool,
+}
+
+impl CurrentFloor {
+    pub fn contains(&self, floor: &Floor) -> bool {
+        **floor == self.floor
+    }
+}
+
+#[derive(Component)]
+pub struct Staircase {
+    pub target_floor: usize,
+}
+
+#[derive(Component)]
+pub struct FloorUi;
+
+// Each staircase shows up on both floors it connects, leading up on the lower one and down on the upper one
+pub fn spawn_stairs(commands: &mut Commands, stairs: &[Stairs]) {
+    for staircase in stairs {
+        for (floor, target_floor, color) in [
+            (staircase.floor, staircase.floor + 1, STAIRS_UP_COLOR),
+            (staircase.floor + 1, staircase.floor, STAIRS_DOWN_COLOR),
+        ] {
+            commands.spawn((
+                SpriteBundle {
+                    transform: Transform {
+                        translation: staircase.position.extend(-0.5),
+                        scale: STAIRS_SIZE.extend(1.),
+                        ..default()
+                    },
+                    sprite: Sprite {
+                        color,
+                        ..default()
+                    },
+                    ..default()
+                },
+                Staircase { target_floor },
+                Floor(floor),
+                OnGameScreen,
+            ));
+        }
+    }
+}
+
+pub fn reset_current_floor(mut current_floor: ResMut<CurrentFloor>) {
+    *current_floor = CurrentFloor::default();
+}
+
+// Both players always share a floor, whoever steps onto the stairs takes the other one along
+pub fn use_stairs(
+    mut current_floor: ResMut<CurrentFloor>,
+    mut player_query: Query<(&mut Transform, &Player)>,
+    stairs_query: Query<(&Transform, &Staircase, &Floor), Without<Player>>,
+) {
+    let touched = player_query
+        .iter()
+        .filter(|(_, player)| player.alive)
+        .find_map(|(player_transform, _)| {
+            let player_bounding_box = Aabb2d::new(
+                player_transform.translation.truncate(),
+                player_transform.scale.truncate() / 2.,
+            );
+
+            stairs_query
+                .iter()
+                .filter(|(_, _, floor)| current_floor.contains(floor))
+                .find(|(stairs_transform, _, _)| {
+                    Aabb2d::new(stairs_transform.translation.truncate(), stairs_transform.scale.truncate() / 2.)
+                        .intersects(&player_bounding_box)
+                })
+        });
+
+    match touched {
+        Some((stairs_transform, staircase, _)) if !current_floor.on_stairs => {
+            current_floor.floor = staircase.target_floor;
+            current_floor.on_stairs = true;
+
+            for (mut player_transform, _) in &mut player_query {
+                player_transform.translation = stairs_transform.translation.truncate().extend(player_transform.translation.z);
+            }
+        }
+        Some(_) => {}
+        None if current_floor.on_stairs => current_floor.on_stairs = false,
+        None => {}
+    }
+}
+
+pub fn update_floor_visibility(
+    current_floor: Res<CurrentFloor>,
+    fog: Res<FogOfWar>,
+    mut floor_query: Query<(&Floor, &Transform, &mut Visibility, Has<HiddenInFog>)>,
+) {
+    for (floor, transform, mut visibility, hidden_in_fog) in &mut floor_query {
+        let shown = current_floor.contains(floor)
+            && (!hidden_in_fog || fog.sees(**floor, transform.translation.truncate()));
+        let target = if shown { Visibility::Inherited } else { Visibility::Hidden };
+        visibility.set_if_neq(target);
+    }
+}
+
+// Only levels with more than one floor show which one the player is on
+pub fn spawn_floor_ui(commands: &mut Commands, floor_count: usize) {
+    if floor_count < 2 { return };
+
+    commands.spawn((
+        FloorUi,
+        OnGameScreen,
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 20.,
+                color: FLOOR_TEXT_COLOR,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(55.),
+            left: Val::Px(5.),
+            ..default()
+        }),
+    ));
+}
+
+pub fn update_floor_ui(
+    current_floor: Res<CurrentFloor>,
+    current_level: Res<CurrentLevel>,
+    mut floor_ui_query: Query<&mut Text, With<FloorUi>>,
+) {
+    for mut text in &mut floor_ui_query {
+        text.sections[0].value = format!("Floor {} of {}", current_floor.floor + 1, current_level.level.floor_count());
+    }
+}
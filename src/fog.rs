@@ -0,0 +1,291 @@
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{Indices, PrimitiveTopology},
+        render_asset::RenderAssetUsages,
+    },
+    sprite::MaterialMesh2dBundle,
+};
+use std::collections::HashMap;
+
+use crate::floors::CurrentFloor;
+use crate::game_state::OnGameScreen;
+use crate::level::CurrentLevel;
+use crate::maze::{CellMaze, MAZE_CELL_SIZE};
+use crate::player::player::Player;
+use crate::save_data::SaveData;
+
+// Cells further than this from the player's cell stay dark even in a straight corridor
+pub const FOG_VISION_RADIUS: f32 = 4.5 * MAZE_CELL_SIZE;
+const FOG_REMEMBERED_COLOR: Color = Color::srgba(0., 0., 0., 0.6);
+// Above walls and items, below nothing that matters since the player's own cell is always seen
+const FOG_Z: f32 = 0.5;
+
+// Coins, enemies and keys vanish outside the player's vision instead of being remembered
+#[derive(Component)]
+pub struct HiddenInFog;
+
+// Covers one cell, opaque until the cell has been seen and dimmed while it is out of sight
+#[derive(Component)]
+pub struct FogOverlay {
+    pub floor: usize,
+    pub cell: usize,
+}
+
+#[derive(Resource, Default)]
+pub struct FogOfWar {
+    pub enabled: bool,
+    mazes: Vec<CellMaze>,
+    walls: Vec<Vec<[f32; 4]>>,
+    // Floor and the players' cells the vision was last worked out from
+    eyes: Option<(usize, Vec<usize>)>,
+    // Cells of the eyes' floor either player sees right now
+    visible: Vec<bool>,
+    explored: Vec<Vec<bool>>,
+    unknown_material: Handle<ColorMaterial>,
+    remembered_material: Handle<ColorMaterial>,
+}
+
+impl FogOfWar {
+    // Always true with the fog turned off
+    pub fn sees(&self, floor: usize, position: Vec2) -> bool {
+        if !self.enabled { return true };
+
+        self.eyes.as_ref().is_some_and(|(eye_floor, _)| *eye_floor == floor)
+            && self.mazes[floor].cell_at(position).is_some_and(|cell| self.visible[cell])
+    }
+
+    pub fn explored(&self, floor: usize, cell: usize) -> bool {
+        self.explored.get(floor).and_then(|cells| cells.get(cell)).is_some_and(|explored| *explored)
+    }
+}
+
+// Unseen cells are covered in the background color, so they look like there is nothing there yet
+pub fn spawn_fog(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    clear_color: Res<ClearColor>,
+    save_data: Res<SaveData>,
+    current_level: Res<CurrentLevel>,
+) {
+    let level = &current_level.level;
+    let enabled = save_data.settings.fog_of_war;
+    let mazes: Vec<CellMaze> = (0..level.floor_count()).map(|floor| level.floor_maze(floor)).collect();
+    let unknown_material = materials.add(clear_color.0);
+    let remembered_material = materials.add(FOG_REMEMBERED_COLOR);
+
+    if enabled {
+        // Square and hex cells all share one shape, so they share one mesh as well
+        let mut shapes: HashMap<Vec<[i32; 2]>, Handle<Mesh>> = HashMap::new();
+
+        for (floor, maze) in mazes.iter().enumerate() {
+            for cell in 0..maze.cell_count() {
+                let center = maze.cell_center(cell);
+                let outline: Vec<Vec2> = maze.cell_outline(cell).into_iter().map(|corner| corner - center).collect();
+                let shape = outline.iter().map(|corner| (*corner * 10.).round().as_ivec2().to_array()).collect();
+                let mesh = shapes.entry(shape).or_insert_with(|| meshes.add(outline_mesh(&outline))).clone();
+
+                commands.spawn((
+                    MaterialMesh2dBundle {
+                        mesh: mesh.into(),
+                        material: unknown_material.clone(),
+                        transform: Transform::from_translation(center.extend(FOG_Z)),
+                        ..default()
+                    },
+                    FogOverlay { floor, cell },
+                    OnGameScreen,
+                ));
+            }
+        }
+    }
+
+    commands.insert_resource(FogOfWar {
+        enabled,
+        walls: (0..level.floor_count()).map(|floor| level.floor_walls(floor).to_vec()).collect(),
+        explored: mazes.iter().map(|maze| vec![false; maze.cell_count()]).collect(),
+        mazes,
+        eyes: None,
+        visible: Vec::new(),
+        unknown_material,
+        remembered_material,
+    });
+}
+
+// Triangle fan around the cell center, which every cell outline surrounds
+fn outline_mesh(outline: &[Vec2]) -> Mesh {
+    let positions: Vec<[f32; 3]> = std::iter::once(Vec2::ZERO)
+        .chain(outline.iter().copied())
+        .map(|point| point.extend(0.).to_array())
+        .collect();
+    let indices: Vec<u32> = (0..outline.len() as u32)
+        .flat_map(|corner| [0, corner + 1, (corner + 1) % outline.len() as u32 + 1])
+        .collect();
+
+    Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::RENDER_WORLD)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0., 0., 1.]; positions.len()])
+        .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0., 0.]; positions.len()])
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+        .with_inserted_indices(Indices::U32(indices))
+}
+
+// Works out what the players see whenever one of them steps into another cell
+pub fn update_fog(
+    mut fog: ResMut<FogOfWar>,
+    current_floor: Res<CurrentFloor>,
+    player_query: Query<&Transform, With<Player>>,
+) {
+    if !fog.enabled { return };
+
+    let floor = current_floor.floor;
+    let Some(maze) = fog.mazes.get(floor) else {
+        return;
+    };
+
+    let eye_cells: Vec<usize> = player_query
+        .iter()
+        .filter_map(|player_transform| maze.cell_at(player_transform.translation.truncate()))
+        .collect();
+    if eye_cells.is_empty() { return };
+
+    if fog.eyes.as_ref().is_some_and(|(eyes_floor, cells)| *eyes_floor == floor && *cells == eye_cells) { return };
+
+    let mut visible = vec![false; maze.cell_count()];
+    for eye_cell in &eye_cells {
+        let eye = maze.cell_center(*eye_cell);
+        let nearby_walls: Vec<&[f32; 4]> = fog.walls[floor]
+            .iter()
+            .filter(|[start_x, start_y, end_x, end_y]| {
+                distance_to_segment(eye, Vec2::new(*start_x, *start_y), Vec2::new(*end_x, *end_y)) <= FOG_VISION_RADIUS
+            })
+            .collect();
+
+        for (cell, seen) in visible.iter_mut().enumerate() {
+            let target = maze.cell_center(cell);
+
+            *seen |= cell == *eye_cell
+                || (target.distance(eye) <= FOG_VISION_RADIUS
+                    && !nearby_walls.iter().any(|[start_x, start_y, end_x, end_y]| {
+                        segments_cross(eye, target, Vec2::new(*start_x, *start_y), Vec2::new(*end_x, *end_y))
+                    }));
+        }
+    }
+
+    let fog = &mut *fog;
+    for (explored, seen) in fog.explored[floor].iter_mut().zip(&visible) {
+        *explored |= *seen;
+    }
+    fog.visible = visible;
+    fog.eyes = Some((floor, eye_cells));
+}
+
+pub fn update_fog_overlay(
+    fog: Res<FogOfWar>,
+    current_floor: Res<CurrentFloor>,
+    mut overlay_query: Query<(&FogOverlay, &mut Handle<ColorMaterial>, &mut Visibility)>,
+) {
+    if !fog.is_changed() && !current_floor.is_changed() { return };
+
+    for (overlay, mut material, mut visibility) in &mut overlay_query {
+        let seen = fog.eyes.as_ref().is_some_and(|(floor, _)| *floor == overlay.floor) && fog.visible[overlay.cell];
+        let shown = overlay.floor == current_floor.floor && !seen;
+        visibility.set_if_neq(if shown { Visibility::Inherited } else { Visibility::Hidden });
+
+        let target = if fog.explored[overlay.floor][overlay.cell] {
+            &fog.remembered_material
+        } else {
+            &fog.unknown_material
+        };
+        if *material != *target {
+            *material = target.clone();
+        }
+    }
+}
+
+fn distance_to_segment(point: Vec2, start: Vec2, end: Vec2) -> f32 {
+    let length_squared = start.distance_squared(end);
+    if length_squared == 0. { return point.distance(start) };
+
+    let progress = ((point - start).dot(end - start) / length_squared).clamp(0., 1.);
+    point.distance(start.lerp(end, progress))
+}
+
+// Touching counts as crossing, so sight does not slip through the corner where two walls meet
+pub fn segments_cross(a_start: Vec2, a_end: Vec2, b_start: Vec2, b_end: Vec2) -> bool {
+    let side = |from: Vec2, to: Vec2, point: Vec2| (to - from).perp_dot(point - from);
+
+    let a_start_side = side(b_start, b_end, a_start);
+    let a_end_side = side(b_start, b_end, a_end);
+    let b_start_side = side(a_start, a_end, b_start);
+    let b_end_side = side(a_start, a_end, b_end);
+
+    // Looking along the line of a wall does not cross it
+    if a_start_side == 0. && a_end_side == 0. { return false };
+
+    a_start_side * a_end_side <= 0. && b_start_side * b_end_side <= 0.
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+    use std::time::Duration;
+
+    use crate::maze::MazeGrid;
+    use crate::player::player::PlayerFacingDirection;
+
+    // Works out the fog of a three cell corridor for a player in its left cell
+    fn fog_of_corridor(grid: &MazeGrid) -> FogOfWar {
+        let maze = CellMaze::from_grid(grid);
+        let mut world = World::new();
+
+        world.init_resource::<CurrentFloor>();
+        world.insert_resource(FogOfWar {
+            enabled: true,
+            walls: vec![maze.segments()],
+            explored: vec![vec![false; maze.cell_count()]],
+            mazes: vec![maze],
+            ..default()
+        });
+        world.spawn((
+            Player {
+                index: 0,
+                alive: true,
+                finished: false,
+                coins: 0,
+                player_attack_cooldown_timer: Timer::new(Duration::ZERO, TimerMode::Once),
+                player_facing_direction: PlayerFacingDirection::Down,
+            },
+            Transform::from_translation(grid.cell_center(UVec2::ZERO).extend(0.)),
+        ));
+
+        world.run_system_once(update_fog);
+        world.remove_resource::<FogOfWar>().unwrap()
+    }
+
+    fn corridor() -> MazeGrid {
+        let mut grid = MazeGrid::new(3, 1);
+        grid.close_border();
+        grid
+    }
+
+    #[test]
+    fn open_corridor_is_seen() {
+        let fog = fog_of_corridor(&corridor());
+
+        assert_eq!(fog.visible, vec![true, true, true]);
+        assert!((0..3).all(|cell| fog.explored(0, cell)));
+    }
+
+    #[test]
+    fn wall_hides_cell_within_vision_radius() {
+        let mut grid = corridor();
+        grid.set_vertical_wall(1, 0, true);
+        let fog = fog_of_corridor(&grid);
+
+        assert!(grid.cell_center(UVec2::ZERO).distance(grid.cell_center(UVec2::new(1, 0))) <= FOG_VISION_RADIUS);
+        assert_eq!(fog.visible, vec![true, false, false]);
+        assert!(fog.explored(0, 0));
+        assert!(!fog.explored(0, 1) && !fog.explored(0, 2));
+    }
+}
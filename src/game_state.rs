@@ -5,6 +5,28 @@ pub enum GameState {
     #[default]
     Menu,
     Game,
+    Restart,
+    Editor,
 }
 
+// Only exists while in GameState::Game, gameplay systems run while it is Running
+#[derive(Clone, Copy, Default, Eq, PartialEq, Debug, Hash, SubStates)]
+#[source(GameState = GameState::Game)]
+pub enum PauseState {
+    #[default]
+    Running,
+    Paused,
+}
+
+// Tag for every entity that belongs to a running level, despawned when leaving the game
+#[derive(Component, Default)]
+pub struct OnGameScreen;
 
+pub fn restart_game(mut game_state: ResMut<NextState<GameState>>) {
+    game_state.set(GameState::Restart);
+}
+
+// Re-entering the same state does not run OnEnter/OnExit, so restarts pass through GameState::Restart
+pub fn finish_restart(mut game_state: ResMut<NextState<GameState>>) {
+    game_state.set(GameState::Game);
+}
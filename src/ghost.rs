@@ -0,0 +1,295 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::finish_area::FinishEvent;
+use crate::floors::CurrentFloor;
+use crate::game_state::OnGameScreen;
+use crate::level::CurrentLevel;
+use crate::run_timer::RunTimer;
+use crate::save_data::storage;
+use crate::player::player::{
+    Player,
+    PLAYER_SIZE,
+    PLAYER_TEXTURE,
+    player_texture_atlas_layout,
+};
+
+const GHOST_COLOR: Color = Color::srgba(1., 1., 1., 0.4);
+const SPLIT_AHEAD_COLOR: Color = Color::srgb(0., 120., 0.);
+const SPLIT_BEHIND_COLOR: Color = Color::srgb(120., 0., 0.);
+
+// Storage directory for the best run of each level, one entry per level id
+const GHOSTS_DIRECTORY: &str = "ghosts";
+
+// Player positions sampled every fixed tick, paired with the run time they were taken at
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct RunRecording {
+    pub samples: Vec<(f32, Vec2)>,
+    // Run time the player arrived on each floor, starting with the ground floor
+    #[serde(default)]
+    pub floor_changes: Vec<(f32, usize)>,
+}
+
+impl RunRecording {
+    pub fn duration(&self) -> f32 {
+        self.samples.last().map_or(0., |(time, _)| *time)
+    }
+
+    pub fn position_at(&self, time: f32) -> Option<Vec2> {
+        let next_index = self.samples.partition_point(|(sample_time, _)| *sample_time < time);
+
+        if next_index == 0 {
+            return self.samples.first().map(|(_, position)| *position);
+        }
+
+        let Some((next_time, next_position)) = self.samples.get(next_index) else {
+            return self.samples.last().map(|(_, position)| *position);
+        };
+
+        let (previous_time, previous_position) = self.samples[next_index - 1];
+        let progress = (time - previous_time) / (next_time - previous_time);
+
+        Some(previous_position.lerp(*next_position, progress))
+    }
+
+    pub fn floor_at(&self, time: f32) -> usize {
+        self.floor_changes
+            .iter()
+            .take_while(|(change_time, _)| *change_time <= time)
+            .last()
+            .map_or(0, |(_, floor)| *floor)
+    }
+
+    // Run time at which the recorded path came closest to the given position, only counting the given floor
+    pub fn time_closest_to(&self, floor: usize, position: Vec2) -> Option<f32> {
+        self.samples
+            .iter()
+            .filter(|(time, _)| self.floor_at(*time) == floor)
+            .min_by(|(_, a), (_, b)| {
+                a.distance_squared(position).total_cmp(&b.distance_squared(position))
+            })
+            .map(|(time, _)| *time)
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct CurrentRun {
+    pub recording: RunRecording,
+    pub finished: bool,
+}
+
+// Fastest recorded run of each level, keyed by level id and kept in storage across launches
+#[derive(Resource, Default)]
+pub struct BestRun(pub HashMap<String, RunRecording>);
+
+impl BestRun {
+    pub fn load() -> BestRun {
+        let recordings = storage::list(GHOSTS_DIRECTORY).into_iter().filter_map(|id| {
+            let contents = storage::read(&ghost_path(&id))?;
+            let recording = ron::from_str(&contents)
+                .map_err(|error| warn!("Could not read the ghost of {id}: {error}"))
+                .ok()?;
+            Some((id, recording))
+        });
+
+        BestRun(recordings.collect())
+    }
+
+    pub fn store(&mut self, level_id: &str, recording: RunRecording) {
+        let result = ron::to_string(&recording)
+            .map_err(|error| error.to_string())
+            .and_then(|contents| storage::write(&ghost_path(level_id), &contents));
+
+        if let Err(error) = result {
+            warn!("Could not write the ghost of {level_id}: {error}");
+        }
+
+        self.0.insert(level_id.to_string(), recording);
+    }
+}
+
+fn ghost_path(level_id: &str) -> String {
+    format!("{GHOSTS_DIRECTORY}/{level_id}")
+}
+
+#[derive(Component)]
+pub struct Ghost {
+    pub elapsed: f32,
+}
+
+#[derive(Component)]
+pub struct SplitUi;
+
+pub fn spawn_ghost(
+    commands: &mut Commands,
+    asset_server: &Res<AssetServer>,
+    texture_atlas_layouts: &mut ResMut<Assets<TextureAtlasLayout>>,
+    best_run: &Res<BestRun>,
+    level_id: &str,
+) {
+    let Some(recording) = best_run.0.get(level_id) else {
+        return;
+    };
+
+    let Some(start_position) = recording.position_at(0.) else {
+        return;
+    };
+
+    commands.spawn((
+        SpriteBundle {
+            texture: asset_server.load(PLAYER_TEXTURE),
+            transform: Transform {
+                // Draw behind the player
+                translation: start_position.extend(-0.5),
+                scale: PLAYER_SIZE.extend(1.0),
+                ..default()
+            },
+            sprite: Sprite {
+                color: GHOST_COLOR,
+                custom_size: Some(Vec2::new(2., 2.)),
+                ..default()
+            },
+            ..default()
+        },
+        TextureAtlas {
+            layout: texture_atlas_layouts.add(player_texture_atlas_layout()),
+            index: 0,
+        },
+        Ghost { elapsed: 0. },
+        OnGameScreen,
+    ));
+
+    commands.spawn((
+        SplitUi,
+        OnGameScreen,
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 20.,
+                color: SPLIT_AHEAD_COLOR,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(30.),
+            left: Val::Px(5.),
+            ..default()
+        }),
+    ));
+}
+
+pub fn reset_current_run(mut current_run: ResMut<CurrentRun>) {
+    *current_run = CurrentRun::default();
+}
+
+pub fn record_player_path(
+    mut current_run: ResMut<CurrentRun>,
+    run_timer: Res<RunTimer>,
+    player_query: Query<(&Transform, &Player)>,
+    current_floor: Res<CurrentFloor>,
+) {
+    // Runs are recorded from the first player, co-op runs race the same ghost
+    let Some((player_transform, _)) = player_query.iter().find(|(_, player)| player.index == 0) else {
+        return;
+    };
+
+    if current_run.finished || !run_timer.running { return };
+
+    let time = run_timer.level_elapsed().as_secs_f32();
+    current_run.recording.samples.push((time, player_transform.translation.truncate()));
+
+    if current_run.recording.floor_at(time) != current_floor.floor {
+        current_run.recording.floor_changes.push((time, current_floor.floor));
+    }
+}
+
+pub fn save_best_run(
+    mut finish_events: EventReader<FinishEvent>,
+    mut current_run: ResMut<CurrentRun>,
+    mut best_run: ResMut<BestRun>,
+    current_level: Res<CurrentLevel>,
+) {
+    if finish_events.is_empty() || current_run.finished {
+        finish_events.clear();
+        return;
+    }
+    finish_events.clear();
+
+    current_run.finished = true;
+
+    let is_new_best = best_run
+        .0
+        .get(&current_level.id)
+        .is_none_or(|best| current_run.recording.duration() < best.duration());
+
+    if is_new_best {
+        best_run.store(&current_level.id, current_run.recording.clone());
+    }
+}
+
+pub fn replay_ghost(
+    mut ghost_query: Query<(&mut Ghost, &mut Transform, &mut Sprite, &mut Visibility)>,
+    best_run: Res<BestRun>,
+    current_level: Res<CurrentLevel>,
+    current_floor: Res<CurrentFloor>,
+    time: Res<Time>,
+) {
+    let Some(recording) = best_run.0.get(&current_level.id) else {
+        return;
+    };
+
+    for (mut ghost, mut ghost_transform, mut ghost_sprite, mut ghost_visibility) in &mut ghost_query {
+        ghost.elapsed += time.delta_seconds();
+
+        // The ghost only shows while it walks the same floor as the player
+        let visible = recording.floor_at(ghost.elapsed) == current_floor.floor;
+        ghost_visibility.set_if_neq(if visible { Visibility::Inherited } else { Visibility::Hidden });
+
+        let Some(position) = recording.position_at(ghost.elapsed) else {
+            continue;
+        };
+
+        let direction_x = position.x - ghost_transform.translation.x;
+        if direction_x != 0. {
+            ghost_sprite.flip_x = direction_x < 0.;
+        }
+
+        ghost_transform.translation.x = position.x;
+        ghost_transform.translation.y = position.y;
+    }
+}
+
+pub fn update_split_ui(
+    run_timer: Res<RunTimer>,
+    best_run: Res<BestRun>,
+    current_level: Res<CurrentLevel>,
+    current_floor: Res<CurrentFloor>,
+    player_query: Query<(&Transform, &Player)>,
+    mut split_query: Query<&mut Text, With<SplitUi>>,
+) {
+    let Some(recording) = best_run.0.get(&current_level.id) else {
+        return;
+    };
+
+    let Some((player_transform, _)) = player_query.iter().find(|(_, player)| player.index == 0) else {
+        return;
+    };
+
+    let Some(best_time) = recording.time_closest_to(current_floor.floor, player_transform.translation.truncate()) else {
+        return;
+    };
+
+    // Negative splits mean the player reached this point sooner than the best run did
+    let split = run_timer.level_elapsed().as_secs_f32() - best_time;
+
+    for mut text in &mut split_query {
+        text.sections[0].value = format!("{:+.2}s", split);
+        text.sections[0].style.color = if split <= 0. {
+            SPLIT_AHEAD_COLOR
+        } else {
+            SPLIT_BEHIND_COLOR
+        };
+    }
+}
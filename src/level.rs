@@ -0,0 +1,533 @@
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{hash_map::Entry, HashMap, VecDeque};
+
+use crate::doors::KeyColor;
+use crate::level_export::{MazePalette, rasterize};
+use crate::maze::{CellMaze, MazeGrid, MazeTopology, MAZE_CELL_SIZE, MAZE_ORIGIN};
+use crate::save_data::{SaveData, storage};
+
+// Built-in levels in play order, finishing one unlocks the next
+const CAMPAIGN: [(&str, &str); 1] = [
+    ("labyrinth", include_str!("../assets/levels/labyrinth.ron")),
+];
+
+pub fn campaign_start_id() -> &'static str {
+    CAMPAIGN[0].0
+}
+
+// Thumbnail pixels per maze cell, so every level gets the same level of detail
+const THUMBNAIL_CELL_PIXELS: usize = 5;
+const THUMBNAIL_PALETTE: MazePalette = MazePalette {
+    background: [20, 20, 20, 255],
+    wall: [230, 230, 230, 255],
+    start: [80, 140, 255, 255],
+    finish: [0, 200, 0, 255],
+    solution: [220, 60, 60, 255],
+};
+
+const DEFAULT_FINISH_AREA_SIZE: Vec2 = Vec2::new(25., 25.);
+
+// Storage directory for levels made outside the campaign, always unlocked
+pub const CUSTOM_LEVELS_DIRECTORY: &str = "levels";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Level {
+    pub name: String,
+    // Levels saved before other topologies existed are rectangular
+    #[serde(default)]
+    pub topology: MazeTopology,
+    // Cells across and up for rectangular and hex mazes, number of rings for polar ones
+    pub width: usize,
+    pub height: usize,
+    pub player_start: Vec2,
+    // Bottom left corner of the finish area
+    pub finish_area_location: Vec2,
+    pub finish_area_size: Vec2,
+    #[serde(default)]
+    pub enemies: Vec<Vec2>,
+    #[serde(default)]
+    pub coins: Vec<Vec2>,
+    // Wall segments as [start_x, start_y, end_x, end_y]
+    pub walls: Vec<[f32; 4]>,
+    // Floors stacked above the one described by the fields above, same shape and size
+    #[serde(default)]
+    pub upper_floors: Vec<LevelFloor>,
+    #[serde(default)]
+    pub stairs: Vec<Stairs>,
+    #[serde(default)]
+    pub finish_floor: usize,
+    #[serde(default)]
+    pub keys: Vec<LevelKey>,
+    #[serde(default)]
+    pub doors: Vec<LevelDoor>,
+    #[serde(default)]
+    pub switches: Vec<LevelSwitch>,
+    #[serde(default)]
+    pub gates: Vec<LevelGate>,
+    #[serde(default)]
+    pub camera: LevelCamera,
+    #[serde(default)]
+    pub music: LevelMusic,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct LevelFloor {
+    #[serde(default)]
+    pub enemies: Vec<Vec2>,
+    #[serde(default)]
+    pub coins: Vec<Vec2>,
+    pub walls: Vec<[f32; 4]>,
+}
+
+// Connects a floor with the one above it, at the same position on both
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Stairs {
+    pub position: Vec2,
+    // The lower of the two floors
+    pub floor: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct LevelKey {
+    pub position: Vec2,
+    #[serde(default)]
+    pub floor: usize,
+    pub color: KeyColor,
+}
+
+// Blocks the passage on its segment until the player brings a key of the same color
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct LevelDoor {
+    pub segment: [f32; 4],
+    #[serde(default)]
+    pub floor: usize,
+    pub color: KeyColor,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct LevelSwitch {
+    pub position: Vec2,
+    #[serde(default)]
+    pub floor: usize,
+}
+
+// Blocks the passage on its segment while closed, stepping on its switch opens or closes it
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct LevelGate {
+    pub segment: [f32; 4],
+    #[serde(default)]
+    pub floor: usize,
+    // Index into the level's switches
+    pub switch: usize,
+    #[serde(default)]
+    pub open: bool,
+}
+
+// How the camera follows the player, any setting left out of a level file keeps its default
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct LevelCamera {
+    // World units per screen pixel, larger shows more of the maze
+    pub zoom: f32,
+    pub min_zoom: f32,
+    pub max_zoom: f32,
+    // How far ahead of the player the camera looks in the direction they face
+    pub look_ahead: f32,
+    // How far the camera's focus may drift from the player before it follows
+    pub dead_zone: Vec2,
+    // Keeps everything beyond the outer walls off screen
+    pub clamp_to_bounds: bool,
+}
+
+impl Default for LevelCamera {
+    fn default() -> Self {
+        LevelCamera {
+            zoom: 1.,
+            min_zoom: 0.5,
+            max_zoom: 2.,
+            look_ahead: 30.,
+            dead_zone: Vec2::new(20., 15.),
+            clamp_to_bounds: true,
+        }
+    }
+}
+
+// Tracks the music director plays on a level, any setting left out of a level file keeps its default
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct LevelMusic {
+    // Asset path of the track looping while the player explores
+    pub calm: String,
+    // Track faded in while enemies are close, a heartbeat when there is none
+    pub intense: Option<String>,
+    // Playback speed of both tracks, which also changes their pitch
+    pub speed: f32,
+}
+
+impl Default for LevelMusic {
+    fn default() -> Self {
+        LevelMusic {
+            calm: "game_song.ogg".to_string(),
+            intense: None,
+            speed: 1.,
+        }
+    }
+}
+
+// The search tracks which switches have been toggled in a bit mask, later switches are never pressed
+pub const MAX_SWITCHES: usize = 32;
+
+// Position in the search together with the keys held and the switches toggled an odd number of times
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct SearchState {
+    floor: usize,
+    cell: usize,
+    keys: u8,
+    switches: u32,
+}
+
+impl Level {
+    // Level without enemies or coins, starting and finishing in the middle of the given cells
+    pub fn from_grid(name: &str, grid: &MazeGrid, start: UVec2, finish: UVec2) -> Level {
+        let index = |cell: UVec2| cell.y as usize * grid.width + cell.x as usize;
+        Level::from_cell_maze(name, &CellMaze::from_grid(grid), index(start), index(finish))
+    }
+
+    pub fn from_cell_maze(name: &str, maze: &CellMaze, start: usize, finish: usize) -> Level {
+        Level {
+            name: name.to_string(),
+            topology: maze.topology,
+            width: maze.width,
+            height: maze.height,
+            player_start: maze.cell_center(start),
+            finish_area_location: finish_area_location(maze.cell_center(finish), DEFAULT_FINISH_AREA_SIZE),
+            finish_area_size: DEFAULT_FINISH_AREA_SIZE,
+            enemies: Vec::new(),
+            coins: Vec::new(),
+            walls: maze.segments(),
+            upper_floors: Vec::new(),
+            stairs: Vec::new(),
+            finish_floor: 0,
+            keys: Vec::new(),
+            doors: Vec::new(),
+            switches: Vec::new(),
+            gates: Vec::new(),
+            camera: LevelCamera::default(),
+            music: LevelMusic::default(),
+        }
+    }
+
+    pub fn from_ron(contents: &str) -> Result<Level, ron::error::SpannedError> {
+        ron::from_str(contents)
+    }
+
+    pub fn to_ron(&self) -> Result<String, ron::Error> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+    }
+
+    // The ground floor, where the player starts
+    pub fn cell_maze(&self) -> CellMaze {
+        self.floor_maze(0)
+    }
+
+    pub fn floor_maze(&self, floor: usize) -> CellMaze {
+        CellMaze::from_segments(self.topology, self.width, self.height, self.floor_walls(floor))
+    }
+
+    pub fn floor_count(&self) -> usize {
+        1 + self.upper_floors.len()
+    }
+
+    pub fn floor_walls(&self, floor: usize) -> &[[f32; 4]] {
+        match floor {
+            0 => &self.walls,
+            _ => self.upper_floors.get(floor - 1).map_or(&[], |upper| &upper.walls),
+        }
+    }
+
+    pub fn floor_enemies(&self, floor: usize) -> &[Vec2] {
+        match floor {
+            0 => &self.enemies,
+            _ => self.upper_floors.get(floor - 1).map_or(&[], |upper| &upper.enemies),
+        }
+    }
+
+    pub fn floor_coins(&self, floor: usize) -> &[Vec2] {
+        match floor {
+            0 => &self.coins,
+            _ => self.upper_floors.get(floor - 1).map_or(&[], |upper| &upper.coins),
+        }
+    }
+
+    /*
+     * Every cell reachable from the player start on any floor, None when the start is outside the maze.
+     * Doors only let the search through once it has picked up a key of their color and gates only while
+     * open, so cells behind them count as reachable when their key or switch can be reached first.
+     */
+    pub fn search(&self) -> Option<LevelSearch> {
+        let mazes: Vec<CellMaze> = (0..self.floor_count()).map(|floor| self.floor_maze(floor)).collect();
+        let start_cell = mazes[0].cell_at(self.player_start)?;
+
+        // Doors and gates by the edge they sit on
+        let mut doors: HashMap<(usize, usize), KeyColor> = HashMap::new();
+        let mut gates: HashMap<(usize, usize), &LevelGate> = HashMap::new();
+        for door in &self.doors {
+            if let Some(edge) = mazes.get(door.floor).and_then(|maze| maze.edge_with_segment(&door.segment)) {
+                doors.insert((door.floor, edge), door.color);
+            }
+        }
+        for gate in &self.gates {
+            if let Some(edge) = mazes.get(gate.floor).and_then(|maze| maze.edge_with_segment(&gate.segment)) {
+                gates.insert((gate.floor, edge), gate);
+            }
+        }
+
+        // Picks up the keys and steps on the switches in the cell a state just moved into
+        let enter = |mut state: SearchState| {
+            let maze = &mazes[state.floor];
+            let here = |floor: usize, position: Vec2| floor == state.floor && maze.cell_at(position) == Some(state.cell);
+
+            for key in self.keys.iter().filter(|key| here(key.floor, key.position)) {
+                state.keys |= key.color.bit();
+            }
+            for (index, _) in self.switches.iter().enumerate().take(MAX_SWITCHES).filter(|(_, switch)| here(switch.floor, switch.position)) {
+                state.switches ^= 1 << index;
+            }
+
+            state
+        };
+
+        let passable = |state: &SearchState, edge: usize| {
+            let door_open = doors.get(&(state.floor, edge)).is_none_or(|color| state.keys & color.bit() != 0);
+            let gate_open = gates.get(&(state.floor, edge)).is_none_or(|gate| {
+                let toggled = gate.switch < MAX_SWITCHES && state.switches & (1 << gate.switch) != 0;
+                gate.open != toggled
+            });
+
+            door_open && gate_open
+        };
+
+        let start = enter(SearchState { floor: 0, cell: start_cell, keys: 0, switches: 0 });
+        let mut previous = HashMap::from([(start, start)]);
+        let mut first = HashMap::from([((start.floor, start.cell), start)]);
+        let mut queue = VecDeque::from([start]);
+
+        while let Some(state) = queue.pop_front() {
+            let maze = &mazes[state.floor];
+            let same_floor = maze
+                .open_neighbors(state.cell)
+                .into_iter()
+                .filter(|neighbor| maze.edge_between(state.cell, *neighbor).is_some_and(|edge| passable(&state, edge)))
+                .map(|neighbor| (state.floor, neighbor));
+
+            // Stairs standing in this cell lead to the same spot one floor up or down
+            let other_floors = self.stairs.iter().filter_map(|stairs| {
+                if maze.cell_at(stairs.position) != Some(state.cell) { return None };
+
+                let target = if stairs.floor == state.floor {
+                    stairs.floor + 1
+                } else if stairs.floor + 1 == state.floor {
+                    stairs.floor
+                } else {
+                    return None;
+                };
+
+                Some((target, mazes.get(target)?.cell_at(stairs.position)?))
+            });
+
+            for (floor, cell) in same_floor.chain(other_floors).collect::<Vec<_>>() {
+                let next = enter(SearchState { floor, cell, ..state });
+
+                if let Entry::Vacant(entry) = previous.entry(next) {
+                    entry.insert(state);
+                    first.entry((floor, cell)).or_insert(next);
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        Some(LevelSearch { mazes, previous, first })
+    }
+
+    // Bottom left and top right corners around all walls
+    pub fn bounds(&self) -> (Vec2, Vec2) {
+        let points = self
+            .walls
+            .iter()
+            .flat_map(|[start_x, start_y, end_x, end_y]| [Vec2::new(*start_x, *start_y), Vec2::new(*end_x, *end_y)]);
+
+        points
+            .fold(None, |bounds: Option<(Vec2, Vec2)>, point| match bounds {
+                Some((min, max)) => Some((min.min(point), max.max(point))),
+                None => Some((point, point)),
+            })
+            .unwrap_or((MAZE_ORIGIN, MAZE_ORIGIN + Vec2::new(self.width as f32, self.height as f32) * MAZE_CELL_SIZE))
+    }
+
+    // Moves the finish area to be centered on the given position
+    pub fn set_finish(&mut self, floor: usize, center: Vec2) {
+        self.finish_floor = floor;
+        self.finish_area_location = finish_area_location(center, self.finish_area_size);
+    }
+
+    pub fn finish_area_center(&self) -> Vec2 {
+        self.finish_area_location + self.finish_area_size / 2.
+    }
+
+    // Floors and cell centers along the shortest way from the player start to the finish area
+    pub fn solution(&self) -> Option<Vec<(usize, Vec2)>> {
+        self.search()?.path_to(self.finish_floor, self.finish_area_center())
+    }
+
+    // Top down picture of the maze, drawn on the CPU so the level select needs no extra camera
+    pub fn thumbnail(&self) -> Image {
+        let (width, height, pixels) = rasterize(self, THUMBNAIL_CELL_PIXELS, &THUMBNAIL_PALETTE, false);
+
+        Image::new(
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            pixels,
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::RENDER_WORLD,
+        )
+    }
+}
+
+fn finish_area_location(center: Vec2, size: Vec2) -> Vec2 {
+    (center - size / 2.).round()
+}
+
+pub struct LevelSearch {
+    mazes: Vec<CellMaze>,
+    // State each reached state was first entered from, the start points at itself
+    previous: HashMap<SearchState, SearchState>,
+    // Earliest state to reach each floor and cell, which lies on the shortest way there
+    first: HashMap<(usize, usize), SearchState>,
+}
+
+impl LevelSearch {
+    fn cell(&self, floor: usize, position: Vec2) -> Option<(usize, usize)> {
+        Some((floor, self.mazes.get(floor)?.cell_at(position)?))
+    }
+
+    pub fn reachable(&self, floor: usize, position: Vec2) -> bool {
+        self.cell(floor, position).is_some_and(|cell| self.first.contains_key(&cell))
+    }
+
+    pub fn path_to(&self, floor: usize, position: Vec2) -> Option<Vec<(usize, Vec2)>> {
+        let mut current = *self.first.get(&self.cell(floor, position)?)?;
+        let mut path = vec![current];
+
+        while let Some(previous) = self.previous.get(&current).filter(|previous| **previous != current) {
+            path.push(*previous);
+            current = *previous;
+        }
+        path.reverse();
+
+        Some(path.into_iter().map(|state| (state.floor, self.mazes[state.floor].cell_center(state.cell))).collect())
+    }
+}
+
+pub struct LevelEntry {
+    pub id: String,
+    pub level: Level,
+    pub campaign: bool,
+}
+
+#[derive(Resource)]
+pub struct Levels(pub Vec<LevelEntry>);
+
+impl Levels {
+    pub fn load() -> Levels {
+        let campaign = CAMPAIGN.iter().map(|(id, contents)| LevelEntry {
+            id: id.to_string(),
+            level: Level::from_ron(contents).expect("campaign levels are valid"),
+            campaign: true,
+        });
+
+        let custom = storage::list(CUSTOM_LEVELS_DIRECTORY).into_iter().filter_map(|id| {
+            let contents = storage::read(&custom_level_path(&id))?;
+
+            match Level::from_ron(&contents) {
+                Ok(level) => Some(LevelEntry { id, level, campaign: false }),
+                Err(error) => {
+                    warn!("Could not read level {id}: {error}");
+                    None
+                }
+            }
+        });
+
+        Levels(campaign.chain(custom).collect())
+    }
+
+    pub fn get(&self, id: &str) -> Option<&LevelEntry> {
+        self.0.iter().find(|entry| entry.id == id)
+    }
+
+    // Adds a level, replacing an existing one with the same id
+    pub fn insert(&mut self, entry: LevelEntry) {
+        match self.0.iter_mut().find(|existing| existing.id == entry.id) {
+            Some(existing) => *existing = entry,
+            None => self.0.push(entry),
+        }
+    }
+
+    pub fn first(&self) -> &LevelEntry {
+        &self.0[0]
+    }
+
+    pub fn next_campaign_level(&self, id: &str) -> Option<&LevelEntry> {
+        let campaign: Vec<&LevelEntry> = self.0.iter().filter(|entry| entry.campaign).collect();
+        let index = campaign.iter().position(|entry| entry.id == id)?;
+
+        campaign.get(index + 1).copied()
+    }
+
+    pub fn is_unlocked(&self, entry: &LevelEntry, save_data: &SaveData) -> bool {
+        !entry.campaign || entry.id == self.first().id || save_data.is_level_unlocked(&entry.id)
+    }
+}
+
+pub fn custom_level_path(id: &str) -> String {
+    format!("{CUSTOM_LEVELS_DIRECTORY}/{id}")
+}
+
+#[derive(Resource)]
+pub struct CurrentLevel {
+    pub id: String,
+    pub level: Level,
+}
+
+impl From<&LevelEntry> for CurrentLevel {
+    fn from(entry: &LevelEntry) -> Self {
+        CurrentLevel {
+            id: entry.id.clone(),
+            level: entry.level.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level_validator::validate_level;
+
+    #[test]
+    fn campaign_levels_are_valid() {
+        for (id, contents) in CAMPAIGN {
+            let level = Level::from_ron(contents).unwrap_or_else(|error| panic!("{id}: {error}"));
+            assert_eq!(validate_level(&level), Vec::new(), "{id}");
+        }
+    }
+}
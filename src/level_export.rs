@@ -0,0 +1,290 @@
+use bevy::prelude::*;
+use std::io::Cursor;
+
+use crate::level::Level;
+use crate::maze::MAZE_CELL_SIZE;
+use crate::save_data::storage;
+
+// Pixels per maze cell in exported PNGs, about 2cm per cell when printed at 150 dpi
+const EXPORT_CELL_PIXELS: usize = 120;
+// Printed size of one maze cell in exported SVGs
+const EXPORT_CELL_MILLIMETERS: f32 = 10.;
+const EXPORT_WALL_WIDTH: f32 = 3.;
+const EXPORT_SOLUTION_WIDTH: f32 = 4.;
+// Blank space around the maze in SVG units, so border walls are not cut off
+const SVG_MARGIN: f32 = 5.;
+
+// Colors as RGBA bytes, shared by the PNG and SVG output
+pub struct MazePalette {
+    pub background: [u8; 4],
+    pub wall: [u8; 4],
+    pub start: [u8; 4],
+    pub finish: [u8; 4],
+    pub solution: [u8; 4],
+}
+
+// Black on white so printed mazes stay readable and cheap on ink
+pub const PRINT_PALETTE: MazePalette = MazePalette {
+    background: [255, 255, 255, 255],
+    wall: [0, 0, 0, 255],
+    start: [40, 90, 220, 255],
+    finish: [0, 150, 0, 255],
+    solution: [220, 40, 40, 255],
+};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ExportFormat {
+    Svg,
+    Png,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Svg => "svg",
+            ExportFormat::Png => "png",
+        }
+    }
+}
+
+pub fn export_level(level: &Level, format: ExportFormat, solution: bool) -> Result<Vec<u8>, String> {
+    match format {
+        ExportFormat::Svg => Ok(export_svg(level, solution).into_bytes()),
+        ExportFormat::Png => export_png(level, solution),
+    }
+}
+
+// Writes the level as SVG and PNG, each with and without the solution, returning the last file written
+pub fn export_level_files(id: &str, level: &Level) -> Result<String, String> {
+    let mut last_path = String::new();
+
+    for format in [ExportFormat::Svg, ExportFormat::Png] {
+        for solution in [false, true] {
+            let suffix = if solution { "_solution" } else { "" };
+            let file_name = format!("{id}{suffix}.{}", format.extension());
+            last_path = storage::export(&file_name, &export_level(level, format, solution)?)?;
+        }
+    }
+
+    Ok(last_path)
+}
+
+/*
+ * Draws the maze into RGBA pixels, returning width, height and pixel bytes.
+ * Walls and the solution may run at any angle, so lines are stamped pixel by pixel.
+ */
+pub fn rasterize(level: &Level, cell_pixels: usize, palette: &MazePalette, solution: bool) -> (u32, u32, Vec<u8>) {
+    let (min, max) = level.bounds();
+    let cells = (max - min) / MAZE_CELL_SIZE;
+    let width = (cells.x * cell_pixels as f32).round() as usize + 1;
+    let height = (cells.y * cell_pixels as f32).round() as usize + 1;
+    let mut pixels = vec![palette.background; width * height];
+    let thickness = (cell_pixels / 20) as isize;
+
+    // World y grows upwards while image rows grow downwards
+    let to_pixel = |position: Vec2| {
+        let cell = (position - min).max(Vec2::ZERO) / MAZE_CELL_SIZE * cell_pixels as f32;
+        let x = (cell.x.round() as usize).min(width - 1);
+        let y = (cell.y.round() as usize).min(height - 1);
+        (x, height - 1 - y)
+    };
+
+    let mut fill = |start: (usize, usize), end: (usize, usize), grow: isize, color: [u8; 4]| {
+        let clamp_x = |x: isize| x.clamp(0, width as isize - 1) as usize;
+        let clamp_y = |y: isize| y.clamp(0, height as isize - 1) as usize;
+
+        let (low_x, high_x) = (start.0.min(end.0) as isize, start.0.max(end.0) as isize);
+        let (low_y, high_y) = (start.1.min(end.1) as isize, start.1.max(end.1) as isize);
+
+        for y in clamp_y(low_y - grow)..=clamp_y(high_y + grow) {
+            for x in clamp_x(low_x - grow)..=clamp_x(high_x + grow) {
+                pixels[y * width + x] = color;
+            }
+        }
+    };
+
+    // One pixel per step along a line, each stamped with the line thickness
+    let line = |start: Vec2, end: Vec2| {
+        let steps = (start.distance(end) / MAZE_CELL_SIZE * cell_pixels as f32).ceil().max(1.) as usize;
+        (0..=steps).map(move |step| to_pixel(start.lerp(end, step as f32 / steps as f32)))
+    };
+
+    // A finish upstairs would sit on top of ground floor cells that lead nowhere near it
+    if level.finish_floor == 0 {
+        fill(
+            to_pixel(level.finish_area_location),
+            to_pixel(level.finish_area_location + level.finish_area_size),
+            0,
+            palette.finish,
+        );
+    }
+
+    if solution {
+        for step in ground_floor_steps(level) {
+            for point in line(step.0, step.1) {
+                fill(point, point, thickness, palette.solution);
+            }
+        }
+    }
+
+    for [start_x, start_y, end_x, end_y] in &level.walls {
+        for point in line(Vec2::new(*start_x, *start_y), Vec2::new(*end_x, *end_y)) {
+            fill(point, point, thickness, palette.wall);
+        }
+    }
+
+    let start = to_pixel(level.player_start);
+    fill(start, start, thickness * 3, palette.start);
+
+    (width as u32, height as u32, pixels.concat())
+}
+
+// Exports show the ground floor, so only the parts of the solution walked there are drawn
+fn ground_floor_steps(level: &Level) -> Vec<(Vec2, Vec2)> {
+    level
+        .solution()
+        .unwrap_or_default()
+        .windows(2)
+        .filter(|step| step[0].0 == 0 && step[1].0 == 0)
+        .map(|step| (step[0].1, step[1].1))
+        .collect()
+}
+
+pub fn export_png(level: &Level, solution: bool) -> Result<Vec<u8>, String> {
+    let (width, height, pixels) = rasterize(level, EXPORT_CELL_PIXELS, &PRINT_PALETTE, solution);
+    let image = image::RgbaImage::from_raw(width, height, pixels).ok_or("Rasterized maze has the wrong size")?;
+
+    let mut png = Cursor::new(Vec::new());
+    image
+        .write_to(&mut png, image::ImageFormat::Png)
+        .map_err(|error| error.to_string())?;
+
+    Ok(png.into_inner())
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;").replace('\'', "&apos;")
+}
+
+fn svg_color([red, green, blue, _]: [u8; 4]) -> String {
+    format!("#{red:02x}{green:02x}{blue:02x}")
+}
+
+// Vector version in millimeters, so it prints at the same scale from any PDF tool
+pub fn export_svg(level: &Level, solution: bool) -> String {
+    let (min, max) = level.bounds();
+    let size = max - min;
+    let millimeters = (size + 2. * SVG_MARGIN) * EXPORT_CELL_MILLIMETERS / MAZE_CELL_SIZE;
+    let palette = &PRINT_PALETTE;
+
+    // SVG y grows downwards, so flip around the maze height
+    let point = |position: Vec2| {
+        let local = position - min;
+        Vec2::new(local.x, size.y - local.y)
+    };
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}mm\" height=\"{}mm\" viewBox=\"{} {} {} {}\">\n",
+        millimeters.x,
+        millimeters.y,
+        -SVG_MARGIN,
+        -SVG_MARGIN,
+        size.x + 2. * SVG_MARGIN,
+        size.y + 2. * SVG_MARGIN,
+    );
+    svg += &format!("  <title>{}</title>\n", xml_escape(&level.name));
+    svg += &format!(
+        "  <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>\n",
+        -SVG_MARGIN,
+        -SVG_MARGIN,
+        size.x + 2. * SVG_MARGIN,
+        size.y + 2. * SVG_MARGIN,
+        svg_color(palette.background),
+    );
+
+    if level.finish_floor == 0 {
+        let finish_corner = point(level.finish_area_location + Vec2::new(0., level.finish_area_size.y));
+        svg += &format!(
+            "  <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>\n",
+            finish_corner.x,
+            finish_corner.y,
+            level.finish_area_size.x,
+            level.finish_area_size.y,
+            svg_color(palette.finish),
+        );
+    }
+
+    if solution {
+        let solution_path: Vec<String> = ground_floor_steps(level)
+            .into_iter()
+            .map(|(start, end)| {
+                let (start, end) = (point(start), point(end));
+                format!("M{} {} L{} {}", start.x, start.y, end.x, end.y)
+            })
+            .collect();
+
+        svg += &format!(
+            "  <path fill=\"none\" stroke=\"{}\" stroke-width=\"{EXPORT_SOLUTION_WIDTH}\" stroke-linejoin=\"round\" stroke-linecap=\"round\" d=\"{}\"/>\n",
+            svg_color(palette.solution),
+            solution_path.join(" "),
+        );
+    }
+
+    let wall_path: Vec<String> = level
+        .walls
+        .iter()
+        .map(|[start_x, start_y, end_x, end_y]| {
+            let (start, end) = (point(Vec2::new(*start_x, *start_y)), point(Vec2::new(*end_x, *end_y)));
+            format!("M{} {} L{} {}", start.x, start.y, end.x, end.y)
+        })
+        .collect();
+
+    svg += &format!(
+        "  <path fill=\"none\" stroke=\"{}\" stroke-width=\"{EXPORT_WALL_WIDTH}\" stroke-linecap=\"square\" d=\"{}\"/>\n",
+        svg_color(palette.wall),
+        wall_path.join(" "),
+    );
+
+    let start = point(level.player_start);
+    svg += &format!(
+        "  <circle cx=\"{}\" cy=\"{}\" r=\"6\" fill=\"{}\"/>\n",
+        start.x,
+        start.y,
+        svg_color(palette.start),
+    );
+
+    svg += "</svg>\n";
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maze::MazeGrid;
+
+    fn corridor(name: &str) -> Level {
+        let mut grid = MazeGrid::new(3, 1);
+        grid.close_border();
+        Level::from_grid(name, &grid, UVec2::ZERO, UVec2::new(2, 0))
+    }
+
+    #[test]
+    fn svg_title_is_escaped() {
+        let svg = export_svg(&corridor("Tom & Jerry's <maze>"), false);
+
+        assert!(svg.contains("<title>Tom &amp; Jerry&apos;s &lt;maze&gt;</title>"));
+    }
+
+    #[test]
+    fn finish_upstairs_is_not_drawn() {
+        let finish = svg_color(PRINT_PALETTE.finish);
+        let mut level = corridor("upstairs");
+        assert!(export_svg(&level, false).contains(&finish));
+
+        level.finish_floor = 1;
+        assert!(!export_svg(&level, false).contains(&finish));
+
+        let (_, _, pixels) = rasterize(&level, EXPORT_CELL_PIXELS, &PRINT_PALETTE, false);
+        assert!(!pixels.chunks_exact(4).any(|pixel| pixel == PRINT_PALETTE.finish));
+    }
+}
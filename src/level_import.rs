@@ -0,0 +1,403 @@
+use bevy::prelude::*;
+
+use crate::level::Level;
+use crate::maze::{CellMaze, MazeGrid};
+
+// Coordinates closer together than this share of the drawing's size are treated as the same grid line
+const COORDINATE_TOLERANCE: f32 = 0.0001;
+// Cells per side, drawings with finer detail than this are not mazes or would not fit in memory
+const MAX_IMPORTED_SIZE: usize = 200;
+
+/*
+ * Reads a maze drawn as straight line segments, as exported by mazesforfun.com and most other
+ * maze generators. Supports <line>, <polyline> and <path> elements with M/L/H/V/Z commands.
+ * The smallest distance between grid lines becomes one 30 unit maze cell.
+ */
+pub fn import_svg(contents: &str, name: &str) -> Result<Level, String> {
+    let mut segments: Vec<(Vec2, Vec2)> = Vec::new();
+
+    for element in svg_elements(contents, "line") {
+        let coordinate = |name: &str| {
+            svg_attribute(element, name)
+                .and_then(|value| value.trim().parse::<f32>().ok())
+                .ok_or(format!("<line> without a valid {name} attribute"))
+        };
+
+        segments.push((
+            Vec2::new(coordinate("x1")?, coordinate("y1")?),
+            Vec2::new(coordinate("x2")?, coordinate("y2")?),
+        ));
+    }
+
+    for element in svg_elements(contents, "polyline") {
+        let numbers = svg_numbers(svg_attribute(element, "points").unwrap_or_default())?;
+        let points: Vec<Vec2> = numbers.chunks_exact(2).map(|pair| Vec2::new(pair[0], pair[1])).collect();
+        segments.extend(points.windows(2).map(|pair| (pair[0], pair[1])));
+    }
+
+    for element in svg_elements(contents, "path") {
+        segments.extend(svg_path_segments(svg_attribute(element, "d").unwrap_or_default())?);
+    }
+
+    if segments.is_empty() {
+        return Err("No line segments found".to_string());
+    }
+
+    grid_from_segments(&segments).map(|grid| level_from_imported_grid(name, grid, None, None))
+}
+
+/*
+ * Reads a plain text maze like
+ *   +--+--+
+ *   |S    |
+ *   +  +--+
+ *   |    F|
+ *   +--+--+
+ * An S and F inside a cell mark the player start and the finish area, otherwise gaps in the
+ * outer wall do like in imported SVGs.
+ */
+pub fn import_ascii(contents: &str, name: &str) -> Result<Level, String> {
+    let lines: Vec<&str> = contents.lines().filter(|line| !line.trim().is_empty()).collect();
+
+    let Some(first_line) = lines.first() else {
+        return Err("Maze is empty".to_string());
+    };
+
+    // Corners on the top line mark where the columns start and end
+    let columns: Vec<usize> = first_line.char_indices().filter(|(_, c)| *c == '+').map(|(index, _)| index).collect();
+    if columns.len() < 2 || lines.len() < 3 || lines.len() % 2 == 0 {
+        return Err("Expected a +--+ grid with walls and cells on alternating lines".to_string());
+    }
+
+    let width = columns.len() - 1;
+    let height = lines.len() / 2;
+    let mut grid = MazeGrid::new(width, height);
+    let mut start = None;
+    let mut finish = None;
+
+    let char_at = |line: &str, index: usize| line.chars().nth(index).unwrap_or(' ');
+
+    for (row, line) in lines.iter().enumerate() {
+        // Text runs top to bottom, the maze grid bottom to top
+        let grid_row = height - row.div_ceil(2);
+
+        if row % 2 == 0 {
+            for x in 0..width {
+                let wall = (columns[x] + 1..columns[x + 1]).any(|index| char_at(line, index) == '-');
+                grid.set_horizontal_wall(x, grid_row, wall);
+            }
+            continue;
+        }
+
+        for (x, column) in columns.iter().enumerate() {
+            grid.set_vertical_wall(x, grid_row, char_at(line, *column) == '|');
+
+            if x == width { continue };
+            let cell = UVec2::new(x as u32, grid_row as u32);
+            for index in column + 1..columns[x + 1] {
+                match char_at(line, index).to_ascii_uppercase() {
+                    'S' => start = Some(cell),
+                    'F' => finish = Some(cell),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    Ok(level_from_imported_grid(name, grid, start, finish))
+}
+
+// Default finish when a drawing marks none, as far from the start as the maze allows
+fn farthest_cell(grid: &MazeGrid, start: UVec2) -> UVec2 {
+    let cell = CellMaze::from_grid(grid).farthest_cell(start.y as usize * grid.width + start.x as usize);
+
+    UVec2::new((cell % grid.width) as u32, (cell / grid.width) as u32)
+}
+
+fn svg_elements<'a>(contents: &'a str, tag: &str) -> Vec<&'a str> {
+    let opening = format!("<{tag}");
+
+    contents
+        .match_indices(&opening)
+        .filter_map(|(start, _)| {
+            let rest = &contents[start + opening.len()..];
+            // Skip longer tag names that share the prefix, like <linearGradient>
+            if !rest.starts_with(|c: char| c.is_whitespace() || c == '/' || c == '>') {
+                return None;
+            }
+            rest.find('>').map(|end| &rest[..end])
+        })
+        .collect()
+}
+
+fn svg_attribute<'a>(element: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = element;
+
+    while let Some(index) = rest.find(name) {
+        let preceded_by_space = rest[..index].ends_with(|c: char| c.is_whitespace()) || index == 0;
+        let after = rest[index + name.len()..].trim_start();
+
+        if preceded_by_space {
+            if let Some(value) = after.strip_prefix('=') {
+                let value = value.trim_start();
+                let quote = value.chars().next()?;
+                let value = &value[1..];
+                return value.find(quote).map(|end| &value[..end]);
+            }
+        }
+
+        rest = &rest[index + name.len()..];
+    }
+
+    None
+}
+
+fn svg_numbers(text: &str) -> Result<Vec<f32>, String> {
+    text.split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|number| !number.is_empty())
+        .map(|number| number.parse::<f32>().map_err(|_| format!("Invalid number {number}")))
+        .collect()
+}
+
+fn svg_path_segments(data: &str) -> Result<Vec<(Vec2, Vec2)>, String> {
+    // Put spaces around commands so they split like numbers
+    let spaced: String = data
+        .chars()
+        .flat_map(|c| if c.is_ascii_alphabetic() { vec![' ', c, ' '] } else { vec![c] })
+        .collect();
+
+    let mut segments = Vec::new();
+    let mut current = Vec2::ZERO;
+    let mut subpath_start = Vec2::ZERO;
+    let mut command = 'M';
+    let mut numbers: Vec<f32> = Vec::new();
+
+    let tokens = spaced.split(|c: char| c.is_whitespace() || c == ',').filter(|token| !token.is_empty());
+
+    for token in tokens {
+        if let Some(letter) = token.chars().next().filter(|c| c.is_ascii_alphabetic()) {
+            command = letter;
+            if command.eq_ignore_ascii_case(&'z') {
+                segments.push((current, subpath_start));
+                current = subpath_start;
+            }
+            continue;
+        }
+
+        numbers.push(token.parse::<f32>().map_err(|_| format!("Invalid path number {token}"))?);
+
+        let needed = match command.to_ascii_uppercase() {
+            'M' | 'L' => 2,
+            'H' | 'V' => 1,
+            other => return Err(format!("Unsupported path command {other}")),
+        };
+        if numbers.len() < needed { continue };
+
+        let relative = command.is_ascii_lowercase();
+        let base = if relative { current } else { Vec2::ZERO };
+        let next = match command.to_ascii_uppercase() {
+            'M' | 'L' => base + Vec2::new(numbers[0], numbers[1]),
+            'H' => Vec2::new(if relative { current.x + numbers[0] } else { numbers[0] }, current.y),
+            _ => Vec2::new(current.x, if relative { current.y + numbers[0] } else { numbers[0] }),
+        };
+        numbers.clear();
+
+        if command.eq_ignore_ascii_case(&'m') {
+            subpath_start = next;
+            // Coordinates after a move continue as lines
+            command = if relative { 'l' } else { 'L' };
+        } else {
+            segments.push((current, next));
+        }
+        current = next;
+    }
+
+    Ok(segments)
+}
+
+// Snaps SVG coordinates onto the maze grid, flipping y since SVG grows downwards
+fn grid_from_segments(segments: &[(Vec2, Vec2)]) -> Result<MazeGrid, String> {
+    let points: Vec<Vec2> = segments.iter().flat_map(|(start, end)| [*start, *end]).collect();
+    let min = points.iter().copied().reduce(Vec2::min).unwrap_or_default();
+    let max = points.iter().copied().reduce(Vec2::max).unwrap_or_default();
+
+    let tolerance = (max - min).max_element() * COORDINATE_TOLERANCE;
+
+    let spacing = |values: Vec<f32>| {
+        let mut values = values;
+        values.sort_by(f32::total_cmp);
+        values.windows(2).map(|pair| pair[1] - pair[0]).filter(|gap| *gap > tolerance).reduce(f32::min)
+    };
+
+    let cell_size = [
+        spacing(points.iter().map(|point| point.x).collect()),
+        spacing(points.iter().map(|point| point.y).collect()),
+    ]
+    .into_iter()
+    .flatten()
+    .reduce(f32::min)
+    .ok_or("Segments do not form a grid")?;
+
+    let to_grid = |point: Vec2| {
+        let cell = ((point - min) / cell_size).round();
+        IVec2::new(cell.x as i32, ((max.y - min.y) / cell_size).round() as i32 - cell.y as i32)
+    };
+
+    let size = to_grid(Vec2::new(max.x, min.y));
+    if size.max_element() as usize > MAX_IMPORTED_SIZE {
+        return Err(format!("A {}x{} maze is larger than {MAX_IMPORTED_SIZE} cells per side", size.x, size.y));
+    }
+    let mut grid = MazeGrid::new(size.x as usize, size.y as usize);
+
+    for (start, end) in segments {
+        let (start, end) = (to_grid(*start), to_grid(*end));
+        let (low, high) = (start.min(end), start.max(end));
+
+        if start.y == end.y {
+            for x in low.x..high.x {
+                grid.set_horizontal_wall(x as usize, start.y as usize, true);
+            }
+        } else if start.x == end.x {
+            for y in low.y..high.y {
+                grid.set_vertical_wall(start.x as usize, y as usize, true);
+            }
+        } else {
+            return Err(format!("Diagonal segment from {start} to {end} does not fit a grid maze"));
+        }
+    }
+
+    Ok(grid)
+}
+
+/*
+ * Generators mark the entrance and exit as gaps in the outer wall. They become the player start
+ * and the finish area unless those are marked already, then get closed so the player cannot walk
+ * out of the maze.
+ */
+fn level_from_imported_grid(name: &str, mut grid: MazeGrid, start: Option<UVec2>, finish: Option<UVec2>) -> Level {
+    let (width, height) = (grid.width as u32, grid.height as u32);
+
+    // Missing border edges per side, each with the cell inside the maze it opens into
+    let sides = [
+        (0..width).filter(|x| !grid.has_horizontal_wall(*x as usize, 0)).map(|x| UVec2::new(x, 0)).collect(),
+        (0..width).filter(|x| !grid.has_horizontal_wall(*x as usize, grid.height)).map(|x| UVec2::new(x, height - 1)).collect(),
+        (0..height).filter(|y| !grid.has_vertical_wall(0, *y as usize)).map(|y| UVec2::new(0, y)).collect(),
+        (0..height).filter(|y| !grid.has_vertical_wall(grid.width, *y as usize)).map(|y| UVec2::new(width - 1, y)).collect::<Vec<UVec2>>(),
+    ];
+
+    // Neighboring gaps on the same side form one wider opening
+    let openings: Vec<UVec2> = sides
+        .iter()
+        .flat_map(|gaps| {
+            gaps.iter().enumerate().filter_map(|(index, gap)| {
+                let continues_opening = index > 0 && (gap.as_ivec2() - gaps[index - 1].as_ivec2()).abs().max_element() == 1;
+                (!continues_opening).then_some(*gap)
+            })
+        })
+        .collect();
+
+    grid.close_border();
+
+    let mut openings = openings.into_iter().filter(|opening| Some(*opening) != start && Some(*opening) != finish);
+    let start = start.or_else(|| openings.next()).unwrap_or(UVec2::ZERO);
+    let finish = finish.or_else(|| openings.next()).unwrap_or_else(|| farthest_cell(&grid, start));
+
+    Level::from_grid(name, &grid, start, finish)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level_export::export_svg;
+
+    const ASCII_MAZE: &str = "
++--+--+
+|S    |
++  +--+
+|    F|
++--+--+
+";
+
+    fn grid(level: &Level) -> MazeGrid {
+        MazeGrid::from_segments(level.width, level.height, &level.walls)
+    }
+
+    #[test]
+    fn imports_ascii_maze() {
+        let level = import_ascii(ASCII_MAZE, "ascii").unwrap();
+        let grid = grid(&level);
+
+        assert_eq!((level.width, level.height), (2, 2));
+        assert_eq!(level.player_start, grid.cell_center(UVec2::new(0, 1)));
+        assert_eq!(grid.cell_at(level.finish_area_center()), Some(UVec2::new(1, 0)));
+        assert!(grid.has_horizontal_wall(1, 1));
+        assert!(!grid.has_horizontal_wall(0, 1));
+        assert!(!grid.has_vertical_wall(1, 0) && !grid.has_vertical_wall(1, 1));
+    }
+
+    #[test]
+    fn imports_svg_maze() {
+        // Openings at the bottom right and top left, a wall down the middle of the top row
+        let svg = r#"<svg><path d="M10 0 H20 V10 M10 20 H0 V0 M10 0 V10"/><line x1="20" y1="10" x2="20" y2="20"/></svg>"#;
+        let level = import_svg(svg, "svg").unwrap();
+        let grid = grid(&level);
+
+        assert_eq!((level.width, level.height), (2, 2));
+        assert_eq!(level.player_start, grid.cell_center(UVec2::new(1, 0)));
+        assert_eq!(grid.cell_at(level.finish_area_center()), Some(UVec2::new(0, 1)));
+        assert!(grid.has_vertical_wall(1, 1));
+        assert!(!grid.has_vertical_wall(1, 0));
+        // Openings are closed once they have been turned into the start and finish
+        assert!(grid.has_horizontal_wall(1, 0) && grid.has_horizontal_wall(0, 2));
+    }
+
+    #[test]
+    fn ascii_openings_become_start_and_finish() {
+        let maze = "
++  +--+
+|     |
++--+--+
+|     |
++--+  +
+";
+        let level = import_ascii(maze, "open").unwrap();
+        let grid = grid(&level);
+
+        assert_eq!(grid.cell_at(level.player_start), Some(UVec2::new(1, 0)));
+        assert_eq!(grid.cell_at(level.finish_area_center()), Some(UVec2::new(0, 1)));
+        assert!(grid.has_horizontal_wall(0, 2) && grid.has_horizontal_wall(1, 0));
+    }
+
+    #[test]
+    fn marked_ascii_maze_gets_closed_border() {
+        let level = import_ascii(&ASCII_MAZE.replacen("|S", " S", 1), "marked").unwrap();
+        let grid = grid(&level);
+
+        assert_eq!(level.player_start, grid.cell_center(UVec2::new(0, 1)));
+        assert!(grid.has_vertical_wall(0, 1));
+    }
+
+    #[test]
+    fn small_svg_keeps_its_grid() {
+        // Drawn with 0.005 unit cells, which an absolute tolerance would merge into one grid line
+        let svg = r#"<svg><path d="M0.005 0 H0.01 V0.01 H0 V0 M0.005 0.005 V0.01"/></svg>"#;
+        let level = import_svg(svg, "small").unwrap();
+
+        assert_eq!((level.width, level.height), (2, 2));
+    }
+
+    #[test]
+    fn huge_svg_is_refused() {
+        let svg = r#"<svg><path d="M0 0 H1000 V1000 H0 Z M1 0 V1000"/></svg>"#;
+
+        assert!(import_svg(svg, "huge").is_err());
+    }
+
+    #[test]
+    fn exported_svg_imports_the_same_walls() {
+        let level = import_ascii(ASCII_MAZE, "ascii").unwrap();
+        let reimported = import_svg(&export_svg(&level, false), "ascii").unwrap();
+
+        assert_eq!(grid(&reimported), grid(&level));
+    }
+}
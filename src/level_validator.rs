@@ -0,0 +1,272 @@
+use bevy::{
+    math::bounding::Aabb2d,
+    prelude::*,
+};
+use std::fmt;
+
+use crate::coins::COIN_SIZE;
+use crate::enemy::ENEMY_SIZE;
+use crate::level::{Level, MAX_SWITCHES};
+use crate::player::player::PLAYER_SIZE;
+use crate::maze::{MazeGrid, MazeTopology};
+use crate::walls::{wall_push_direction, wall_transform};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LevelProblem {
+    PlayerStartOutsideMaze,
+    PlayerStartInsideWall,
+    FinishAreaUnreachable,
+    CoinUnreachable(Vec2),
+    CoinInsideWall(Vec2),
+    EnemyInsideWall(Vec2),
+    OverlappingWalls([f32; 4], [f32; 4]),
+    BorderGap(BorderSide, usize),
+    // Gap in the outer wall of a hex or polar maze, at the middle of the missing wall
+    OuterWallGap(Vec2),
+    FinishFloorMissing(usize),
+    StairsOutsideMaze(Vec2),
+    KeyUnreachable(Vec2),
+    // Door or gate whose segment is not an open passage between two cells
+    LockOutsidePassage([f32; 4]),
+    GateSwitchMissing(usize),
+    // Zoom limits out of order or negative look ahead and dead zone
+    CameraSettingsInvalid,
+    // Music would never play
+    MusicSpeedInvalid,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BorderSide {
+    Bottom,
+    Top,
+    Left,
+    Right,
+}
+
+impl fmt::Display for LevelProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LevelProblem::PlayerStartOutsideMaze => write!(f, "player start is outside the maze"),
+            LevelProblem::PlayerStartInsideWall => write!(f, "player start overlaps a wall"),
+            LevelProblem::FinishAreaUnreachable => write!(f, "finish area cannot be reached from the player start"),
+            LevelProblem::CoinUnreachable(position) => {
+                write!(f, "coin at ({}, {}) cannot be reached", position.x, position.y)
+            }
+            LevelProblem::CoinInsideWall(position) => {
+                write!(f, "coin at ({}, {}) overlaps a wall", position.x, position.y)
+            }
+            LevelProblem::EnemyInsideWall(position) => {
+                write!(f, "enemy at ({}, {}) overlaps a wall", position.x, position.y)
+            }
+            LevelProblem::OverlappingWalls(a, b) => write!(f, "walls {a:?} and {b:?} overlap"),
+            LevelProblem::BorderGap(side, cell) => {
+                write!(f, "gap in the {} border at cell {cell}", format!("{side:?}").to_lowercase())
+            }
+            LevelProblem::OuterWallGap(position) => {
+                write!(f, "gap in the outer wall at ({}, {})", position.x, position.y)
+            }
+            LevelProblem::FinishFloorMissing(floor) => write!(f, "finish area is on floor {floor}, which does not exist"),
+            LevelProblem::StairsOutsideMaze(position) => {
+                write!(f, "stairs at ({}, {}) do not lead between two floors of the maze", position.x, position.y)
+            }
+            LevelProblem::KeyUnreachable(position) => {
+                write!(f, "key at ({}, {}) cannot be reached", position.x, position.y)
+            }
+            LevelProblem::LockOutsidePassage(segment) => write!(f, "door or gate {segment:?} does not block a passage"),
+            LevelProblem::GateSwitchMissing(switch) => {
+                write!(f, "gate opened by switch {switch}, which is missing or beyond the first {MAX_SWITCHES}")
+            }
+            LevelProblem::CameraSettingsInvalid => {
+                write!(f, "camera zoom must lie between its positive minimum and maximum, look ahead and dead zone cannot be negative")
+            }
+            LevelProblem::MusicSpeedInvalid => write!(f, "music speed must be positive"),
+        }
+    }
+}
+
+// Checks a level for anything that keeps it from being played as intended, empty when it is fine
+pub fn validate_level(level: &Level) -> Vec<LevelProblem> {
+    let mut problems = Vec::new();
+
+    if inside_wall(level, 0, level.player_start, PLAYER_SIZE) {
+        problems.push(LevelProblem::PlayerStartInsideWall);
+    }
+
+    if level.finish_floor >= level.floor_count() {
+        problems.push(LevelProblem::FinishFloorMissing(level.finish_floor));
+    }
+
+    for stairs in &level.stairs {
+        let inside = |floor: usize| floor < level.floor_count() && level.floor_maze(floor).cell_at(stairs.position).is_some();
+        if !inside(stairs.floor) || !inside(stairs.floor + 1) {
+            problems.push(LevelProblem::StairsOutsideMaze(stairs.position));
+        }
+    }
+
+    match level.search() {
+        Some(search) => {
+            if !search.reachable(level.finish_floor, level.finish_area_center()) {
+                problems.push(LevelProblem::FinishAreaUnreachable);
+            }
+
+            for floor in 0..level.floor_count() {
+                for coin in level.floor_coins(floor) {
+                    if !search.reachable(floor, *coin) {
+                        problems.push(LevelProblem::CoinUnreachable(*coin));
+                    }
+                }
+            }
+
+            for key in &level.keys {
+                if !search.reachable(key.floor, key.position) {
+                    problems.push(LevelProblem::KeyUnreachable(key.position));
+                }
+            }
+        }
+        None => problems.push(LevelProblem::PlayerStartOutsideMaze),
+    }
+
+    for floor in 0..level.floor_count() {
+        validate_floor(level, floor, &mut problems);
+    }
+
+    let locks = level
+        .doors
+        .iter()
+        .map(|door| (door.floor, door.segment))
+        .chain(level.gates.iter().map(|gate| (gate.floor, gate.segment)));
+
+    for (floor, segment) in locks {
+        let in_passage = floor < level.floor_count() && {
+            let maze = level.floor_maze(floor);
+            maze.edge_with_segment(&segment)
+                .is_some_and(|edge| !maze.edges()[edge].wall && maze.edges()[edge].neighbor.is_some())
+        };
+
+        if !in_passage {
+            problems.push(LevelProblem::LockOutsidePassage(segment));
+        }
+    }
+
+    for gate in &level.gates {
+        if gate.switch >= level.switches.len().min(MAX_SWITCHES) {
+            problems.push(LevelProblem::GateSwitchMissing(gate.switch));
+        }
+    }
+
+    let camera = &level.camera;
+    let zoom_in_range = 0. < camera.min_zoom && camera.min_zoom <= camera.zoom && camera.zoom <= camera.max_zoom;
+    if !zoom_in_range || camera.look_ahead < 0. || camera.dead_zone.min_element() < 0. {
+        problems.push(LevelProblem::CameraSettingsInvalid);
+    }
+
+    if level.music.speed <= 0. {
+        problems.push(LevelProblem::MusicSpeedInvalid);
+    }
+
+    problems
+}
+
+// Same test the game uses for collisions, so rotated walls count with their real shape
+fn inside_wall(level: &Level, floor: usize, position: Vec2, size: Vec2) -> bool {
+    let bounds = Aabb2d::new(position, size / 2.);
+
+    level.floor_walls(floor).iter().any(|[start_x, start_y, end_x, end_y]| {
+        wall_push_direction(&bounds, &wall_transform(level.topology, *start_x, *start_y, *end_x, *end_y)).is_some()
+    })
+}
+
+// Problems that only concern the walls and items of a single floor
+fn validate_floor(level: &Level, floor: usize, problems: &mut Vec<LevelProblem>) {
+    for coin in level.floor_coins(floor) {
+        if inside_wall(level, floor, *coin, COIN_SIZE) {
+            problems.push(LevelProblem::CoinInsideWall(*coin));
+        }
+    }
+
+    for enemy in level.floor_enemies(floor) {
+        if inside_wall(level, floor, *enemy, ENEMY_SIZE) {
+            problems.push(LevelProblem::EnemyInsideWall(*enemy));
+        }
+    }
+
+    let walls = level.floor_walls(floor);
+    for (index, a) in walls.iter().enumerate() {
+        for b in &walls[index + 1..] {
+            if segments_overlap(a, b) {
+                problems.push(LevelProblem::OverlappingWalls(*a, *b));
+            }
+        }
+    }
+
+    if level.topology != MazeTopology::Rectangular {
+        let maze = level.floor_maze(floor);
+        for edge in maze.edges().iter().filter(|edge| edge.neighbor.is_none() && !edge.wall) {
+            let [start_x, start_y, end_x, end_y] = edge.segment;
+            problems.push(LevelProblem::OuterWallGap(Vec2::new(start_x + end_x, start_y + end_y) / 2.));
+        }
+
+        return;
+    }
+
+    let grid = MazeGrid::from_segments(level.width, level.height, walls);
+
+    for x in 0..grid.width {
+        if !grid.has_horizontal_wall(x, 0) {
+            problems.push(LevelProblem::BorderGap(BorderSide::Bottom, x));
+        }
+        if !grid.has_horizontal_wall(x, grid.height) {
+            problems.push(LevelProblem::BorderGap(BorderSide::Top, x));
+        }
+    }
+
+    for y in 0..grid.height {
+        if !grid.has_vertical_wall(0, y) {
+            problems.push(LevelProblem::BorderGap(BorderSide::Left, y));
+        }
+        if !grid.has_vertical_wall(grid.width, y) {
+            problems.push(LevelProblem::BorderGap(BorderSide::Right, y));
+        }
+    }
+}
+
+// Collinear segments sharing more than an end point, touching corners are fine
+fn segments_overlap(
+    [a_start_x, a_start_y, a_end_x, a_end_y]: &[f32; 4],
+    [b_start_x, b_start_y, b_end_x, b_end_y]: &[f32; 4],
+) -> bool {
+    let overlap = |a_start: f32, a_end: f32, b_start: f32, b_end: f32| {
+        a_start.max(a_end).min(b_start.max(b_end)) - a_start.min(a_end).max(b_start.min(b_end)) > 0.
+    };
+
+    let a_horizontal = a_start_y == a_end_y;
+    let b_horizontal = b_start_y == b_end_y;
+    let a_vertical = a_start_x == a_end_x;
+    let b_vertical = b_start_x == b_end_x;
+
+    (a_horizontal && b_horizontal && a_start_y == b_start_y && overlap(*a_start_x, *a_end_x, *b_start_x, *b_end_x))
+        || (a_vertical && b_vertical && a_start_x == b_start_x && overlap(*a_start_y, *a_end_y, *b_start_y, *b_end_y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maze::MAZE_ORIGIN;
+
+    #[test]
+    fn reports_gap_and_enemy_inside_wall() {
+        let mut grid = MazeGrid::new(2, 2);
+        grid.close_border();
+        grid.set_horizontal_wall(1, 0, false);
+        grid.set_vertical_wall(1, 1, true);
+
+        let mut level = Level::from_grid("broken", &grid, UVec2::new(0, 0), UVec2::new(1, 1));
+        let enemy = grid.cell_center(UVec2::new(0, 1)).with_x(MAZE_ORIGIN.x);
+        level.enemies.push(enemy);
+
+        let problems = validate_level(&level);
+        assert!(problems.contains(&LevelProblem::BorderGap(BorderSide::Bottom, 1)));
+        assert!(problems.contains(&LevelProblem::EnemyInsideWall(enemy)));
+        assert!(!problems.contains(&LevelProblem::FinishAreaUnreachable));
+    }
+}
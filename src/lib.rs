@@ -0,0 +1,29 @@
+pub mod player;
+pub mod audio;
+pub mod walls;
+pub mod collider;
+pub mod doors;
+pub mod camera;
+pub mod finish_area;
+pub mod floors;
+pub mod fog;
+pub mod maze;
+pub mod maze_generator;
+pub mod enemy;
+pub mod enemy_audio;
+pub mod coins;
+pub mod editor;
+pub mod music;
+pub mod pause;
+pub mod menu;
+pub mod minimap;
+pub mod game_state;
+pub mod ghost;
+pub mod level;
+pub mod level_export;
+pub mod level_import;
+pub mod level_validator;
+pub mod run_timer;
+pub mod save_data;
+pub mod save_slot;
+pub mod settings;
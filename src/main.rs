@@ -1,75 +1,205 @@
 use bevy::{
-    input::common_conditions::input_pressed,
+    input::common_conditions::input_just_pressed,
     prelude::*,
 };
 
-mod player;
-mod walls;
-mod collider;
-mod camera;
-mod finish_area;
-mod maze;
-mod enemy;
-mod coins;
-mod music;
-mod menu;
-mod game_state;
-
-use crate::player::player::{
+use labyrinth_bevy::player::player::{
     spawn_player,
     move_player,
     execute_player_walking_animations,
     trigger_player_walking_animation,
 };
-use crate::player::player_attack::{
+use labyrinth_bevy::player::player_attack::{
     player_attack,
     player_attack_check_for_enemy_collisions,
     remove_player_attacks,
     cooldown_player_attack_timer,
 };
-use crate::walls::{
+use labyrinth_bevy::walls::{
     spawn_walls,
     player_wall_collistions,
+    WallBumpEvent,
+};
+use labyrinth_bevy::audio::SfxPlugin;
+use labyrinth_bevy::enemy_audio::EnemyAudioPlugin;
+use labyrinth_bevy::collider::CollisionEvent;
+use labyrinth_bevy::doors::{
+    HeldKeys,
+    spawn_keys,
+    spawn_doors,
+    spawn_switches,
+    spawn_gates,
+    spawn_key_ui,
+    reset_held_keys,
+    pick_up_keys,
+    open_doors,
+    press_switches,
+    update_gates,
+    update_key_ui,
+};
+use labyrinth_bevy::camera::{
+    CameraPlugin,
+    reset_camera,
+    add_camera_trauma,
+    update_camera,
+};
+use labyrinth_bevy::finish_area::{FinishEvent, check_for_players_in_finish_area, spawn_finish_area};
+use labyrinth_bevy::floors::{
+    CurrentFloor,
+    reset_current_floor,
+    spawn_floor_ui,
+    spawn_stairs,
+    update_floor_ui,
+    update_floor_visibility,
+    use_stairs,
+};
+use labyrinth_bevy::fog::{
+    FogOfWar,
+    spawn_fog,
+    update_fog,
+    update_fog_overlay,
 };
-use crate::collider::CollisionEvent;
-use crate::camera::{setup_camera, update_camera};
-use crate::finish_area::spawn_finish_area;
-use crate::enemy::{
+use labyrinth_bevy::minimap::{
+    Minimap,
+    spawn_minimap,
+    toggle_minimap,
+    update_minimap,
+};
+use labyrinth_bevy::enemy::{
     spawn_enemies,
     apply_enemy_velocity,
     update_enemy_movement,
     check_for_player_collisions_with_enemies,
 };
-use crate::coins::{
+use labyrinth_bevy::coins::{
     Score,
     spawn_coins,
     spawn_scoreboard,
+    reset_score,
     update_scoreboard,
     check_for_player_collisions_with_coins,
 };
-use crate::music::play_music;
-use crate::menu::{
+use labyrinth_bevy::editor::{
+    OnEditorScreen,
+    editor_setup,
+    editor_cleanup,
+    editor_mouse_input,
+    editor_keyboard_input,
+    draw_editor,
+    update_editor_status_ui,
+};
+use labyrinth_bevy::music::MusicPlugin;
+use labyrinth_bevy::pause::{
+    toggle_pause,
+    pause_on_focus_lost,
+    pause_game,
+    resume_game,
+};
+use labyrinth_bevy::menu::{
     MenuState,
     OnMainMenuScreen,
+    OnPauseMenuScreen,
+    OnLevelSelectScreen,
+    OnCustomMazeScreen,
+    OnSettingsMenuScreen,
     menu_setup,
     menu_action,
     main_menu_setup,
+    level_select_menu_setup,
+    custom_maze_menu_setup,
+    update_maze_preview,
+    pause_menu_setup,
+    settings_menu_setup,
+    audio_settings_menu_setup,
+    display_settings_menu_setup,
+    controls_settings_menu_setup,
+    accessibility_settings_menu_setup,
+    gameplay_settings_menu_setup,
+    coop_settings_menu_setup,
+    coop_controls_settings_menu_setup,
+    setting_action,
+    update_setting_labels,
+    stop_rebinding,
 };
-use crate::game_state::{
+use labyrinth_bevy::game_state::{
     GameState,
+    PauseState,
+    OnGameScreen,
+    restart_game,
+    finish_restart,
+};
+use labyrinth_bevy::ghost::{
+    BestRun,
+    CurrentRun,
+    spawn_ghost,
+    reset_current_run,
+    record_player_path,
+    save_best_run,
+    replay_ghost,
+    update_split_ui,
+};
+use labyrinth_bevy::level::{CurrentLevel, Levels};
+use labyrinth_bevy::run_timer::{
+    RunTimer,
+    spawn_run_timer_ui,
+    start_run_timer,
+    tick_run_timer,
+    stop_run_timer,
+    update_run_timer_ui,
+};
+use labyrinth_bevy::save_data::{
+    SaveData,
+    record_level_results,
+};
+use labyrinth_bevy::save_slot::{
+    SaveSlot,
+    save_level,
+    resume_level,
+    clear_finished_save_slot,
+};
+use labyrinth_bevy::settings::{
+    RebindingControl,
+    key_name,
+    movement_pressed,
+    rebind_control,
+    apply_audio_settings,
+    apply_display_settings,
+    apply_color_palette,
 };
-
-fn setup(mut commands: Commands) {
-    commands.spawn(Camera2dBundle::default());
-}
 
 fn explain_game(
     commands: &mut Commands,
+    save_data: &Res<SaveData>,
 ) {
+    let controls = &save_data.settings.controls;
+    let second_player_controls = &save_data.settings.second_player_controls;
+
+    let coop = if save_data.settings.two_players {
+        format!(
+            "\nPlayer 2 uses {}/{}/{}/{} to move and {} to attack.",
+            key_name(second_player_controls.left),
+            key_name(second_player_controls.right),
+            key_name(second_player_controls.up),
+            key_name(second_player_controls.down),
+            key_name(second_player_controls.attack),
+        )
+    } else {
+        String::new()
+    };
+
     commands.spawn((
+        OnGameScreen,
         TextBundle::from_sections([
             TextSection::new(
-                "Use arrow keys to move, press x to attack.\nAvoid enemies and try to find the green finish area.",
+                format!(
+                    "Use {}/{}/{}/{} to move, press {} to attack, press r to restart, press F5 to save, press m for the minimap.{}\nPress +/- to zoom and Tab for an overview of the maze.\nAvoid enemies and try to find the finish area.",
+                    key_name(controls.left),
+                    key_name(controls.right),
+                    key_name(controls.up),
+                    key_name(controls.down),
+                    key_name(controls.attack),
+                    coop,
+                ),
                 TextStyle {
                     font_size: 20.,
                     color: Color::srgb(80., 80., 80.),
@@ -91,15 +221,32 @@ fn game_setup(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+    best_run: Res<BestRun>,
+    save_data: Res<SaveData>,
+    current_level: Res<CurrentLevel>,
 ) {
-    explain_game(&mut commands);
-    spawn_walls(&mut commands);
-    spawn_player(&mut commands, &asset_server, &mut texture_atlas_layouts);
-    spawn_finish_area(&mut commands);
-    spawn_enemies(&mut commands, &asset_server);
-    spawn_coins(&mut commands);
+    let level = &current_level.level;
+
+    explain_game(&mut commands, &save_data);
+    for floor in 0..level.floor_count() {
+        spawn_walls(&mut commands, level.topology, level.floor_walls(floor), floor);
+        spawn_enemies(&mut commands, &asset_server, level.floor_enemies(floor), floor);
+        spawn_coins(&mut commands, level.floor_coins(floor), floor);
+    }
+    spawn_stairs(&mut commands, &level.stairs);
+    spawn_keys(&mut commands, &level.keys);
+    spawn_doors(&mut commands, level.topology, &level.doors);
+    spawn_switches(&mut commands, &level.switches);
+    spawn_gates(&mut commands, level.topology, &level.gates);
+    spawn_key_ui(&mut commands, &level.keys);
+    spawn_floor_ui(&mut commands, level.floor_count());
+    for index in 0..save_data.settings.player_count() {
+        spawn_player(&mut commands, &asset_server, &mut texture_atlas_layouts, level.player_start, index);
+    }
+    spawn_finish_area(&mut commands, level.finish_area_location, level.finish_area_size, level.finish_floor);
     spawn_scoreboard(&mut commands);
-    play_music(&mut commands, &asset_server);
+    spawn_run_timer_ui(&mut commands);
+    spawn_ghost(&mut commands, &asset_server, &mut texture_atlas_layouts, &best_run, &current_level.id);
 }
 
 fn despawn_screen<T: Component>(to_despawn: Query<Entity, With<T>>, mut commands: Commands) {
@@ -109,56 +256,190 @@ fn despawn_screen<T: Component>(to_despawn: Query<Entity, With<T>>, mut commands
 }
 
 fn main() {
+    let levels = Levels::load();
+    let first_level = CurrentLevel::from(levels.first());
+
     App::new()
-        .add_plugins(DefaultPlugins)
+        .add_plugins((DefaultPlugins, CameraPlugin, SfxPlugin, MusicPlugin, EnemyAudioPlugin))
         .init_state::<MenuState>()
         .init_state::<GameState>()
+        .add_sub_state::<PauseState>()
         .add_event::<CollisionEvent>()
+        .add_event::<WallBumpEvent>()
+        .add_event::<FinishEvent>()
         .insert_resource(Score(0))
-        .add_systems(Startup, setup)
-        .add_systems(Startup, setup_camera.run_if(in_state(GameState::Game)))
+        .init_resource::<CurrentRun>()
+        .init_resource::<RunTimer>()
+        .init_resource::<CurrentFloor>()
+        .init_resource::<HeldKeys>()
+        .init_resource::<FogOfWar>()
+        .init_resource::<Minimap>()
+        .insert_resource(SaveData::load())
+        .insert_resource(SaveSlot::load())
+        .insert_resource(BestRun::load())
+        .insert_resource(first_level)
+        .insert_resource(levels)
+        .init_resource::<RebindingControl>()
         .add_systems(OnEnter(MenuState::Main), main_menu_setup)
+        .add_systems(OnExit(MenuState::Main), despawn_screen::<OnMainMenuScreen>)
+        .add_systems(OnEnter(MenuState::LevelSelect), level_select_menu_setup)
+        .add_systems(OnExit(MenuState::LevelSelect), despawn_screen::<OnLevelSelectScreen>)
+        .add_systems(OnEnter(MenuState::CustomMaze), custom_maze_menu_setup)
+        .add_systems(OnExit(MenuState::CustomMaze), despawn_screen::<OnCustomMazeScreen>)
+        .add_systems(OnEnter(MenuState::Pause), pause_menu_setup)
+        .add_systems(OnExit(MenuState::Pause), despawn_screen::<OnPauseMenuScreen>)
+        .add_systems(OnEnter(MenuState::Settings), settings_menu_setup)
+        .add_systems(OnEnter(MenuState::SettingsAudio), audio_settings_menu_setup)
+        .add_systems(OnEnter(MenuState::SettingsDisplay), display_settings_menu_setup)
+        .add_systems(OnEnter(MenuState::SettingsControls), controls_settings_menu_setup)
+        .add_systems(OnEnter(MenuState::SettingsAccessibility), accessibility_settings_menu_setup)
+        .add_systems(OnEnter(MenuState::SettingsGameplay), gameplay_settings_menu_setup)
+        .add_systems(OnEnter(MenuState::SettingsCoop), coop_settings_menu_setup)
+        .add_systems(OnEnter(MenuState::SettingsCoopControls), coop_controls_settings_menu_setup)
+        .add_systems(
+            OnExit(MenuState::Settings),
+            despawn_screen::<OnSettingsMenuScreen>
+        )
+        .add_systems(
+            OnExit(MenuState::SettingsAudio),
+            despawn_screen::<OnSettingsMenuScreen>
+        )
+        .add_systems(
+            OnExit(MenuState::SettingsDisplay),
+            despawn_screen::<OnSettingsMenuScreen>
+        )
+        .add_systems(
+            OnExit(MenuState::SettingsControls),
+            (despawn_screen::<OnSettingsMenuScreen>, stop_rebinding)
+        )
+        .add_systems(
+            OnExit(MenuState::SettingsAccessibility),
+            despawn_screen::<OnSettingsMenuScreen>
+        )
+        .add_systems(
+            OnExit(MenuState::SettingsGameplay),
+            despawn_screen::<OnSettingsMenuScreen>
+        )
+        .add_systems(
+            OnExit(MenuState::SettingsCoop),
+            despawn_screen::<OnSettingsMenuScreen>
+        )
+        .add_systems(
+            OnExit(MenuState::SettingsCoopControls),
+            (despawn_screen::<OnSettingsMenuScreen>, stop_rebinding)
+        )
         .add_systems(OnEnter(GameState::Menu), menu_setup)
-        .add_systems(OnExit(GameState::Menu), despawn_screen::<OnMainMenuScreen>)
-        .add_systems(OnEnter(GameState::Game), game_setup)
+        .add_systems(
+            OnEnter(GameState::Game),
+            (game_setup, spawn_fog, spawn_minimap, reset_score, reset_current_run, reset_current_floor, reset_held_keys, start_run_timer, resume_level, reset_camera).chain()
+        )
+        .add_systems(OnExit(GameState::Game), despawn_screen::<OnGameScreen>)
+        .add_systems(OnEnter(GameState::Restart), finish_restart)
+        .add_systems(OnEnter(GameState::Editor), editor_setup)
+        .add_systems(OnExit(GameState::Editor), (despawn_screen::<OnEditorScreen>, editor_cleanup))
+        .add_systems(
+            Update,
+            (editor_mouse_input, editor_keyboard_input, draw_editor, update_editor_status_ui)
+            .chain()
+            .run_if(in_state(GameState::Editor))
+        )
+        .add_systems(
+            Update,
+            restart_game
+            .run_if(input_just_pressed(KeyCode::KeyR))
+            .run_if(in_state(PauseState::Running))
+        )
+        .add_systems(
+            Update,
+            save_level
+            .run_if(input_just_pressed(KeyCode::F5))
+            .run_if(in_state(PauseState::Running))
+        )
+        .add_systems(
+            Update,
+            toggle_minimap
+            .run_if(input_just_pressed(KeyCode::KeyM))
+            .run_if(in_state(PauseState::Running))
+        )
         .add_systems(
             Update,
             execute_player_walking_animations
-            .run_if(in_state(GameState::Game))
+            .run_if(in_state(PauseState::Running))
         )
         .add_systems(
             Update,
-            (
-                trigger_player_walking_animation.run_if(input_pressed(KeyCode::ArrowLeft)),
-                trigger_player_walking_animation.run_if(input_pressed(KeyCode::ArrowRight)),
-                trigger_player_walking_animation.run_if(input_pressed(KeyCode::ArrowUp)),
-                trigger_player_walking_animation.run_if(input_pressed(KeyCode::ArrowDown)),
-            )
-            .run_if(in_state(GameState::Game))
+            trigger_player_walking_animation
+            .run_if(movement_pressed)
+            .run_if(in_state(PauseState::Running))
         )
         .add_systems(
             FixedUpdate,
             (
-                apply_enemy_velocity,
-                move_player,
-                player_wall_collistions,
-                update_camera,
-                update_enemy_movement,
-                check_for_player_collisions_with_enemies,
-                player_attack,
-                player_attack_check_for_enemy_collisions,
-                remove_player_attacks,
-                cooldown_player_attack_timer,
-                update_scoreboard,
-                check_for_player_collisions_with_coins,
+                (
+                    apply_enemy_velocity,
+                    move_player,
+                    check_for_players_in_finish_area,
+                    open_doors,
+                    player_wall_collistions,
+                    use_stairs,
+                    pick_up_keys,
+                    press_switches,
+                    update_gates,
+                    update_camera,
+                    update_enemy_movement,
+                    check_for_player_collisions_with_enemies,
+                    player_attack,
+                    player_attack_check_for_enemy_collisions,
+                    remove_player_attacks,
+                    cooldown_player_attack_timer,
+                    update_scoreboard,
+                    check_for_player_collisions_with_coins,
+                )
+                .chain(),
+                (
+                    tick_run_timer,
+                    record_player_path,
+                    record_level_results,
+                    stop_run_timer,
+                    save_best_run,
+                    clear_finished_save_slot,
+                    replay_ghost,
+                    update_split_ui,
+                    update_run_timer_ui,
+                    update_fog,
+                    update_fog_overlay,
+                    update_floor_visibility,
+                    update_floor_ui,
+                    update_key_ui,
+                    update_minimap,
+                    add_camera_trauma,
+                )
+                .chain(),
             )
             .chain()
+            .run_if(in_state(PauseState::Running))
+        )
+        .add_systems(
+            Update,
+            (menu_action, setting_action, update_setting_labels, update_maze_preview, rebind_control)
+            .run_if(in_state(GameState::Menu).or_else(in_state(PauseState::Paused)))
+        )
+        .add_systems(OnEnter(PauseState::Paused), pause_game)
+        .add_systems(OnExit(PauseState::Paused), resume_game)
+        .add_systems(
+            Update,
+            (toggle_pause, pause_on_focus_lost)
             .run_if(in_state(GameState::Game))
         )
         .add_systems(
             Update,
-            menu_action.run_if(in_state(GameState::Menu))
+            (
+                apply_audio_settings,
+                apply_display_settings,
+            )
+            .run_if(resource_changed::<SaveData>)
         )
+        .add_systems(Update, apply_color_palette)
         .run();
 }
 
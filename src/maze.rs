@@ -1,447 +1,677 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::f32::consts::TAU;
 
 use crate::walls::WallBundle;
 
-pub fn draw_maze(commands: &mut Commands) {
-    // Generated from https://mazesforfun.com/maze-generator
-    commands.spawn(WallBundle::new(1., 1., 1., 31.));
-    commands.spawn(WallBundle::new(1., 31., 1., 61.));
-    commands.spawn(WallBundle::new(1., 61., 1., 91.));
-    commands.spawn(WallBundle::new(1., 91., 1., 121.));
-    commands.spawn(WallBundle::new(1., 121., 1., 151.));
-    commands.spawn(WallBundle::new(1., 151., 1., 181.));
-    commands.spawn(WallBundle::new(1., 181., 1., 211.));
-    commands.spawn(WallBundle::new(1., 211., 1., 241.));
-    commands.spawn(WallBundle::new(1., 241., 1., 271.));
-    commands.spawn(WallBundle::new(1., 271., 1., 301.));
-    commands.spawn(WallBundle::new(1., 301., 1., 331.));
-    commands.spawn(WallBundle::new(1., 331., 1., 361.));
-    commands.spawn(WallBundle::new(1., 361., 1., 391.));
-    commands.spawn(WallBundle::new(1., 391., 1., 421.));
-    commands.spawn(WallBundle::new(1., 421., 1., 451.));
-    commands.spawn(WallBundle::new(1., 451., 1., 481.));
-    commands.spawn(WallBundle::new(1., 481., 1., 511.));
-    commands.spawn(WallBundle::new(1., 511., 1., 541.));
-    commands.spawn(WallBundle::new(1., 541., 1., 571.));
-    commands.spawn(WallBundle::new(1., 571., 1., 601.));
-    commands.spawn(WallBundle::new(1., 31., 31., 31.));
-    commands.spawn(WallBundle::new(1., 91., 31., 91.));
-    commands.spawn(WallBundle::new(1., 181., 31., 181.));
-    commands.spawn(WallBundle::new(1., 241., 31., 241.));
-    commands.spawn(WallBundle::new(31., 241., 31., 271.));
-    commands.spawn(WallBundle::new(31., 271., 31., 301.));
-    commands.spawn(WallBundle::new(31., 331., 31., 361.));
-    commands.spawn(WallBundle::new(1., 391., 31., 391.));
-    commands.spawn(WallBundle::new(31., 391., 31., 421.));
-    commands.spawn(WallBundle::new(31., 451., 31., 481.));
-    commands.spawn(WallBundle::new(31., 511., 31., 541.));
-    commands.spawn(WallBundle::new(1., 571., 31., 571.));
-    commands.spawn(WallBundle::new(1., 601., 31., 601.));
-    commands.spawn(WallBundle::new(31., 1., 61., 1.));
-    commands.spawn(WallBundle::new(61., 1., 61., 31.));
-    commands.spawn(WallBundle::new(61., 31., 61., 61.));
-    commands.spawn(WallBundle::new(31., 61., 61., 61.));
-    commands.spawn(WallBundle::new(31., 91., 61., 91.));
-    commands.spawn(WallBundle::new(31., 121., 61., 121.));
-    commands.spawn(WallBundle::new(31., 151., 61., 151.));
-    commands.spawn(WallBundle::new(31., 181., 61., 181.));
-    commands.spawn(WallBundle::new(31., 211., 61., 211.));
-    commands.spawn(WallBundle::new(31., 241., 61., 241.));
-    commands.spawn(WallBundle::new(61., 271., 61., 301.));
-    commands.spawn(WallBundle::new(61., 301., 61., 331.));
-    commands.spawn(WallBundle::new(31., 331., 61., 331.));
-    commands.spawn(WallBundle::new(31., 361., 61., 361.));
-    commands.spawn(WallBundle::new(61., 361., 61., 391.));
-    commands.spawn(WallBundle::new(31., 421., 61., 421.));
-    commands.spawn(WallBundle::new(61., 421., 61., 451.));
-    commands.spawn(WallBundle::new(61., 451., 61., 481.));
-    commands.spawn(WallBundle::new(31., 481., 61., 481.));
-    commands.spawn(WallBundle::new(31., 511., 61., 511.));
-    commands.spawn(WallBundle::new(31., 541., 61., 541.));
-    commands.spawn(WallBundle::new(31., 571., 61., 571.));
-    commands.spawn(WallBundle::new(31., 601., 61., 601.));
-    commands.spawn(WallBundle::new(61., 1., 91., 1.));
-    commands.spawn(WallBundle::new(61., 31., 91., 31.));
-    commands.spawn(WallBundle::new(91., 61., 91., 91.));
-    commands.spawn(WallBundle::new(61., 91., 91., 91.));
-    commands.spawn(WallBundle::new(91., 91., 91., 121.));
-    commands.spawn(WallBundle::new(61., 121., 91., 121.));
-    commands.spawn(WallBundle::new(61., 151., 91., 151.));
-    commands.spawn(WallBundle::new(91., 151., 91., 181.));
-    commands.spawn(WallBundle::new(91., 181., 91., 211.));
-    commands.spawn(WallBundle::new(61., 211., 91., 211.));
-    commands.spawn(WallBundle::new(61., 241., 91., 241.));
-    commands.spawn(WallBundle::new(61., 271., 91., 271.));
-    commands.spawn(WallBundle::new(91., 271., 91., 301.));
-    commands.spawn(WallBundle::new(91., 301., 91., 331.));
-    commands.spawn(WallBundle::new(91., 331., 91., 361.));
-    commands.spawn(WallBundle::new(91., 361., 91., 391.));
-    commands.spawn(WallBundle::new(91., 391., 91., 421.));
-    commands.spawn(WallBundle::new(91., 421., 91., 451.));
-    commands.spawn(WallBundle::new(91., 451., 91., 481.));
-    commands.spawn(WallBundle::new(91., 481., 91., 511.));
-    commands.spawn(WallBundle::new(61., 511., 91., 511.));
-    commands.spawn(WallBundle::new(61., 541., 91., 541.));
-    commands.spawn(WallBundle::new(61., 571., 91., 571.));
-    commands.spawn(WallBundle::new(61., 601., 91., 601.));
-    commands.spawn(WallBundle::new(91., 1., 121., 1.));
-    commands.spawn(WallBundle::new(91., 31., 121., 31.));
-    commands.spawn(WallBundle::new(91., 61., 121., 61.));
-    commands.spawn(WallBundle::new(121., 61., 121., 91.));
-    commands.spawn(WallBundle::new(121., 121., 121., 151.));
-    commands.spawn(WallBundle::new(91., 151., 121., 151.));
-    commands.spawn(WallBundle::new(121., 181., 121., 211.));
-    commands.spawn(WallBundle::new(121., 211., 121., 241.));
-    commands.spawn(WallBundle::new(91., 241., 121., 241.));
-    commands.spawn(WallBundle::new(91., 271., 121., 271.));
-    commands.spawn(WallBundle::new(91., 331., 121., 331.));
-    commands.spawn(WallBundle::new(121., 361., 121., 391.));
-    commands.spawn(WallBundle::new(121., 391., 121., 421.));
-    commands.spawn(WallBundle::new(121., 421., 121., 451.));
-    commands.spawn(WallBundle::new(91., 481., 121., 481.));
-    commands.spawn(WallBundle::new(121., 511., 121., 541.));
-    commands.spawn(WallBundle::new(121., 541., 121., 571.));
-    commands.spawn(WallBundle::new(91., 571., 121., 571.));
-    commands.spawn(WallBundle::new(91., 601., 121., 601.));
-    commands.spawn(WallBundle::new(121., 1., 151., 1.));
-    commands.spawn(WallBundle::new(121., 31., 151., 31.));
-    commands.spawn(WallBundle::new(151., 31., 151., 61.));
-    commands.spawn(WallBundle::new(121., 91., 151., 91.));
-    commands.spawn(WallBundle::new(151., 91., 151., 121.));
-    commands.spawn(WallBundle::new(121., 151., 151., 151.));
-    commands.spawn(WallBundle::new(151., 151., 151., 181.));
-    commands.spawn(WallBundle::new(121., 211., 151., 211.));
-    commands.spawn(WallBundle::new(151., 241., 151., 271.));
-    commands.spawn(WallBundle::new(151., 271., 151., 301.));
-    commands.spawn(WallBundle::new(121., 301., 151., 301.));
-    commands.spawn(WallBundle::new(121., 331., 151., 331.));
-    commands.spawn(WallBundle::new(121., 361., 151., 361.));
-    commands.spawn(WallBundle::new(121., 391., 151., 391.));
-    commands.spawn(WallBundle::new(151., 421., 151., 451.));
-    commands.spawn(WallBundle::new(151., 451., 151., 481.));
-    commands.spawn(WallBundle::new(121., 481., 151., 481.));
-    commands.spawn(WallBundle::new(121., 511., 151., 511.));
-    commands.spawn(WallBundle::new(151., 511., 151., 541.));
-    commands.spawn(WallBundle::new(121., 601., 151., 601.));
-    commands.spawn(WallBundle::new(151., 1., 181., 1.));
-    commands.spawn(WallBundle::new(181., 1., 181., 31.));
-    commands.spawn(WallBundle::new(151., 61., 181., 61.));
-    commands.spawn(WallBundle::new(181., 61., 181., 91.));
-    commands.spawn(WallBundle::new(151., 121., 181., 121.));
-    commands.spawn(WallBundle::new(181., 121., 181., 151.));
-    commands.spawn(WallBundle::new(151., 181., 181., 181.));
-    commands.spawn(WallBundle::new(151., 211., 181., 211.));
-    commands.spawn(WallBundle::new(181., 211., 181., 241.));
-    commands.spawn(WallBundle::new(181., 241., 181., 271.));
-    commands.spawn(WallBundle::new(151., 271., 181., 271.));
-    commands.spawn(WallBundle::new(151., 331., 181., 331.));
-    commands.spawn(WallBundle::new(151., 361., 181., 361.));
-    commands.spawn(WallBundle::new(151., 391., 181., 391.));
-    commands.spawn(WallBundle::new(151., 421., 181., 421.));
-    commands.spawn(WallBundle::new(151., 481., 181., 481.));
-    commands.spawn(WallBundle::new(181., 481., 181., 511.));
-    commands.spawn(WallBundle::new(181., 511., 181., 541.));
-    commands.spawn(WallBundle::new(181., 541., 181., 571.));
-    commands.spawn(WallBundle::new(151., 571., 181., 571.));
-    commands.spawn(WallBundle::new(151., 601., 181., 601.));
-    commands.spawn(WallBundle::new(181., 1., 211., 1.));
-    commands.spawn(WallBundle::new(211., 31., 211., 61.));
-    commands.spawn(WallBundle::new(211., 61., 211., 91.));
-    commands.spawn(WallBundle::new(181., 91., 211., 91.));
-    commands.spawn(WallBundle::new(211., 91., 211., 121.));
-    commands.spawn(WallBundle::new(181., 151., 211., 151.));
-    commands.spawn(WallBundle::new(181., 181., 211., 181.));
-    commands.spawn(WallBundle::new(181., 211., 211., 211.));
-    commands.spawn(WallBundle::new(181., 271., 211., 271.));
-    commands.spawn(WallBundle::new(181., 301., 211., 301.));
-    commands.spawn(WallBundle::new(211., 301., 211., 331.));
-    commands.spawn(WallBundle::new(181., 331., 211., 331.));
-    commands.spawn(WallBundle::new(181., 361., 211., 361.));
-    commands.spawn(WallBundle::new(181., 391., 211., 391.));
-    commands.spawn(WallBundle::new(181., 421., 211., 421.));
-    commands.spawn(WallBundle::new(181., 451., 211., 451.));
-    commands.spawn(WallBundle::new(211., 481., 211., 511.));
-    commands.spawn(WallBundle::new(211., 511., 211., 541.));
-    commands.spawn(WallBundle::new(181., 541., 211., 541.));
-    commands.spawn(WallBundle::new(211., 571., 211., 601.));
-    commands.spawn(WallBundle::new(181., 601., 211., 601.));
-    commands.spawn(WallBundle::new(211., 1., 241., 1.));
-    commands.spawn(WallBundle::new(241., 31., 241., 61.));
-    commands.spawn(WallBundle::new(241., 61., 241., 91.));
-    commands.spawn(WallBundle::new(211., 121., 241., 121.));
-    commands.spawn(WallBundle::new(241., 121., 241., 151.));
-    commands.spawn(WallBundle::new(241., 151., 241., 181.));
-    commands.spawn(WallBundle::new(211., 181., 241., 181.));
-    commands.spawn(WallBundle::new(241., 181., 241., 211.));
-    commands.spawn(WallBundle::new(241., 211., 241., 241.));
-    commands.spawn(WallBundle::new(211., 241., 241., 241.));
-    commands.spawn(WallBundle::new(211., 271., 241., 271.));
-    commands.spawn(WallBundle::new(211., 331., 241., 331.));
-    commands.spawn(WallBundle::new(241., 331., 241., 361.));
-    commands.spawn(WallBundle::new(211., 391., 241., 391.));
-    commands.spawn(WallBundle::new(211., 421., 241., 421.));
-    commands.spawn(WallBundle::new(241., 421., 241., 451.));
-    commands.spawn(WallBundle::new(211., 451., 241., 451.));
-    commands.spawn(WallBundle::new(211., 481., 241., 481.));
-    commands.spawn(WallBundle::new(241., 511., 241., 541.));
-    commands.spawn(WallBundle::new(241., 541., 241., 571.));
-    commands.spawn(WallBundle::new(211., 571., 241., 571.));
-    commands.spawn(WallBundle::new(211., 601., 241., 601.));
-    commands.spawn(WallBundle::new(241., 1., 271., 1.));
-    commands.spawn(WallBundle::new(271., 1., 271., 31.));
-    commands.spawn(WallBundle::new(241., 61., 271., 61.));
-    commands.spawn(WallBundle::new(271., 91., 271., 121.));
-    commands.spawn(WallBundle::new(271., 121., 271., 151.));
-    commands.spawn(WallBundle::new(241., 151., 271., 151.));
-    commands.spawn(WallBundle::new(271., 181., 271., 211.));
-    commands.spawn(WallBundle::new(271., 211., 271., 241.));
-    commands.spawn(WallBundle::new(271., 241., 271., 271.));
-    commands.spawn(WallBundle::new(241., 271., 271., 271.));
-    commands.spawn(WallBundle::new(241., 301., 271., 301.));
-    commands.spawn(WallBundle::new(271., 331., 271., 361.));
-    commands.spawn(WallBundle::new(241., 361., 271., 361.));
-    commands.spawn(WallBundle::new(241., 391., 271., 391.));
-    commands.spawn(WallBundle::new(271., 391., 271., 421.));
-    commands.spawn(WallBundle::new(241., 451., 271., 451.));
-    commands.spawn(WallBundle::new(241., 481., 271., 481.));
-    commands.spawn(WallBundle::new(241., 511., 271., 511.));
-    commands.spawn(WallBundle::new(271., 511., 271., 541.));
-    commands.spawn(WallBundle::new(241., 601., 271., 601.));
-    commands.spawn(WallBundle::new(271., 1., 301., 1.));
-    commands.spawn(WallBundle::new(271., 31., 301., 31.));
-    commands.spawn(WallBundle::new(271., 61., 301., 61.));
-    commands.spawn(WallBundle::new(301., 61., 301., 91.));
-    commands.spawn(WallBundle::new(271., 121., 301., 121.));
-    commands.spawn(WallBundle::new(301., 151., 301., 181.));
-    commands.spawn(WallBundle::new(271., 181., 301., 181.));
-    commands.spawn(WallBundle::new(301., 241., 301., 271.));
-    commands.spawn(WallBundle::new(271., 271., 301., 271.));
-    commands.spawn(WallBundle::new(271., 301., 301., 301.));
-    commands.spawn(WallBundle::new(301., 301., 301., 331.));
-    commands.spawn(WallBundle::new(271., 361., 301., 361.));
-    commands.spawn(WallBundle::new(271., 391., 301., 391.));
-    commands.spawn(WallBundle::new(271., 421., 301., 421.));
-    commands.spawn(WallBundle::new(271., 451., 301., 451.));
-    commands.spawn(WallBundle::new(271., 481., 301., 481.));
-    commands.spawn(WallBundle::new(301., 481., 301., 511.));
-    commands.spawn(WallBundle::new(301., 511., 301., 541.));
-    commands.spawn(WallBundle::new(301., 541., 301., 571.));
-    commands.spawn(WallBundle::new(271., 571., 301., 571.));
-    commands.spawn(WallBundle::new(271., 601., 301., 601.));
-    commands.spawn(WallBundle::new(301., 1., 331., 1.));
-    commands.spawn(WallBundle::new(331., 31., 331., 61.));
-    commands.spawn(WallBundle::new(301., 61., 331., 61.));
-    commands.spawn(WallBundle::new(301., 91., 331., 91.));
-    commands.spawn(WallBundle::new(301., 121., 331., 121.));
-    commands.spawn(WallBundle::new(301., 151., 331., 151.));
-    commands.spawn(WallBundle::new(301., 181., 331., 181.));
-    commands.spawn(WallBundle::new(301., 211., 331., 211.));
-    commands.spawn(WallBundle::new(331., 211., 331., 241.));
-    commands.spawn(WallBundle::new(301., 271., 331., 271.));
-    commands.spawn(WallBundle::new(301., 301., 331., 301.));
-    commands.spawn(WallBundle::new(331., 331., 331., 361.));
-    commands.spawn(WallBundle::new(301., 361., 331., 361.));
-    commands.spawn(WallBundle::new(331., 361., 331., 391.));
-    commands.spawn(WallBundle::new(301., 421., 331., 421.));
-    commands.spawn(WallBundle::new(301., 451., 331., 451.));
-    commands.spawn(WallBundle::new(301., 511., 331., 511.));
-    commands.spawn(WallBundle::new(331., 541., 331., 571.));
-    commands.spawn(WallBundle::new(331., 571., 331., 601.));
-    commands.spawn(WallBundle::new(301., 601., 331., 601.));
-    commands.spawn(WallBundle::new(331., 1., 361., 1.));
-    commands.spawn(WallBundle::new(361., 1., 361., 31.));
-    commands.spawn(WallBundle::new(331., 61., 361., 61.));
-    commands.spawn(WallBundle::new(331., 91., 361., 91.));
-    commands.spawn(WallBundle::new(361., 91., 361., 121.));
-    commands.spawn(WallBundle::new(361., 121., 361., 151.));
-    commands.spawn(WallBundle::new(331., 151., 361., 151.));
-    commands.spawn(WallBundle::new(361., 151., 361., 181.));
-    commands.spawn(WallBundle::new(361., 181., 361., 211.));
-    commands.spawn(WallBundle::new(331., 241., 361., 241.));
-    commands.spawn(WallBundle::new(361., 241., 361., 271.));
-    commands.spawn(WallBundle::new(361., 271., 361., 301.));
-    commands.spawn(WallBundle::new(331., 301., 361., 301.));
-    commands.spawn(WallBundle::new(361., 301., 361., 331.));
-    commands.spawn(WallBundle::new(331., 361., 361., 361.));
-    commands.spawn(WallBundle::new(361., 391., 361., 421.));
-    commands.spawn(WallBundle::new(331., 421., 361., 421.));
-    commands.spawn(WallBundle::new(331., 451., 361., 451.));
-    commands.spawn(WallBundle::new(331., 481., 361., 481.));
-    commands.spawn(WallBundle::new(361., 481., 361., 511.));
-    commands.spawn(WallBundle::new(361., 511., 361., 541.));
-    commands.spawn(WallBundle::new(331., 541., 361., 541.));
-    commands.spawn(WallBundle::new(331., 601., 361., 601.));
-    commands.spawn(WallBundle::new(361., 1., 391., 1.));
-    commands.spawn(WallBundle::new(361., 31., 391., 31.));
-    commands.spawn(WallBundle::new(391., 31., 391., 61.));
-    commands.spawn(WallBundle::new(391., 61., 391., 91.));
-    commands.spawn(WallBundle::new(391., 91., 391., 121.));
-    commands.spawn(WallBundle::new(391., 121., 391., 151.));
-    commands.spawn(WallBundle::new(361., 181., 391., 181.));
-    commands.spawn(WallBundle::new(361., 211., 391., 211.));
-    commands.spawn(WallBundle::new(361., 241., 391., 241.));
-    commands.spawn(WallBundle::new(361., 271., 391., 271.));
-    commands.spawn(WallBundle::new(361., 331., 391., 331.));
-    commands.spawn(WallBundle::new(391., 331., 391., 361.));
-    commands.spawn(WallBundle::new(391., 361., 391., 391.));
-    commands.spawn(WallBundle::new(361., 421., 391., 421.));
-    commands.spawn(WallBundle::new(361., 451., 391., 451.));
-    commands.spawn(WallBundle::new(391., 451., 391., 481.));
-    commands.spawn(WallBundle::new(361., 511., 391., 511.));
-    commands.spawn(WallBundle::new(391., 541., 391., 571.));
-    commands.spawn(WallBundle::new(361., 571., 391., 571.));
-    commands.spawn(WallBundle::new(361., 601., 391., 601.));
-    commands.spawn(WallBundle::new(391., 1., 421., 1.));
-    commands.spawn(WallBundle::new(421., 61., 421., 91.));
-    commands.spawn(WallBundle::new(421., 91., 421., 121.));
-    commands.spawn(WallBundle::new(421., 121., 421., 151.));
-    commands.spawn(WallBundle::new(391., 151., 421., 151.));
-    commands.spawn(WallBundle::new(391., 181., 421., 181.));
-    commands.spawn(WallBundle::new(421., 211., 421., 241.));
-    commands.spawn(WallBundle::new(421., 241., 421., 271.));
-    commands.spawn(WallBundle::new(391., 271., 421., 271.));
-    commands.spawn(WallBundle::new(391., 301., 421., 301.));
-    commands.spawn(WallBundle::new(391., 331., 421., 331.));
-    commands.spawn(WallBundle::new(391., 391., 421., 391.));
-    commands.spawn(WallBundle::new(421., 391., 421., 421.));
-    commands.spawn(WallBundle::new(391., 421., 421., 421.));
-    commands.spawn(WallBundle::new(421., 421., 421., 451.));
-    commands.spawn(WallBundle::new(391., 481., 421., 481.));
-    commands.spawn(WallBundle::new(421., 481., 421., 511.));
-    commands.spawn(WallBundle::new(421., 511., 421., 541.));
-    commands.spawn(WallBundle::new(391., 541., 421., 541.));
-    commands.spawn(WallBundle::new(421., 541., 421., 571.));
-    commands.spawn(WallBundle::new(391., 601., 421., 601.));
-    commands.spawn(WallBundle::new(421., 1., 451., 1.));
-    commands.spawn(WallBundle::new(421., 31., 451., 31.));
-    commands.spawn(WallBundle::new(451., 31., 451., 61.));
-    commands.spawn(WallBundle::new(451., 61., 451., 91.));
-    commands.spawn(WallBundle::new(451., 91., 451., 121.));
-    commands.spawn(WallBundle::new(451., 121., 451., 151.));
-    commands.spawn(WallBundle::new(451., 151., 451., 181.));
-    commands.spawn(WallBundle::new(421., 181., 451., 181.));
-    commands.spawn(WallBundle::new(451., 181., 451., 211.));
-    commands.spawn(WallBundle::new(421., 211., 451., 211.));
-    commands.spawn(WallBundle::new(451., 241., 451., 271.));
-    commands.spawn(WallBundle::new(451., 271., 451., 301.));
-    commands.spawn(WallBundle::new(421., 301., 451., 301.));
-    commands.spawn(WallBundle::new(451., 301., 451., 331.));
-    commands.spawn(WallBundle::new(451., 331., 451., 361.));
-    commands.spawn(WallBundle::new(421., 361., 451., 361.));
-    commands.spawn(WallBundle::new(421., 391., 451., 391.));
-    commands.spawn(WallBundle::new(421., 451., 451., 451.));
-    commands.spawn(WallBundle::new(451., 451., 451., 481.));
-    commands.spawn(WallBundle::new(451., 481., 451., 511.));
-    commands.spawn(WallBundle::new(421., 571., 451., 571.));
-    commands.spawn(WallBundle::new(421., 601., 451., 601.));
-    commands.spawn(WallBundle::new(451., 1., 481., 1.));
-    commands.spawn(WallBundle::new(451., 31., 481., 31.));
-    commands.spawn(WallBundle::new(451., 91., 481., 91.));
-    commands.spawn(WallBundle::new(481., 91., 481., 121.));
-    commands.spawn(WallBundle::new(481., 151., 481., 181.));
-    commands.spawn(WallBundle::new(451., 211., 481., 211.));
-    commands.spawn(WallBundle::new(451., 241., 481., 241.));
-    commands.spawn(WallBundle::new(451., 271., 481., 271.));
-    commands.spawn(WallBundle::new(451., 361., 481., 361.));
-    commands.spawn(WallBundle::new(481., 361., 481., 391.));
-    commands.spawn(WallBundle::new(481., 391., 481., 421.));
-    commands.spawn(WallBundle::new(451., 421., 481., 421.));
-    commands.spawn(WallBundle::new(481., 421., 481., 451.));
-    commands.spawn(WallBundle::new(481., 451., 481., 481.));
-    commands.spawn(WallBundle::new(451., 511., 481., 511.));
-    commands.spawn(WallBundle::new(451., 541., 481., 541.));
-    commands.spawn(WallBundle::new(451., 571., 481., 571.));
-    commands.spawn(WallBundle::new(481., 571., 481., 601.));
-    commands.spawn(WallBundle::new(451., 601., 481., 601.));
-    commands.spawn(WallBundle::new(481., 1., 511., 1.));
-    commands.spawn(WallBundle::new(511., 1., 511., 31.));
-    commands.spawn(WallBundle::new(511., 31., 511., 61.));
-    commands.spawn(WallBundle::new(481., 61., 511., 61.));
-    commands.spawn(WallBundle::new(481., 91., 511., 91.));
-    commands.spawn(WallBundle::new(511., 121., 511., 151.));
-    commands.spawn(WallBundle::new(481., 151., 511., 151.));
-    commands.spawn(WallBundle::new(511., 151., 511., 181.));
-    commands.spawn(WallBundle::new(511., 181., 511., 211.));
-    commands.spawn(WallBundle::new(511., 211., 511., 241.));
-    commands.spawn(WallBundle::new(481., 241., 511., 241.));
-    commands.spawn(WallBundle::new(511., 271., 511., 301.));
-    commands.spawn(WallBundle::new(481., 301., 511., 301.));
-    commands.spawn(WallBundle::new(481., 331., 511., 331.));
-    commands.spawn(WallBundle::new(481., 361., 511., 361.));
-    commands.spawn(WallBundle::new(511., 391., 511., 421.));
-    commands.spawn(WallBundle::new(511., 421., 511., 451.));
-    commands.spawn(WallBundle::new(481., 481., 511., 481.));
-    commands.spawn(WallBundle::new(481., 511., 511., 511.));
-    commands.spawn(WallBundle::new(511., 511., 511., 541.));
-    commands.spawn(WallBundle::new(481., 541., 511., 541.));
-    commands.spawn(WallBundle::new(511., 541., 511., 571.));
-    commands.spawn(WallBundle::new(481., 601., 511., 601.));
-    commands.spawn(WallBundle::new(511., 1., 541., 1.));
-    commands.spawn(WallBundle::new(541., 31., 541., 61.));
-    commands.spawn(WallBundle::new(511., 61., 541., 61.));
-    commands.spawn(WallBundle::new(511., 91., 541., 91.));
-    commands.spawn(WallBundle::new(541., 91., 541., 121.));
-    commands.spawn(WallBundle::new(541., 121., 541., 151.));
-    commands.spawn(WallBundle::new(511., 181., 541., 181.));
-    commands.spawn(WallBundle::new(541., 211., 541., 241.));
-    commands.spawn(WallBundle::new(511., 241., 541., 241.));
-    commands.spawn(WallBundle::new(541., 241., 541., 271.));
-    commands.spawn(WallBundle::new(511., 301., 541., 301.));
-    commands.spawn(WallBundle::new(511., 331., 541., 331.));
-    commands.spawn(WallBundle::new(541., 331., 541., 361.));
-    commands.spawn(WallBundle::new(541., 361., 541., 391.));
-    commands.spawn(WallBundle::new(511., 391., 541., 391.));
-    commands.spawn(WallBundle::new(541., 391., 541., 421.));
-    commands.spawn(WallBundle::new(541., 421., 541., 451.));
-    commands.spawn(WallBundle::new(511., 481., 541., 481.));
-    commands.spawn(WallBundle::new(541., 481., 541., 511.));
-    commands.spawn(WallBundle::new(511., 541., 541., 541.));
-    commands.spawn(WallBundle::new(511., 601., 541., 601.));
-    commands.spawn(WallBundle::new(541., 1., 571., 1.));
-    commands.spawn(WallBundle::new(541., 61., 571., 61.));
-    commands.spawn(WallBundle::new(571., 61., 571., 91.));
-    commands.spawn(WallBundle::new(571., 91., 571., 121.));
-    commands.spawn(WallBundle::new(541., 151., 571., 151.));
-    commands.spawn(WallBundle::new(541., 181., 571., 181.));
-    commands.spawn(WallBundle::new(541., 241., 571., 241.));
-    commands.spawn(WallBundle::new(571., 271., 571., 301.));
-    commands.spawn(WallBundle::new(541., 301., 571., 301.));
-    commands.spawn(WallBundle::new(571., 301., 571., 331.));
-    commands.spawn(WallBundle::new(541., 361., 571., 361.));
-    commands.spawn(WallBundle::new(571., 391., 571., 421.));
-    commands.spawn(WallBundle::new(541., 451., 571., 451.));
-    commands.spawn(WallBundle::new(571., 451., 571., 481.));
-    commands.spawn(WallBundle::new(571., 481., 571., 511.));
-    commands.spawn(WallBundle::new(541., 511., 571., 511.));
-    commands.spawn(WallBundle::new(541., 541., 571., 541.));
-    commands.spawn(WallBundle::new(541., 571., 571., 571.));
-    commands.spawn(WallBundle::new(541., 601., 571., 601.));
-    commands.spawn(WallBundle::new(571., 1., 601., 1.));
-    commands.spawn(WallBundle::new(601., 1., 601., 31.));
-    commands.spawn(WallBundle::new(571., 31., 601., 31.));
-    commands.spawn(WallBundle::new(601., 31., 601., 61.));
-    commands.spawn(WallBundle::new(601., 61., 601., 91.));
-    commands.spawn(WallBundle::new(601., 91., 601., 121.));
-    commands.spawn(WallBundle::new(601., 121., 601., 151.));
-    commands.spawn(WallBundle::new(601., 151., 601., 181.));
-    commands.spawn(WallBundle::new(571., 181., 601., 181.));
-    commands.spawn(WallBundle::new(601., 181., 601., 211.));
-    commands.spawn(WallBundle::new(571., 211., 601., 211.));
-    commands.spawn(WallBundle::new(601., 211., 601., 241.));
-    commands.spawn(WallBundle::new(601., 241., 601., 271.));
-    commands.spawn(WallBundle::new(601., 271., 601., 301.));
-    commands.spawn(WallBundle::new(601., 301., 601., 331.));
-    commands.spawn(WallBundle::new(571., 331., 601., 331.));
-    commands.spawn(WallBundle::new(601., 331., 601., 361.));
-    commands.spawn(WallBundle::new(601., 361., 601., 391.));
-    commands.spawn(WallBundle::new(601., 391., 601., 421.));
-    commands.spawn(WallBundle::new(571., 421., 601., 421.));
-    commands.spawn(WallBundle::new(601., 421., 601., 451.));
-    commands.spawn(WallBundle::new(601., 451., 601., 481.));
-    commands.spawn(WallBundle::new(601., 481., 601., 511.));
-    commands.spawn(WallBundle::new(601., 511., 601., 541.));
-    commands.spawn(WallBundle::new(601., 541., 601., 571.));
-    commands.spawn(WallBundle::new(571., 571., 601., 571.));
-    commands.spawn(WallBundle::new(601., 571., 601., 601.));
+pub const MAZE_CELL_SIZE: f32 = 30.;
+pub const MAZE_ORIGIN: Vec2 = Vec2::new(1., 1.);
+
+/*
+ * Cell-edge representation of a maze.
+ * Horizontal walls run along the bottom edge of cell (x, y), so row y == height is the top border.
+ * Vertical walls run along the left edge of cell (x, y), so column x == width is the right border.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct MazeGrid {
+    pub width: usize,
+    pub height: usize,
+    horizontal_walls: Vec<bool>,
+    vertical_walls: Vec<bool>,
+}
+
+impl MazeGrid {
+    pub fn new(width: usize, height: usize) -> MazeGrid {
+        MazeGrid {
+            width,
+            height,
+            horizontal_walls: vec![false; width * (height + 1)],
+            vertical_walls: vec![false; (width + 1) * height],
+        }
+    }
+
+    pub fn from_segments(width: usize, height: usize, segments: &[[f32; 4]]) -> MazeGrid {
+        let mut grid = MazeGrid::new(width, height);
+
+        for [start_x, start_y, end_x, end_y] in segments {
+            let start = grid_point(Vec2::new(start_x.min(*end_x), start_y.min(*end_y)));
+            let end = grid_point(Vec2::new(start_x.max(*end_x), start_y.max(*end_y)));
+
+            if start.y == end.y {
+                for x in start.x..end.x {
+                    grid.set_horizontal_wall(x as usize, start.y as usize, true);
+                }
+            } else {
+                for y in start.y..end.y {
+                    grid.set_vertical_wall(start.x as usize, y as usize, true);
+                }
+            }
+        }
+
+        grid
+    }
+
+    // Every cell walled in on all sides, the starting point for maze generation
+    pub fn closed(width: usize, height: usize) -> MazeGrid {
+        MazeGrid {
+            width,
+            height,
+            horizontal_walls: vec![true; width * (height + 1)],
+            vertical_walls: vec![true; (width + 1) * height],
+        }
+    }
+
+    // Wall segments as [start_x, start_y, end_x, end_y] in world coordinates, one per cell edge
+    pub fn segments(&self) -> Vec<[f32; 4]> {
+        let mut segments = Vec::new();
+
+        for y in 0..=self.height {
+            for x in 0..self.width {
+                if self.has_horizontal_wall(x, y) {
+                    let start = world_point(x, y);
+                    segments.push([start.x, start.y, start.x + MAZE_CELL_SIZE, start.y]);
+                }
+            }
+        }
+
+        for y in 0..self.height {
+            for x in 0..=self.width {
+                if self.has_vertical_wall(x, y) {
+                    let start = world_point(x, y);
+                    segments.push([start.x, start.y, start.x, start.y + MAZE_CELL_SIZE]);
+                }
+            }
+        }
+
+        segments
+    }
+
+    pub fn has_horizontal_wall(&self, x: usize, y: usize) -> bool {
+        x < self.width && y <= self.height && self.horizontal_walls[y * self.width + x]
+    }
+
+    pub fn has_vertical_wall(&self, x: usize, y: usize) -> bool {
+        x <= self.width && y < self.height && self.vertical_walls[y * (self.width + 1) + x]
+    }
+
+    pub fn set_horizontal_wall(&mut self, x: usize, y: usize, wall: bool) {
+        if x < self.width && y <= self.height {
+            self.horizontal_walls[y * self.width + x] = wall;
+        }
+    }
+
+    pub fn set_vertical_wall(&mut self, x: usize, y: usize, wall: bool) {
+        if x <= self.width && y < self.height {
+            self.vertical_walls[y * (self.width + 1) + x] = wall;
+        }
+    }
+
+    pub fn cell_center(&self, cell: UVec2) -> Vec2 {
+        MAZE_ORIGIN + (cell.as_vec2() + 0.5) * MAZE_CELL_SIZE
+    }
+
+    pub fn cell_at(&self, position: Vec2) -> Option<UVec2> {
+        let cell = ((position - MAZE_ORIGIN) / MAZE_CELL_SIZE).floor();
+
+        if cell.x < 0. || cell.y < 0. || cell.x >= self.width as f32 || cell.y >= self.height as f32 {
+            return None;
+        }
+
+        Some(cell.as_uvec2())
+    }
+
+    // Walls along every outer edge, leaving the inside open
+    pub fn close_border(&mut self) {
+        for x in 0..self.width {
+            self.set_horizontal_wall(x, 0, true);
+            self.set_horizontal_wall(x, self.height, true);
+        }
+        for y in 0..self.height {
+            self.set_vertical_wall(0, y, true);
+            self.set_vertical_wall(self.width, y, true);
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum MazeTopology {
+    // Square cells on an axis aligned grid, width by height cells
+    #[default]
+    Rectangular,
+    // Pointy topped hexagons in width columns and height rows, odd rows shifted right
+    Hexagonal,
+    // Rings of cells around a center cell, width rings with more cells towards the outside
+    Polar,
+}
+
+impl MazeTopology {
+    pub const ALL: [MazeTopology; 3] = [MazeTopology::Rectangular, MazeTopology::Hexagonal, MazeTopology::Polar];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MazeTopology::Rectangular => "Square",
+            MazeTopology::Hexagonal => "Hex",
+            MazeTopology::Polar => "Circular",
+        }
+    }
+}
+
+// Border between two cells, or between a cell and the outside of the maze
+#[derive(Clone, Debug, PartialEq)]
+pub struct MazeEdge {
+    pub cell: usize,
+    pub neighbor: Option<usize>,
+    pub segment: [f32; 4],
+    pub wall: bool,
+}
+
+/*
+ * Maze of any topology as a graph of numbered cells and the edges between them.
+ * Edges carry their wall segment in world coordinates, which may run at any angle.
+ * Rectangular cells are numbered row by row like MazeGrid, polar cells ring by ring from the center.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct CellMaze {
+    pub topology: MazeTopology,
+    pub width: usize,
+    pub height: usize,
+    centers: Vec<Vec2>,
+    edges: Vec<MazeEdge>,
+    cell_edges: Vec<Vec<usize>>,
+    // Cells per ring for polar mazes, starting with the single center cell
+    ring_sectors: Vec<usize>,
+}
+
+impl CellMaze {
+    // Same as MazeGrid::closed, for any topology
+    pub fn closed(topology: MazeTopology, width: usize, height: usize) -> CellMaze {
+        match topology {
+            MazeTopology::Rectangular => CellMaze::from_grid(&MazeGrid::closed(width, height)),
+            MazeTopology::Hexagonal => CellMaze::hexagonal(width, height),
+            MazeTopology::Polar => CellMaze::polar(width),
+        }
+    }
+
+    pub fn from_grid(grid: &MazeGrid) -> CellMaze {
+        let index = |x: usize, y: usize| y * grid.width + x;
+        let mut maze = CellMaze::empty(MazeTopology::Rectangular, grid.width, grid.height);
+
+        for y in 0..grid.height {
+            for x in 0..grid.width {
+                maze.centers.push(grid.cell_center(UVec2::new(x as u32, y as u32)));
+            }
+        }
+        maze.cell_edges = vec![Vec::new(); maze.centers.len()];
+
+        // Same order as MazeGrid::segments, so converting back and forth keeps the wall list stable
+        for y in 0..=grid.height {
+            for x in 0..grid.width {
+                let start = world_point(x, y);
+                let cells = [(y > 0).then(|| index(x, y - 1)), (y < grid.height).then(|| index(x, y))];
+                maze.push_edge(cells, start, start + Vec2::new(MAZE_CELL_SIZE, 0.), grid.has_horizontal_wall(x, y));
+            }
+        }
+
+        for y in 0..grid.height {
+            for x in 0..=grid.width {
+                let start = world_point(x, y);
+                let cells = [(x > 0).then(|| index(x - 1, y)), (x < grid.width).then(|| index(x, y))];
+                maze.push_edge(cells, start, start + Vec2::new(0., MAZE_CELL_SIZE), grid.has_vertical_wall(x, y));
+            }
+        }
+
+        maze
+    }
+
+    // Rebuilds the walls of a level, an edge is closed when a segment covers it exactly
+    pub fn from_segments(topology: MazeTopology, width: usize, height: usize, segments: &[[f32; 4]]) -> CellMaze {
+        if topology == MazeTopology::Rectangular {
+            return CellMaze::from_grid(&MazeGrid::from_segments(width, height, segments));
+        }
+
+        let walls: HashSet<[i32; 4]> = segments.iter().map(segment_key).collect();
+        let mut maze = CellMaze::closed(topology, width, height);
+
+        for edge in &mut maze.edges {
+            edge.wall = walls.contains(&segment_key(&edge.segment));
+        }
+
+        maze
+    }
+
+    fn empty(topology: MazeTopology, width: usize, height: usize) -> CellMaze {
+        CellMaze {
+            topology,
+            width,
+            height,
+            centers: Vec::new(),
+            edges: Vec::new(),
+            cell_edges: Vec::new(),
+            ring_sectors: Vec::new(),
+        }
+    }
+
+    fn push_edge(&mut self, cells: [Option<usize>; 2], start: Vec2, end: Vec2, wall: bool) {
+        let (cell, neighbor) = match cells {
+            [Some(cell), neighbor] | [neighbor, Some(cell)] => (cell, neighbor),
+            [None, None] => return,
+        };
+
+        let index = self.edges.len();
+        self.cell_edges[cell].push(index);
+        if let Some(neighbor) = neighbor {
+            self.cell_edges[neighbor].push(index);
+        }
+
+        self.edges.push(MazeEdge {
+            cell,
+            neighbor,
+            segment: [start.x, start.y, end.x, end.y],
+            wall,
+        });
+    }
+
+    /*
+     * Hexagons one cell size across their flat sides, so corridors are as wide as in square mazes.
+     * Side k runs from the corner at 30 + 60k degrees to the next one.
+     */
+    fn hexagonal(columns: usize, rows: usize) -> CellMaze {
+        let mut maze = CellMaze::empty(MazeTopology::Hexagonal, columns, rows);
+        let radius = MAZE_CELL_SIZE / 3_f32.sqrt();
+        let index = |column: i32, row: i32| {
+            (column >= 0 && row >= 0 && (column as usize) < columns && (row as usize) < rows)
+                .then(|| row as usize * columns + column as usize)
+        };
+
+        for row in 0..rows {
+            for column in 0..columns {
+                let shift = if row % 2 == 1 { 0.5 } else { 0. };
+                maze.centers.push(
+                    MAZE_ORIGIN
+                        + Vec2::new((column as f32 + 0.5 + shift) * MAZE_CELL_SIZE, radius + row as f32 * 1.5 * radius),
+                );
+            }
+        }
+        maze.cell_edges = vec![Vec::new(); maze.centers.len()];
+
+        for row in 0..rows as i32 {
+            for column in 0..columns as i32 {
+                let cell = row as usize * columns + column as usize;
+                let shift = row % 2;
+                // Neighbors across sides 0 to 5: north east, north west, west, south west, south east, east
+                let neighbors = [
+                    (column + shift, row + 1),
+                    (column + shift - 1, row + 1),
+                    (column - 1, row),
+                    (column + shift - 1, row - 1),
+                    (column + shift, row - 1),
+                    (column + 1, row),
+                ];
+
+                for (side, (neighbor_column, neighbor_row)) in neighbors.into_iter().enumerate() {
+                    let neighbor = index(neighbor_column, neighbor_row);
+                    // Shared sides are added once, from the lower numbered cell
+                    if neighbor.is_some_and(|neighbor| neighbor < cell) { continue };
+
+                    let corner = |corner: usize| {
+                        let angle = (30. + 60. * corner as f32).to_radians();
+                        maze.centers[cell] + Vec2::from_angle(angle) * radius
+                    };
+                    let (start, end) = (corner(side), corner(side + 1));
+                    maze.push_edge([Some(cell), neighbor], start, end, true);
+                }
+            }
+        }
+
+        maze
+    }
+
+    /*
+     * Rings one cell size deep around a round center cell. A ring doubles its number of cells
+     * once they would get more than twice as wide as deep, so cells stay roughly square.
+     * Curved walls are drawn as straight chords between the corners of the cells.
+     */
+    fn polar(rings: usize) -> CellMaze {
+        let rings = rings.max(2);
+        let mut maze = CellMaze::empty(MazeTopology::Polar, rings, rings);
+        let center = MAZE_ORIGIN + Vec2::splat(rings as f32 * MAZE_CELL_SIZE);
+
+        maze.ring_sectors.push(1);
+        for ring in 1..rings {
+            let previous = maze.ring_sectors[ring - 1];
+            let sectors = if ring == 1 {
+                6
+            } else if TAU * ring as f32 / previous as f32 > 2. {
+                previous * 2
+            } else {
+                previous
+            };
+            maze.ring_sectors.push(sectors);
+        }
+
+        let ring_start = |maze: &CellMaze, ring: usize| maze.ring_sectors[..ring].iter().sum::<usize>();
+        let point = |radius: f32, angle: f32| center + Vec2::from_angle(angle) * radius * MAZE_CELL_SIZE;
+
+        maze.centers.push(center);
+        for ring in 1..rings {
+            let sectors = maze.ring_sectors[ring];
+            for sector in 0..sectors {
+                maze.centers.push(point(ring as f32 + 0.5, (sector as f32 + 0.5) * TAU / sectors as f32));
+            }
+        }
+        maze.cell_edges = vec![Vec::new(); maze.centers.len()];
+
+        for ring in 0..rings {
+            let sectors = maze.ring_sectors[ring];
+            let first = ring_start(&maze, ring);
+
+            // Radial walls between cells of the same ring
+            if ring > 0 {
+                for sector in 0..sectors {
+                    let angle = (sector + 1) as f32 * TAU / sectors as f32;
+                    let cells = [Some(first + sector), Some(first + (sector + 1) % sectors)];
+                    maze.push_edge(cells, point(ring as f32, angle), point(ring as f32 + 1., angle), true);
+                }
+            }
+
+            // Outer walls, split along the cells of the next ring or closing the maze
+            let outer_sectors = maze.ring_sectors.get(ring + 1).copied().unwrap_or(sectors);
+            let outer_first = ring_start(&maze, ring + 1);
+            for outer_sector in 0..outer_sectors {
+                let inner = first + outer_sector * sectors / outer_sectors;
+                let outer = (ring + 1 < rings).then_some(outer_first + outer_sector);
+                let angle = |sector: usize| sector as f32 * TAU / outer_sectors as f32;
+                let radius = ring as f32 + 1.;
+                maze.push_edge([Some(inner), outer], point(radius, angle(outer_sector)), point(radius, angle(outer_sector + 1)), true);
+            }
+        }
+
+        maze
+    }
+
+    pub fn cell_count(&self) -> usize {
+        self.centers.len()
+    }
+
+    pub fn edges(&self) -> &[MazeEdge] {
+        &self.edges
+    }
+
+    pub fn cell_center(&self, cell: usize) -> Vec2 {
+        self.centers[cell]
+    }
+
+    pub fn nearest_cell(&self, position: Vec2) -> usize {
+        (0..self.cell_count())
+            .min_by(|a, b| self.centers[*a].distance(position).total_cmp(&self.centers[*b].distance(position)))
+            .unwrap_or_default()
+    }
+
+    pub fn set_wall(&mut self, edge: usize, wall: bool) {
+        if let Some(edge) = self.edges.get_mut(edge) {
+            edge.wall = wall;
+        }
+    }
+
+    pub fn cell_at(&self, position: Vec2) -> Option<usize> {
+        match self.topology {
+            MazeTopology::Rectangular => {
+                let cell = ((position - MAZE_ORIGIN) / MAZE_CELL_SIZE).floor();
+                let inside = cell.x >= 0. && cell.y >= 0. && cell.x < self.width as f32 && cell.y < self.height as f32;
+                inside.then(|| cell.y as usize * self.width + cell.x as usize)
+            }
+            // Hexagons are exactly the area closest to their center
+            MazeTopology::Hexagonal => Some(self.nearest_cell(position))
+                .filter(|cell| self.centers[*cell].distance(position) <= MAZE_CELL_SIZE / 3_f32.sqrt()),
+            MazeTopology::Polar => {
+                let offset = position - self.centers[0];
+                let ring = (offset.length() / MAZE_CELL_SIZE) as usize;
+                if ring == 0 { return Some(0) };
+
+                let sectors = *self.ring_sectors.get(ring)?;
+                let angle = offset.y.atan2(offset.x).rem_euclid(TAU);
+                let sector = ((angle / TAU * sectors as f32) as usize).min(sectors - 1);
+                Some(self.ring_sectors[..ring].iter().sum::<usize>() + sector)
+            }
+        }
+    }
+
+    // Cells sharing an edge with the given one, whether or not a wall is in between
+    pub fn neighbors(&self, cell: usize) -> Vec<usize> {
+        self.cell_edges[cell]
+            .iter()
+            .filter_map(|edge| self.other_cell(&self.edges[*edge], cell))
+            .collect()
+    }
+
+    // Neighboring cells that can be reached without crossing a wall
+    pub fn open_neighbors(&self, cell: usize) -> Vec<usize> {
+        self.cell_edges[cell]
+            .iter()
+            .map(|edge| &self.edges[*edge])
+            .filter(|edge| !edge.wall)
+            .filter_map(|edge| self.other_cell(edge, cell))
+            .collect()
+    }
+
+    fn other_cell(&self, edge: &MazeEdge, cell: usize) -> Option<usize> {
+        if edge.cell == cell { edge.neighbor } else { Some(edge.cell) }
+    }
+
+    // Corners of a cell in counterclockwise order, every cell surrounds its own center
+    pub fn cell_outline(&self, cell: usize) -> Vec<Vec2> {
+        let center = self.centers[cell];
+        let mut corners: Vec<Vec2> = Vec::new();
+
+        for edge in &self.cell_edges[cell] {
+            let [start_x, start_y, end_x, end_y] = self.edges[*edge].segment;
+            for corner in [Vec2::new(start_x, start_y), Vec2::new(end_x, end_y)] {
+                if corners.iter().all(|known| known.distance(corner) > 0.1) {
+                    corners.push(corner);
+                }
+            }
+        }
+
+        corners.sort_by(|a, b| (*a - center).to_angle().total_cmp(&(*b - center).to_angle()));
+        corners
+    }
+
+    // Edge shared by two neighboring cells
+    pub fn edge_between(&self, a: usize, b: usize) -> Option<usize> {
+        self.cell_edges[a]
+            .iter()
+            .copied()
+            .find(|edge| self.other_cell(&self.edges[*edge], a) == Some(b))
+    }
+
+    // Edge lying on the given segment, in either direction
+    pub fn edge_with_segment(&self, segment: &[f32; 4]) -> Option<usize> {
+        let key = segment_key(segment);
+        self.edges.iter().position(|edge| segment_key(&edge.segment) == key)
+    }
+
+    // Opens the wall shared by two neighboring cells
+    pub fn remove_wall_between(&mut self, a: usize, b: usize) {
+        if let Some(edge) = self.edge_between(a, b) {
+            self.edges[edge].wall = false;
+        }
+    }
+
+    // Wall segments as [start_x, start_y, end_x, end_y] in world coordinates, one per closed edge
+    pub fn segments(&self) -> Vec<[f32; 4]> {
+        self.edges.iter().filter(|edge| edge.wall).map(|edge| edge.segment).collect()
+    }
+
+    // Number of steps from start to every cell, None for cells that cannot be reached
+    pub fn distances_from(&self, start: usize) -> Vec<Option<usize>> {
+        let mut distances = vec![None; self.cell_count()];
+        let mut queue = VecDeque::from([start]);
+        distances[start] = Some(0);
+
+        while let Some(cell) = queue.pop_front() {
+            let distance = distances[cell].unwrap_or_default();
+
+            for neighbor in self.open_neighbors(cell) {
+                if distances[neighbor].is_none() {
+                    distances[neighbor] = Some(distance + 1);
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+
+        distances
+    }
+
+    // Cell that takes the most steps to reach from start
+    pub fn farthest_cell(&self, start: usize) -> usize {
+        let distances = self.distances_from(start);
+
+        (0..distances.len()).max_by_key(|index| distances[*index]).unwrap_or(start)
+    }
+
+    // Breadth first search, returns the cells from start to goal inclusive
+    pub fn shortest_path(&self, start: usize, goal: usize) -> Option<Vec<usize>> {
+        let mut previous: Vec<Option<usize>> = vec![None; self.cell_count()];
+        let mut visited = vec![false; self.cell_count()];
+        let mut queue = VecDeque::from([start]);
+        visited[start] = true;
+
+        while let Some(cell) = queue.pop_front() {
+            if cell == goal {
+                let mut path = vec![goal];
+                let mut current = goal;
+                while let Some(parent) = previous[current] {
+                    path.push(parent);
+                    current = parent;
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            for neighbor in self.open_neighbors(cell) {
+                if !visited[neighbor] {
+                    visited[neighbor] = true;
+                    previous[neighbor] = Some(cell);
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+
+        None
+    }
+}
+
+// Direction independent key for matching saved wall segments against maze edges
+fn segment_key([start_x, start_y, end_x, end_y]: &[f32; 4]) -> [i32; 4] {
+    let round = |value: f32| (value * 10.).round() as i32;
+    let (start, end) = ((round(*start_x), round(*start_y)), (round(*end_x), round(*end_y)));
+    let (low, high) = (start.min(end), start.max(end));
+
+    [low.0, low.1, high.0, high.1]
+}
+
+fn grid_point(position: Vec2) -> UVec2 {
+    ((position - MAZE_ORIGIN) / MAZE_CELL_SIZE).round().as_uvec2()
+}
+
+fn world_point(x: usize, y: usize) -> Vec2 {
+    MAZE_ORIGIN + Vec2::new(x as f32, y as f32) * MAZE_CELL_SIZE
 }
 
+// Spawns the walls of one floor, only collided with while the player is on that floor
+pub fn draw_maze(commands: &mut Commands, topology: MazeTopology, walls: &[[f32; 4]], floor: usize) {
+    for [start_x, start_y, end_x, end_y] in walls {
+        commands.spawn(WallBundle::new(topology, *start_x, *start_y, *end_x, *end_y, floor));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open(mut maze: CellMaze) -> CellMaze {
+        for edge in &mut maze.edges {
+            edge.wall = false;
+        }
+        maze
+    }
+
+    fn sorted(mut cells: Vec<usize>) -> Vec<usize> {
+        cells.sort();
+        cells
+    }
+
+    #[test]
+    fn hex_cells_neighbor_every_cell_one_cell_size_away() {
+        let maze = open(CellMaze::hexagonal(5, 4));
+
+        for cell in 0..maze.cell_count() {
+            let touching: Vec<usize> = (0..maze.cell_count())
+                .filter(|other| *other != cell)
+                .filter(|other| (maze.cell_center(cell).distance(maze.cell_center(*other)) - MAZE_CELL_SIZE).abs() < 0.01)
+                .collect();
+
+            assert_eq!(sorted(maze.neighbors(cell)), touching, "cell {cell}");
+            assert!(touching.len() <= 6);
+        }
+        // Second cell of the second row is away from the border and shares all six sides
+        assert_eq!(maze.neighbors(6).len(), 6);
+    }
+
+    #[test]
+    fn hex_distances_follow_hex_steps() {
+        let (columns, rows) = (5, 4);
+        let maze = open(CellMaze::hexagonal(columns, rows));
+        // Odd rows are shifted right, converted to axial coordinates to count steps
+        let axial = |cell: usize| {
+            let (column, row) = ((cell % columns) as i32, (cell / columns) as i32);
+            IVec2::new(column - (row - (row & 1)) / 2, row)
+        };
+
+        for start in [0, 7, maze.cell_count() - 1] {
+            for (cell, distance) in maze.distances_from(start).into_iter().enumerate() {
+                let offset = axial(cell) - axial(start);
+                let steps = offset.x.abs().max(offset.y.abs()).max((offset.x + offset.y).abs()) as usize;
+                assert_eq!(distance, Some(steps), "from {start} to {cell}");
+            }
+        }
+    }
+
+    #[test]
+    fn polar_cells_neighbor_their_ring_and_the_rings_around() {
+        let rings = 5;
+        let maze = open(CellMaze::polar(rings));
+        let ring_of = |cell: usize| (0..rings).find(|ring| cell < maze.ring_sectors[..=*ring].iter().sum()).unwrap();
+
+        assert_eq!(maze.neighbors(0).len(), maze.ring_sectors[1]);
+        assert!(maze.neighbors(0).iter().all(|neighbor| ring_of(*neighbor) == 1));
+
+        for cell in 1..maze.cell_count() {
+            let ring = ring_of(cell);
+            let neighbors = maze.neighbors(cell);
+            let in_ring = |offset: usize| neighbors.iter().filter(|neighbor| ring_of(**neighbor) + 1 == ring + offset).count();
+            let outer_sectors = maze.ring_sectors.get(ring + 1).map_or(0, |outer| outer / maze.ring_sectors[ring]);
+
+            assert_eq!(in_ring(0), 1, "cell {cell} inwards");
+            assert_eq!(in_ring(1), 2, "cell {cell} around");
+            assert_eq!(in_ring(2), outer_sectors, "cell {cell} outwards");
+            assert!(neighbors.iter().all(|neighbor| maze.neighbors(*neighbor).contains(&cell)));
+        }
+    }
+
+    #[test]
+    fn polar_distance_from_center_is_the_ring() {
+        let rings = 5;
+        let maze = open(CellMaze::polar(rings));
+        let ring_of = |cell: usize| (0..rings).find(|ring| cell < maze.ring_sectors[..=*ring].iter().sum()).unwrap();
+
+        for (cell, distance) in maze.distances_from(0).into_iter().enumerate() {
+            let ring = ring_of(cell);
+            assert_eq!(distance, Some(ring), "cell {cell}");
+
+            let radius = maze.cell_center(cell).distance(maze.cell_center(0)) / MAZE_CELL_SIZE;
+            assert!(ring == 0 || (radius - (ring as f32 + 0.5)).abs() < 0.01, "cell {cell}");
+        }
+    }
+}
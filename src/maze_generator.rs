@@ -0,0 +1,430 @@
+use bevy::prelude::*;
+use rand::{
+    rngs::StdRng,
+    seq::SliceRandom,
+    Rng,
+    SeedableRng,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::doors::KeyColor;
+use crate::level::{Level, LevelDoor, LevelFloor, LevelGate, LevelKey, LevelSwitch, Stairs};
+use crate::maze::{CellMaze, MazeTopology};
+
+pub const MAZE_SIZES: [usize; 5] = [10, 15, 20, 25, 30];
+pub const BRAID_FACTORS: [f32; 5] = [0., 0.25, 0.5, 0.75, 1.];
+pub const ENEMY_DENSITIES: [f32; 5] = [0., 0.01, 0.02, 0.04, 0.08];
+pub const COIN_DENSITIES: [f32; 5] = [0., 0.02, 0.05, 0.1, 0.2];
+pub const FLOOR_COUNTS: [usize; 3] = [1, 2, 3];
+pub const LOCK_COUNTS: [usize; 4] = [0, 1, 2, 3];
+
+// Enemies never spawn this close to the player start, counted in steps through the maze
+const ENEMY_SAFE_DISTANCE: usize = 4;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MazeAlgorithm {
+    // Long winding corridors with few branches
+    RecursiveBacktracker,
+    // Many short dead ends branching off everywhere
+    Prim,
+    // Evenly mixed corridors, no bias towards any direction
+    Kruskal,
+}
+
+impl MazeAlgorithm {
+    pub const ALL: [MazeAlgorithm; 3] = [
+        MazeAlgorithm::RecursiveBacktracker,
+        MazeAlgorithm::Prim,
+        MazeAlgorithm::Kruskal,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MazeAlgorithm::RecursiveBacktracker => "Backtracker",
+            MazeAlgorithm::Prim => "Prim",
+            MazeAlgorithm::Kruskal => "Kruskal",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct MazeSettings {
+    pub topology: MazeTopology,
+    // Polar mazes use the width as their number of rings
+    pub width: usize,
+    pub height: usize,
+    pub algorithm: MazeAlgorithm,
+    // Share of dead ends that get opened up into loops
+    pub braid: f32,
+    // Enemies and coins per maze cell
+    pub enemy_density: f32,
+    pub coin_density: f32,
+    // Floors stacked on top of each other, joined by stairs
+    pub floors: usize,
+    // Locked doors and switch gates on the way through each floor
+    pub locks: usize,
+    pub seed: u64,
+}
+
+impl Default for MazeSettings {
+    fn default() -> Self {
+        MazeSettings {
+            topology: MazeTopology::Rectangular,
+            width: 15,
+            height: 15,
+            algorithm: MazeAlgorithm::RecursiveBacktracker,
+            braid: 0.,
+            enemy_density: 0.02,
+            coin_density: 0.05,
+            floors: 1,
+            locks: 0,
+            seed: 1,
+        }
+    }
+}
+
+impl MazeSettings {
+    // Equal settings always generate the same maze, so they double as the level id for best runs
+    pub fn level_id(&self) -> String {
+        format!(
+            "generated_{:?}_{:?}_{}x{}x{}_{}_{}_{}_{}_{}",
+            self.topology,
+            self.algorithm,
+            self.width,
+            self.height,
+            self.floors,
+            self.locks,
+            self.braid,
+            self.enemy_density,
+            self.coin_density,
+            self.seed,
+        )
+    }
+}
+
+pub fn generate_maze(settings: &MazeSettings, rng: &mut StdRng) -> CellMaze {
+    let mut maze = CellMaze::closed(settings.topology, settings.width, settings.height);
+
+    match settings.algorithm {
+        MazeAlgorithm::RecursiveBacktracker => carve_backtracker(&mut maze, rng),
+        MazeAlgorithm::Prim => carve_prim(&mut maze, rng),
+        MazeAlgorithm::Kruskal => carve_kruskal(&mut maze, rng),
+    }
+
+    braid(&mut maze, settings.braid, rng);
+
+    maze
+}
+
+fn carve_backtracker(maze: &mut CellMaze, rng: &mut StdRng) {
+    let mut visited = vec![false; maze.cell_count()];
+    let mut stack = vec![0];
+    visited[0] = true;
+
+    while let Some(&cell) = stack.last() {
+        let unvisited: Vec<usize> = maze
+            .neighbors(cell)
+            .into_iter()
+            .filter(|neighbor| !visited[*neighbor])
+            .collect();
+
+        let Some(&next) = unvisited.choose(rng) else {
+            stack.pop();
+            continue;
+        };
+
+        maze.remove_wall_between(cell, next);
+        visited[next] = true;
+        stack.push(next);
+    }
+}
+
+fn carve_prim(maze: &mut CellMaze, rng: &mut StdRng) {
+    let mut in_maze = vec![false; maze.cell_count()];
+    let mut frontier = vec![0];
+
+    while !frontier.is_empty() {
+        let cell = frontier.swap_remove(rng.gen_range(0..frontier.len()));
+        if in_maze[cell] { continue };
+
+        // Join the new cell to a random cell already in the maze, except for the very first one
+        let connected: Vec<usize> = maze
+            .neighbors(cell)
+            .into_iter()
+            .filter(|neighbor| in_maze[*neighbor])
+            .collect();
+
+        if let Some(&neighbor) = connected.choose(rng) {
+            maze.remove_wall_between(cell, neighbor);
+        }
+
+        in_maze[cell] = true;
+
+        for neighbor in maze.neighbors(cell) {
+            if !in_maze[neighbor] {
+                frontier.push(neighbor);
+            }
+        }
+    }
+}
+
+fn carve_kruskal(maze: &mut CellMaze, rng: &mut StdRng) {
+    let mut edges: Vec<(usize, usize)> = maze
+        .edges()
+        .iter()
+        .filter_map(|edge| edge.neighbor.map(|neighbor| (edge.cell, neighbor)))
+        .collect();
+    edges.shuffle(rng);
+
+    // Union-find over cells, a wall is only opened when it joins two separate regions
+    let mut parents: Vec<usize> = (0..maze.cell_count()).collect();
+    fn root(parents: &mut [usize], mut index: usize) -> usize {
+        while parents[index] != index {
+            parents[index] = parents[parents[index]];
+            index = parents[index];
+        }
+        index
+    }
+
+    for (a, b) in edges {
+        let root_a = root(&mut parents, a);
+        let root_b = root(&mut parents, b);
+
+        if root_a != root_b {
+            parents[root_a] = root_b;
+            maze.remove_wall_between(a, b);
+        }
+    }
+}
+
+// Opens one extra wall in a share of the dead ends, turning them into loops
+fn braid(maze: &mut CellMaze, factor: f32, rng: &mut StdRng) {
+    if factor <= 0. { return };
+
+    let mut dead_ends: Vec<usize> = (0..maze.cell_count())
+        .filter(|cell| maze.open_neighbors(*cell).len() == 1)
+        .collect();
+    dead_ends.shuffle(rng);
+
+    for cell in dead_ends {
+        // An earlier opening may already have fixed this dead end
+        let open = maze.open_neighbors(cell);
+        if open.len() != 1 || rng.gen::<f32>() >= factor { continue };
+
+        let closed: Vec<usize> = maze
+            .neighbors(cell)
+            .into_iter()
+            .filter(|neighbor| !open.contains(neighbor))
+            .collect();
+
+        // Prefer joining two dead ends, which removes both with a single wall
+        let dead_end_neighbor = closed
+            .iter()
+            .copied()
+            .filter(|neighbor| maze.open_neighbors(*neighbor).len() == 1)
+            .collect::<Vec<usize>>()
+            .choose(rng)
+            .copied();
+
+        if let Some(neighbor) = dead_end_neighbor.or_else(|| closed.choose(rng).copied()) {
+            maze.remove_wall_between(cell, neighbor);
+        }
+    }
+}
+
+/*
+ * Turns the settings into a playable level. Each floor's stairs up sit in the cell farthest from
+ * where the player arrives on it, and the finish in the cell farthest from the arrival on the top floor.
+ */
+pub fn generate_level(settings: &MazeSettings) -> Level {
+    let mut rng = StdRng::seed_from_u64(settings.seed);
+    let start = 0;
+    let mut arrival = start;
+    let mut floors: Vec<(CellMaze, Vec<Vec2>, Vec<Vec2>)> = Vec::new();
+    let mut stairs = Vec::new();
+    let mut locks = Locks::default();
+
+    for floor in 0..settings.floors.max(1) {
+        let maze = generate_maze(settings, &mut rng);
+        // Up the stairs on every floor but the top one, where the finish takes that cell
+        let exit = maze.farthest_cell(arrival);
+        let distances = maze.distances_from(arrival);
+
+        if floor + 1 < settings.floors {
+            stairs.push(Stairs { position: maze.cell_center(exit), floor });
+        }
+
+        let locked_cells = place_locks(&maze, arrival, exit, floor, settings.locks, &mut rng, &mut locks);
+
+        let mut free_cells: Vec<usize> = (0..maze.cell_count())
+            .filter(|cell| !locked_cells.contains(cell))
+            .collect();
+        free_cells.shuffle(&mut rng);
+
+        let cell_count = maze.cell_count() as f32;
+        let enemy_count = (cell_count * settings.enemy_density).round() as usize;
+        let coin_count = (cell_count * settings.coin_density).round() as usize;
+
+        let enemies: Vec<Vec2> = free_cells
+            .iter()
+            .filter(|cell| distances[**cell].is_some_and(|distance| distance >= ENEMY_SAFE_DISTANCE))
+            .take(enemy_count)
+            .map(|cell| maze.cell_center(*cell))
+            .collect();
+
+        let coins: Vec<Vec2> = free_cells
+            .iter()
+            .map(|cell| maze.cell_center(*cell))
+            .filter(|position| !enemies.contains(position))
+            .take(coin_count)
+            .collect();
+
+        floors.push((maze, enemies, coins));
+        arrival = exit;
+    }
+
+    let mut name = match settings.topology {
+        MazeTopology::Polar => format!("{} {} {} rings", settings.algorithm.name(), settings.topology.name(), settings.width),
+        _ => format!("{} {} {}x{}", settings.algorithm.name(), settings.topology.name(), settings.width, settings.height),
+    };
+    if floors.len() > 1 {
+        name += &format!(" {} floors", floors.len());
+    }
+
+    // The last exit has no stairs, so it is where the finish goes
+    let finish_floor = floors.len() - 1;
+    let finish_position = floors[finish_floor].0.cell_center(arrival);
+    let mut floors = floors.into_iter();
+    let (ground, enemies, coins) = floors.next().expect("at least one floor is generated");
+
+    let mut level = Level {
+        enemies,
+        coins,
+        upper_floors: floors
+            .map(|(maze, enemies, coins)| LevelFloor {
+                enemies,
+                coins,
+                walls: maze.segments(),
+            })
+            .collect(),
+        stairs,
+        keys: locks.keys,
+        doors: locks.doors,
+        switches: locks.switches,
+        gates: locks.gates,
+        ..Level::from_cell_maze(&name, &ground, start, start)
+    };
+    level.set_finish(finish_floor, finish_position);
+
+    level
+}
+
+#[derive(Default)]
+struct Locks {
+    keys: Vec<LevelKey>,
+    doors: Vec<LevelDoor>,
+    switches: Vec<LevelSwitch>,
+    gates: Vec<LevelGate>,
+}
+
+/*
+ * Spreads locks evenly over the way from arrival to exit and returns the cells taken up, arrival and exit included.
+ * Each key or switch lies where the player gets to with only the locks before it opened, so the floor stays solvable.
+ * Switches go into dead ends, which are never walked through on the way to anything else.
+ */
+fn place_locks(
+    maze: &CellMaze,
+    arrival: usize,
+    exit: usize,
+    floor: usize,
+    count: usize,
+    rng: &mut StdRng,
+    locks: &mut Locks,
+) -> Vec<usize> {
+    let mut used = vec![arrival, exit];
+    let Some(path) = maze.shortest_path(arrival, exit) else {
+        return used;
+    };
+
+    let steps = path.len() - 1;
+    let count = count.min(steps.saturating_sub(1));
+    let edges: Vec<usize> = (0..count)
+        .filter_map(|lock| {
+            let step = (lock + 1) * steps / (count + 1);
+            maze.edge_between(path[step], path[step + 1])
+        })
+        .collect();
+
+    for (lock, edge) in edges.iter().enumerate() {
+        let mut shut = maze.clone();
+        for later in &edges[lock..] {
+            shut.set_wall(*later, true);
+        }
+
+        let distances = shut.distances_from(arrival);
+        let candidates: Vec<usize> = (0..maze.cell_count())
+            .filter(|cell| distances[*cell].is_some() && !used.contains(cell))
+            .collect();
+        let dead_ends: Vec<usize> = candidates
+            .iter()
+            .copied()
+            .filter(|cell| maze.open_neighbors(*cell).len() == 1)
+            .collect();
+        let segment = maze.edges()[*edge].segment;
+
+        // Every other lock is a gate, unless there is no dead end left for its switch
+        if lock % 2 == 1 {
+            if let Some(cell) = dead_ends.choose(rng).copied() {
+                locks.gates.push(LevelGate { segment, floor, switch: locks.switches.len(), open: false });
+                locks.switches.push(LevelSwitch { position: maze.cell_center(cell), floor });
+                used.push(cell);
+                continue;
+            }
+        }
+
+        let Some(cell) = candidates.choose(rng).copied() else {
+            continue;
+        };
+
+        let color = KeyColor::ALL[locks.doors.len() % KeyColor::ALL.len()];
+        locks.doors.push(LevelDoor { segment, floor, color });
+        locks.keys.push(LevelKey { position: maze.cell_center(cell), floor, color });
+        used.push(cell);
+    }
+
+    used
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level_validator::validate_level;
+
+    #[test]
+    fn generated_levels_with_locks_are_valid() {
+        for algorithm in MazeAlgorithm::ALL {
+            for topology in MazeTopology::ALL {
+                for floors in FLOOR_COUNTS {
+                    for (locks, seed) in [1, 3].into_iter().flat_map(|locks| (1..=3).map(move |seed| (locks, seed))) {
+                        let settings = MazeSettings {
+                            topology,
+                            width: 10,
+                            height: 10,
+                            algorithm,
+                            braid: if seed % 2 == 0 { 0.5 } else { 0. },
+                            floors,
+                            locks,
+                            seed,
+                            ..default()
+                        };
+                        let level = generate_level(&settings);
+
+                        assert_eq!(validate_level(&level), Vec::new(), "{settings:?}");
+                        assert!(level.solution().is_some(), "{settings:?}");
+                    }
+                }
+            }
+        }
+    }
+}
@@ -2,33 +2,116 @@ use bevy::prelude::*;
 
 use crate::game_state::{
     GameState,
+    PauseState,
+};
+use crate::level::{CurrentLevel, Level, Levels};
+use crate::level_export::export_level_files;
+use crate::maze_generator::generate_level;
+use crate::save_data::SaveData;
+use crate::save_slot::SaveSlot;
+use crate::settings::{
+    ControlAction,
+    RebindingControl,
+    SettingAction,
 };
 
 #[derive(Clone, Copy, Default, Eq, PartialEq, Debug, Hash, States)]
 pub enum MenuState {
-    #[default]
     Main,
+    LevelSelect,
+    CustomMaze,
+    Settings,
+    SettingsAudio,
+    SettingsDisplay,
+    SettingsControls,
+    SettingsAccessibility,
+    SettingsGameplay,
+    SettingsCoop,
+    SettingsCoopControls,
+    Pause,
+    #[default]
+    Disabled,
 }
 
 #[derive(Component)]
 pub struct OnMainMenuScreen;
 
+#[derive(Component)]
+pub struct OnPauseMenuScreen;
+
+#[derive(Component)]
+pub struct OnLevelSelectScreen;
+
+#[derive(Component)]
+pub struct OnCustomMazeScreen;
+
+// Result of the last export, shown under the pause menu buttons
+#[derive(Component)]
+pub struct ExportStatusUi;
+
+// Thumbnail of the maze the current generator settings produce
+#[derive(Component)]
+pub struct MazePreview;
+
+// Shared by the settings overview and each of its sub-pages, only one is open at a time
+#[derive(Component)]
+pub struct OnSettingsMenuScreen;
+
+// Text of a setting button, kept in sync with the current value of the setting
+#[derive(Component)]
+pub struct SettingLabel(SettingAction);
+
 #[derive(Component)]
 pub enum MenuButtonAction {
     Play,
+    Continue,
+    Levels,
+    SelectLevel(String),
+    CustomMaze,
+    PlayCustomMaze,
+    Editor,
+    Settings,
+    SettingsAudio,
+    SettingsDisplay,
+    SettingsControls,
+    SettingsAccessibility,
+    SettingsGameplay,
+    SettingsCoop,
+    SettingsCoopControls,
+    BackToMainMenu,
+    BackToSettings,
+    Resume,
+    RestartLevel,
+    ExportMaze,
+    QuitToMenu,
     Quit,
 }
 
+const PAUSE_BACKGROUND_COLOR: Color = Color::srgba(0., 0., 0., 0.6);
+const LEVEL_CARD_COLOR: Color = Color::srgb(0.15, 0.15, 0.15);
+const LOCKED_LEVEL_CARD_COLOR: Color = Color::srgb(0.06, 0.06, 0.06);
+const LOCKED_THUMBNAIL_TINT: Color = Color::srgba(1., 1., 1., 0.25);
+const MAZE_PREVIEW_SIZE: f32 = 360.;
+
 pub fn menu_setup(mut menu_state: ResMut<NextState<MenuState>>) {
     menu_state.set(MenuState::Main)
 }
 
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
 pub fn menu_action(
     interaction_query: Query<
         (&Interaction, &MenuButtonAction),
         (Changed<Interaction>, With<Button>), >,
     mut app_exit_events: EventWriter<AppExit>,
     mut game_state: ResMut<NextState<GameState>>,
+    mut menu_state: ResMut<NextState<MenuState>>,
+    mut pause_state: ResMut<NextState<PauseState>>,
+    current_game_state: Res<State<GameState>>,
+    mut save_slot: ResMut<SaveSlot>,
+    levels: Res<Levels>,
+    mut current_level: ResMut<CurrentLevel>,
+    save_data: Res<SaveData>,
+    mut export_status_query: Query<&mut Text, With<ExportStatusUi>>,
 ) {
     for (interaction, menu_button_action) in &interaction_query {
         if *interaction == Interaction::Pressed {
@@ -38,17 +121,81 @@ pub fn menu_action(
                 }
                 MenuButtonAction::Play => {
                     game_state.set(GameState::Game);
+                    menu_state.set(MenuState::Disabled);
+                }
+                MenuButtonAction::Continue => {
+                    let Some(level) = save_slot.snapshot.as_ref().and_then(|snapshot| snapshot.current_level(&levels)) else {
+                        warn!("The saved level no longer exists");
+                        continue;
+                    };
+
+                    *current_level = level;
+                    save_slot.resume = true;
+                    game_state.set(GameState::Game);
+                    menu_state.set(MenuState::Disabled);
+                }
+                MenuButtonAction::Levels => menu_state.set(MenuState::LevelSelect),
+                MenuButtonAction::SelectLevel(id) => {
+                    let Some(entry) = levels.get(id) else {
+                        continue;
+                    };
+
+                    *current_level = CurrentLevel::from(entry);
+                    game_state.set(GameState::Game);
+                    menu_state.set(MenuState::Disabled);
+                }
+                MenuButtonAction::CustomMaze => menu_state.set(MenuState::CustomMaze),
+                MenuButtonAction::PlayCustomMaze => {
+                    let maze = &save_data.settings.maze;
+
+                    *current_level = CurrentLevel {
+                        id: maze.level_id(),
+                        level: generate_level(maze),
+                    };
+                    game_state.set(GameState::Game);
+                    menu_state.set(MenuState::Disabled);
+                }
+                MenuButtonAction::Editor => {
+                    game_state.set(GameState::Editor);
+                    menu_state.set(MenuState::Disabled);
+                }
+                MenuButtonAction::Settings => menu_state.set(MenuState::Settings),
+                MenuButtonAction::SettingsAudio => menu_state.set(MenuState::SettingsAudio),
+                MenuButtonAction::SettingsDisplay => menu_state.set(MenuState::SettingsDisplay),
+                MenuButtonAction::SettingsControls => menu_state.set(MenuState::SettingsControls),
+                MenuButtonAction::SettingsAccessibility => menu_state.set(MenuState::SettingsAccessibility),
+                MenuButtonAction::SettingsGameplay => menu_state.set(MenuState::SettingsGameplay),
+                MenuButtonAction::SettingsCoop => menu_state.set(MenuState::SettingsCoop),
+                MenuButtonAction::SettingsCoopControls => menu_state.set(MenuState::SettingsCoopControls),
+                // The settings pages are shared with the pause menu, so go back to wherever they were opened from
+                MenuButtonAction::BackToMainMenu => match current_game_state.get() {
+                    GameState::Game => menu_state.set(MenuState::Pause),
+                    _ => menu_state.set(MenuState::Main),
+                },
+                MenuButtonAction::BackToSettings => menu_state.set(MenuState::Settings),
+                MenuButtonAction::Resume => pause_state.set(PauseState::Running),
+                MenuButtonAction::RestartLevel => game_state.set(GameState::Restart),
+                MenuButtonAction::ExportMaze => {
+                    let status = match export_level_files(&current_level.id, &current_level.level) {
+                        Ok(path) => format!("Exported SVG and PNG next to {path}"),
+                        Err(error) => format!("Could not export: {error}"),
+                    };
+
+                    for mut text in &mut export_status_query {
+                        text.sections[0].value = status.clone();
+                    }
                 }
+                MenuButtonAction::QuitToMenu => game_state.set(GameState::Menu),
             }
         }
     }
 }
 
-pub fn main_menu_setup(mut commands: Commands) {
+pub fn main_menu_setup(mut commands: Commands, save_slot: Res<SaveSlot>) {
     let button_style = Style {
         width: Val::Px(250.),
-        height: Val::Px(65.),
-        margin: UiRect::all(Val::Px(20.)),
+        height: Val::Px(55.),
+        margin: UiRect::all(Val::Px(10.)),
         justify_content: JustifyContent::Center,
         align_items: AlignItems::Center,
         ..default()
@@ -64,6 +211,7 @@ pub fn main_menu_setup(mut commands: Commands) {
             style: Style {
                 width: Val::Percent(100.),
                 height: Val::Percent(100.),
+                flex_direction: FlexDirection::Column,
                 align_items: AlignItems::Center,
                 justify_content: JustifyContent::Center,
                 ..default()
@@ -87,6 +235,78 @@ pub fn main_menu_setup(mut commands: Commands) {
             ));
         });
 
+        if save_slot.snapshot.is_some() {
+            parent.spawn((
+                ButtonBundle {
+                    style: button_style.clone(),
+                    ..default()
+                },
+                MenuButtonAction::Continue
+            ))
+            .with_children(|parent| {
+                parent.spawn(TextBundle::from_section(
+                    "Continue",
+                    button_text_style.clone(),
+                ));
+            });
+        }
+
+        parent.spawn((
+            ButtonBundle {
+                style: button_style.clone(),
+                ..default()
+            },
+            MenuButtonAction::Levels
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Levels",
+                button_text_style.clone(),
+            ));
+        });
+
+        parent.spawn((
+            ButtonBundle {
+                style: button_style.clone(),
+                ..default()
+            },
+            MenuButtonAction::CustomMaze
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Custom Maze",
+                button_text_style.clone(),
+            ));
+        });
+
+        parent.spawn((
+            ButtonBundle {
+                style: button_style.clone(),
+                ..default()
+            },
+            MenuButtonAction::Editor
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Editor",
+                button_text_style.clone(),
+            ));
+        });
+
+        parent.spawn((
+            ButtonBundle {
+                style: button_style.clone(),
+                ..default()
+            },
+            MenuButtonAction::Settings
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Settings",
+                button_text_style.clone(),
+            ));
+        });
+
         parent.spawn((
             ButtonBundle {
                 style: button_style.clone(),
@@ -104,3 +324,414 @@ pub fn main_menu_setup(mut commands: Commands) {
     });
 }
 
+pub fn level_select_menu_setup(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    levels: Res<Levels>,
+    save_data: Res<SaveData>,
+) {
+    commands.spawn((settings_page_layout(), OnLevelSelectScreen)).with_children(|parent| {
+        parent.spawn(NodeBundle {
+            style: Style {
+                flex_wrap: FlexWrap::Wrap,
+                justify_content: JustifyContent::Center,
+                max_width: Val::Percent(90.),
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            for entry in &levels.0 {
+                let unlocked = levels.is_unlocked(entry, &save_data);
+                let thumbnail = images.add(entry.level.thumbnail());
+
+                let card_style = Style {
+                    width: Val::Px(200.),
+                    margin: UiRect::all(Val::Px(10.)),
+                    padding: UiRect::all(Val::Px(10.)),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    ..default()
+                };
+
+                let mut card = if unlocked {
+                    parent.spawn((
+                        ButtonBundle {
+                            style: card_style,
+                            background_color: LEVEL_CARD_COLOR.into(),
+                            ..default()
+                        },
+                        MenuButtonAction::SelectLevel(entry.id.clone()),
+                    ))
+                } else {
+                    // Locked levels are shown so the player knows what is ahead, but cannot be picked
+                    parent.spawn(NodeBundle {
+                        style: card_style,
+                        background_color: LOCKED_LEVEL_CARD_COLOR.into(),
+                        ..default()
+                    })
+                };
+
+                card.with_children(|parent| {
+                    parent.spawn(ImageBundle {
+                        style: Style {
+                            width: Val::Px(180.),
+                            height: Val::Px(180.),
+                            ..default()
+                        },
+                        image: UiImage::new(thumbnail).with_color(if unlocked {
+                            Color::WHITE
+                        } else {
+                            LOCKED_THUMBNAIL_TINT
+                        }),
+                        ..default()
+                    });
+
+                    parent.spawn(TextBundle::from_section(entry.level.name.clone(), settings_text_style()));
+                    parent.spawn(TextBundle::from_section(
+                        level_record(&entry.id, unlocked, &save_data),
+                        TextStyle {
+                            font_size: 20.,
+                            ..default()
+                        },
+                    ));
+                });
+            }
+        });
+
+        spawn_menu_button(parent, "Back", MenuButtonAction::BackToMainMenu);
+    });
+}
+
+pub fn custom_maze_menu_setup(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    save_data: Res<SaveData>,
+) {
+    let level = generate_level(&save_data.settings.maze);
+    let preview = images.add(level.thumbnail());
+
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            ..default()
+        },
+        OnCustomMazeScreen,
+    )).with_children(|parent| {
+        parent.spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Column,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            for action in [
+                SettingAction::MazeTopology,
+                SettingAction::MazeWidth,
+                SettingAction::MazeHeight,
+                SettingAction::MazeFloors,
+                SettingAction::MazeLocks,
+                SettingAction::MazeAlgorithm,
+                SettingAction::MazeBraid,
+                SettingAction::EnemyDensity,
+                SettingAction::CoinDensity,
+                SettingAction::MazeSeed,
+            ] {
+                spawn_setting_button(parent, action, &save_data);
+            }
+        });
+
+        parent.spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                margin: UiRect::left(Val::Px(20.)),
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                ImageBundle {
+                    style: maze_preview_style(&level),
+                    image: UiImage::new(preview),
+                    ..default()
+                },
+                MazePreview,
+            ));
+
+            spawn_menu_button(parent, "Play", MenuButtonAction::PlayCustomMaze);
+            spawn_menu_button(parent, "Back", MenuButtonAction::BackToMainMenu);
+        });
+    });
+}
+
+// Keeps the preview at the maze's aspect ratio within a fixed square
+fn maze_preview_style(level: &Level) -> Style {
+    let (min, max) = level.bounds();
+    let size = max - min;
+    let scale = MAZE_PREVIEW_SIZE / size.max_element();
+
+    Style {
+        width: Val::Px(size.x * scale),
+        height: Val::Px(size.y * scale),
+        margin: UiRect::bottom(Val::Px(8.)),
+        ..default()
+    }
+}
+
+pub fn update_maze_preview(
+    save_data: Res<SaveData>,
+    mut images: ResMut<Assets<Image>>,
+    mut preview_query: Query<(&UiImage, &mut Style), With<MazePreview>>,
+) {
+    // Save data changes all through play, only the custom maze screen shows the preview
+    if !save_data.is_changed() || preview_query.is_empty() { return };
+
+    let level = generate_level(&save_data.settings.maze);
+
+    for (preview, mut style) in &mut preview_query {
+        images.insert(&preview.texture, level.thumbnail());
+        *style = maze_preview_style(&level);
+    }
+}
+
+fn level_record(id: &str, unlocked: bool, save_data: &SaveData) -> String {
+    if !unlocked {
+        return "Locked".to_string();
+    }
+
+    match (save_data.best_times.get(id), save_data.best_scores.get(id)) {
+        (Some(time), Some(score)) => format!("Best {time:.2}s, score {score}"),
+        _ => "Not finished yet".to_string(),
+    }
+}
+
+pub fn pause_menu_setup(mut commands: Commands) {
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            background_color: PAUSE_BACKGROUND_COLOR.into(),
+            ..default()
+        },
+        OnPauseMenuScreen,
+    )).with_children(|parent| {
+        parent.spawn(TextBundle::from_section(
+            "Paused",
+            TextStyle {
+                font_size: 60.,
+                ..default()
+            },
+        ));
+
+        spawn_menu_button(parent, "Resume", MenuButtonAction::Resume);
+        spawn_menu_button(parent, "Restart Level", MenuButtonAction::RestartLevel);
+        spawn_menu_button(parent, "Settings", MenuButtonAction::Settings);
+        spawn_menu_button(parent, "Export Maze", MenuButtonAction::ExportMaze);
+        spawn_menu_button(parent, "Quit to Menu", MenuButtonAction::QuitToMenu);
+
+        parent.spawn((
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font_size: 20.,
+                    ..default()
+                },
+            ),
+            ExportStatusUi,
+        ));
+    });
+}
+
+fn settings_page_layout() -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            width: Val::Percent(100.),
+            height: Val::Percent(100.),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        ..default()
+    }
+}
+
+fn settings_button_style() -> Style {
+    Style {
+        width: Val::Px(500.),
+        height: Val::Px(55.),
+        margin: UiRect::all(Val::Px(8.)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    }
+}
+
+fn settings_text_style() -> TextStyle {
+    TextStyle {
+        font_size: 30.,
+        ..default()
+    }
+}
+
+fn spawn_menu_button(parent: &mut ChildBuilder, text: &str, action: MenuButtonAction) {
+    parent.spawn((
+        ButtonBundle {
+            style: settings_button_style(),
+            ..default()
+        },
+        action,
+    ))
+    .with_children(|parent| {
+        parent.spawn(TextBundle::from_section(text, settings_text_style()));
+    });
+}
+
+fn spawn_setting_button(parent: &mut ChildBuilder, action: SettingAction, save_data: &SaveData) {
+    parent.spawn((
+        ButtonBundle {
+            style: settings_button_style(),
+            ..default()
+        },
+        action,
+    ))
+    .with_children(|parent| {
+        parent.spawn((
+            TextBundle::from_section(action.label(&save_data.settings, &RebindingControl::default()), settings_text_style()),
+            SettingLabel(action),
+        ));
+    });
+}
+
+pub fn settings_menu_setup(mut commands: Commands) {
+    commands.spawn((settings_page_layout(), OnSettingsMenuScreen)).with_children(|parent| {
+        spawn_menu_button(parent, "Audio", MenuButtonAction::SettingsAudio);
+        spawn_menu_button(parent, "Display", MenuButtonAction::SettingsDisplay);
+        spawn_menu_button(parent, "Controls", MenuButtonAction::SettingsControls);
+        spawn_menu_button(parent, "Accessibility", MenuButtonAction::SettingsAccessibility);
+        spawn_menu_button(parent, "Gameplay", MenuButtonAction::SettingsGameplay);
+        spawn_menu_button(parent, "Co-op", MenuButtonAction::SettingsCoop);
+        spawn_menu_button(parent, "Back", MenuButtonAction::BackToMainMenu);
+    });
+}
+
+fn spawn_settings_page(commands: &mut Commands, save_data: &SaveData, actions: &[SettingAction]) {
+    commands.spawn((settings_page_layout(), OnSettingsMenuScreen)).with_children(|parent| {
+        for action in actions {
+            spawn_setting_button(parent, *action, save_data);
+        }
+        spawn_menu_button(parent, "Back", MenuButtonAction::BackToSettings);
+    });
+}
+
+pub fn audio_settings_menu_setup(mut commands: Commands, save_data: Res<SaveData>) {
+    spawn_settings_page(&mut commands, &save_data, &[
+        SettingAction::MusicVolume,
+        SettingAction::SfxVolume,
+    ]);
+}
+
+pub fn display_settings_menu_setup(mut commands: Commands, save_data: Res<SaveData>) {
+    spawn_settings_page(&mut commands, &save_data, &[
+        SettingAction::WindowMode,
+        SettingAction::Resolution,
+        SettingAction::Vsync,
+    ]);
+}
+
+pub fn controls_settings_menu_setup(mut commands: Commands, save_data: Res<SaveData>) {
+    let actions = ControlAction::ALL.map(|action| SettingAction::Rebind(0, action));
+    spawn_settings_page(&mut commands, &save_data, &actions);
+}
+
+// The second player's keys get a page of their own, both sets of controls do not fit on one
+pub fn coop_controls_settings_menu_setup(mut commands: Commands, save_data: Res<SaveData>) {
+    let actions = ControlAction::ALL.map(|action| SettingAction::Rebind(1, action));
+    spawn_settings_page(&mut commands, &save_data, &actions);
+}
+
+pub fn accessibility_settings_menu_setup(mut commands: Commands, save_data: Res<SaveData>) {
+    spawn_settings_page(&mut commands, &save_data, &[
+        SettingAction::ColorblindPalette,
+        SettingAction::ReducedMotion,
+    ]);
+}
+
+pub fn gameplay_settings_menu_setup(mut commands: Commands, save_data: Res<SaveData>) {
+    spawn_settings_page(&mut commands, &save_data, &[
+        SettingAction::FogOfWar,
+        SettingAction::Minimap,
+        SettingAction::MinimapFinish,
+        SettingAction::MinimapCoins,
+    ]);
+}
+
+pub fn coop_settings_menu_setup(mut commands: Commands, save_data: Res<SaveData>) {
+    commands.spawn((settings_page_layout(), OnSettingsMenuScreen)).with_children(|parent| {
+        for action in [
+            SettingAction::TwoPlayers,
+            SettingAction::SplitScreen,
+            SettingAction::CoopScoring,
+            SettingAction::CoopFinish,
+        ] {
+            spawn_setting_button(parent, action, &save_data);
+        }
+        spawn_menu_button(parent, "Player 2 controls", MenuButtonAction::SettingsCoopControls);
+        spawn_menu_button(parent, "Back", MenuButtonAction::BackToSettings);
+    });
+}
+
+#[allow(clippy::type_complexity)]
+pub fn setting_action(
+    interaction_query: Query<
+        (&Interaction, &SettingAction),
+        (Changed<Interaction>, With<Button>), >,
+    mut save_data: ResMut<SaveData>,
+    mut rebinding: ResMut<RebindingControl>,
+) {
+    for (interaction, setting_action) in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            if let SettingAction::Rebind(player, control_action) = setting_action {
+                *rebinding = RebindingControl {
+                    control: Some((*player, *control_action)),
+                    notice: None,
+                };
+                continue;
+            }
+
+            setting_action.apply(&mut save_data.settings);
+            save_data.save();
+        }
+    }
+}
+
+pub fn update_setting_labels(
+    save_data: Res<SaveData>,
+    rebinding: Res<RebindingControl>,
+    mut label_query: Query<(&mut Text, &SettingLabel)>,
+) {
+    if !save_data.is_changed() && !rebinding.is_changed() { return };
+
+    for (mut text, label) in &mut label_query {
+        text.sections[0].value = label.0.label(&save_data.settings, &rebinding);
+    }
+}
+
+pub fn stop_rebinding(mut rebinding: ResMut<RebindingControl>) {
+    *rebinding = RebindingControl::default();
+}
@@ -0,0 +1,281 @@
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        texture::ImageSampler,
+    },
+};
+
+use crate::coins::Coin;
+use crate::floors::{CurrentFloor, Floor};
+use crate::fog::FogOfWar;
+use crate::game_state::OnGameScreen;
+use crate::level::CurrentLevel;
+use crate::maze::{CellMaze, MAZE_CELL_SIZE};
+use crate::player::player::Player;
+use crate::save_data::SaveData;
+
+// Longest side of the minimap on screen, mazes are drawn with as many pixels per cell as fit
+const MINIMAP_SIZE: f32 = 180.;
+const MINIMAP_MAX_CELL_PIXELS: usize = 8;
+const MINIMAP_BACKGROUND: [u8; 4] = [0, 0, 0, 170];
+const MINIMAP_EXPLORED: [u8; 4] = [55, 55, 70, 220];
+const MINIMAP_WALL: [u8; 4] = [220, 220, 220, 255];
+const MINIMAP_PLAYER: [u8; 4] = [80, 140, 255, 255];
+const MINIMAP_SECOND_PLAYER: [u8; 4] = [255, 140, 115, 255];
+const MINIMAP_FINISH: [u8; 4] = [0, 200, 0, 255];
+const MINIMAP_COIN: [u8; 4] = [230, 200, 0, 255];
+const MINIMAP_STAIRS: [u8; 4] = [170, 110, 50, 255];
+const MINIMAP_TEXT_COLOR: Color = Color::srgb(80., 80., 80.);
+
+#[derive(Component)]
+pub struct MinimapUi;
+
+#[derive(Component)]
+pub struct MinimapFloorText;
+
+// What the minimap image was last drawn from, it is only drawn again once any of it changes
+#[derive(Clone, PartialEq)]
+struct MinimapState {
+    floor: usize,
+    cells: Vec<usize>,
+    coins: usize,
+    show_finish: bool,
+    show_coins: bool,
+}
+
+#[derive(Resource, Default)]
+pub struct Minimap {
+    image: Handle<Image>,
+    mazes: Vec<CellMaze>,
+    min: Vec2,
+    width: usize,
+    height: usize,
+    cell_pixels: usize,
+    // Cells the player has stood in, the explored area while there is no fog of war to go by
+    visited: Vec<Vec<bool>>,
+    drawn: Option<MinimapState>,
+}
+
+impl Minimap {
+    // World y grows upwards while image rows grow downwards
+    fn to_pixel(&self, position: Vec2) -> (isize, isize) {
+        let pixel = (position - self.min) / MAZE_CELL_SIZE * self.cell_pixels as f32;
+        (pixel.x.round() as isize, self.height as isize - 1 - pixel.y.round() as isize)
+    }
+
+    fn stamp(&self, pixels: &mut [[u8; 4]], position: Vec2, grow: isize, color: [u8; 4]) {
+        let (x, y) = self.to_pixel(position);
+
+        for stamp_y in (y - grow).max(0)..=(y + grow).min(self.height as isize - 1) {
+            for stamp_x in (x - grow).max(0)..=(x + grow).min(self.width as isize - 1) {
+                pixels[stamp_y as usize * self.width + stamp_x as usize] = color;
+            }
+        }
+    }
+
+    fn line(&self, pixels: &mut [[u8; 4]], [start_x, start_y, end_x, end_y]: [f32; 4], color: [u8; 4]) {
+        let (start, end) = (Vec2::new(start_x, start_y), Vec2::new(end_x, end_y));
+        let steps = (start.distance(end) / MAZE_CELL_SIZE * self.cell_pixels as f32).ceil().max(1.) as usize;
+
+        for step in 0..=steps {
+            self.stamp(pixels, start.lerp(end, step as f32 / steps as f32), 0, color);
+        }
+    }
+}
+
+pub fn spawn_minimap(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    current_level: Res<CurrentLevel>,
+) {
+    let level = &current_level.level;
+    let (min, max) = level.bounds();
+    let cells = (max - min) / MAZE_CELL_SIZE;
+    let cell_pixels = ((MINIMAP_SIZE / cells.max_element()) as usize).clamp(2, MINIMAP_MAX_CELL_PIXELS);
+    let width = (cells.x * cell_pixels as f32).round() as usize + 1;
+    let height = (cells.y * cell_pixels as f32).round() as usize + 1;
+    let mazes: Vec<CellMaze> = (0..level.floor_count()).map(|floor| level.floor_maze(floor)).collect();
+
+    let image = images.add(minimap_image(width, height, vec![MINIMAP_BACKGROUND; width * height]));
+
+    commands
+        .spawn((
+            MinimapUi,
+            OnGameScreen,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(55.),
+                    right: Val::Px(5.),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::End,
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn(ImageBundle {
+                style: Style {
+                    width: Val::Px(width as f32),
+                    height: Val::Px(height as f32),
+                    ..default()
+                },
+                image: UiImage::new(image.clone()),
+                ..default()
+            });
+
+            // Only levels with more than one floor say which one the map shows
+            if level.floor_count() > 1 {
+                parent.spawn((
+                    MinimapFloorText,
+                    TextBundle::from_section(
+                        "",
+                        TextStyle {
+                            font_size: 16.,
+                            color: MINIMAP_TEXT_COLOR,
+                            ..default()
+                        },
+                    ),
+                ));
+            }
+        });
+
+    commands.insert_resource(Minimap {
+        image,
+        visited: mazes.iter().map(|maze| vec![false; maze.cell_count()]).collect(),
+        mazes,
+        min,
+        width,
+        height,
+        cell_pixels,
+        drawn: None,
+    });
+}
+
+fn minimap_image(width: usize, height: usize, pixels: Vec<[u8; 4]>) -> Image {
+    let mut image = Image::new(
+        Extent3d {
+            width: width as u32,
+            height: height as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        pixels.concat(),
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    // Keeps the few pixels per cell crisp instead of blurring walls into the floor
+    image.sampler = ImageSampler::nearest();
+    image
+}
+
+pub fn toggle_minimap(mut save_data: ResMut<SaveData>) {
+    save_data.settings.minimap = !save_data.settings.minimap;
+    save_data.save();
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn update_minimap(
+    mut minimap: ResMut<Minimap>,
+    mut images: ResMut<Assets<Image>>,
+    fog: Res<FogOfWar>,
+    current_floor: Res<CurrentFloor>,
+    current_level: Res<CurrentLevel>,
+    save_data: Res<SaveData>,
+    player_query: Query<(&Transform, &Player)>,
+    coins_query: Query<(&Transform, &Floor), With<Coin>>,
+    mut minimap_ui_query: Query<&mut Visibility, With<MinimapUi>>,
+    mut floor_text_query: Query<&mut Text, With<MinimapFloorText>>,
+) {
+    let settings = &save_data.settings;
+
+    for mut visibility in &mut minimap_ui_query {
+        visibility.set_if_neq(if settings.minimap { Visibility::Inherited } else { Visibility::Hidden });
+    }
+
+    let floor = current_floor.floor;
+    let Some(maze) = minimap.mazes.get(floor) else {
+        return;
+    };
+
+    let mut players: Vec<(Vec2, usize, usize)> = player_query
+        .iter()
+        .filter_map(|(player_transform, player)| {
+            let position = player_transform.translation.truncate();
+            maze.cell_at(position).map(|cell| (position, cell, player.index))
+        })
+        .collect();
+    if players.is_empty() { return };
+    // The first player is drawn last, on top of the second one when they share a cell
+    players.sort_by_key(|(_, _, index)| std::cmp::Reverse(*index));
+
+    let coins: Vec<Vec2> = coins_query
+        .iter()
+        .filter(|(_, coin_floor)| current_floor.contains(coin_floor))
+        .map(|(coin_transform, _)| coin_transform.translation.truncate())
+        .collect();
+
+    let state = MinimapState {
+        floor,
+        cells: players.iter().map(|(_, cell, _)| *cell).collect(),
+        coins: coins.len(),
+        show_finish: settings.minimap_finish,
+        show_coins: settings.minimap_coins,
+    };
+    if minimap.drawn.as_ref() == Some(&state) { return };
+
+    for (_, cell, _) in &players {
+        minimap.visited[floor][*cell] = true;
+    }
+
+    let level = &current_level.level;
+    let maze = &minimap.mazes[floor];
+    // With fog of war the map only knows what has been seen, without it the whole maze is on screen anyway
+    let explored = |cell: usize| {
+        if fog.enabled { fog.explored(floor, cell) } else { minimap.visited[floor][cell] }
+    };
+    let known = |position: Vec2| !fog.enabled || maze.cell_at(position).is_some_and(explored);
+
+    let mut pixels = vec![MINIMAP_BACKGROUND; minimap.width * minimap.height];
+    let cell_grow = (minimap.cell_pixels / 2) as isize;
+
+    for cell in (0..maze.cell_count()).filter(|cell| explored(*cell)) {
+        minimap.stamp(&mut pixels, maze.cell_center(cell), cell_grow, MINIMAP_EXPLORED);
+    }
+
+    for edge in maze.edges().iter().filter(|edge| edge.wall) {
+        if !fog.enabled || explored(edge.cell) || edge.neighbor.is_some_and(explored) {
+            minimap.line(&mut pixels, edge.segment, MINIMAP_WALL);
+        }
+    }
+
+    for stairs in level.stairs.iter().filter(|stairs| stairs.floor == floor || stairs.floor + 1 == floor) {
+        if known(stairs.position) {
+            minimap.stamp(&mut pixels, stairs.position, 1, MINIMAP_STAIRS);
+        }
+    }
+
+    if settings.minimap_finish && level.finish_floor == floor && known(level.finish_area_center()) {
+        minimap.stamp(&mut pixels, level.finish_area_center(), cell_grow.max(1), MINIMAP_FINISH);
+    }
+
+    if settings.minimap_coins {
+        for coin in coins.iter().filter(|coin| known(**coin)) {
+            minimap.stamp(&mut pixels, *coin, 0, MINIMAP_COIN);
+        }
+    }
+
+    for (position, _, index) in &players {
+        minimap.stamp(&mut pixels, *position, 1, if *index == 0 { MINIMAP_PLAYER } else { MINIMAP_SECOND_PLAYER });
+    }
+
+    images.insert(&minimap.image, minimap_image(minimap.width, minimap.height, pixels));
+    minimap.drawn = Some(state);
+
+    for mut text in &mut floor_text_query {
+        text.sections[0].value = format!("Floor {}", floor + 1);
+    }
+}
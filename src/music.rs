@@ -1,19 +1,261 @@
 use bevy::prelude::*;
 use bevy::audio::{PlaybackMode, Volume};
 
-pub fn play_music(
-    commands: &mut Commands,
-    asset_server: &Res<AssetServer>,
+use crate::audio::{AudioBus, OnAudioBus, SfxPlugin, SynthSound};
+use crate::enemy::{Enemy, PlayerHitEvent, Velocity};
+use crate::finish_area::FinishEvent;
+use crate::floors::{CurrentFloor, Floor};
+use crate::game_state::{GameState, PauseState};
+use crate::level::CurrentLevel;
+use crate::player::player::Player;
+use crate::save_data::SaveData;
+
+// Enemies within the first distance of a player make the music fully intense, beyond the second it stays calm
+const DANGER_DISTANCE: f32 = 60.;
+const CALM_DISTANCE: f32 = 180.;
+// An enemy heading straight for a player is heard coming from further away
+const CHASE_DISTANCE: f32 = 300.;
+const CHASE_ALIGNMENT: f32 = 0.9;
+// How fast the layers crossfade, in full intensity per second
+const CROSSFADE_SPEED: f32 = 1.5;
+// Loudness left to the calm layer at full intensity
+const INTENSE_CALM_GAIN: f32 = 0.4;
+const FADE_OUT_SECONDS: f32 = 1.;
+
+// Every piece of music, the pause menu pauses all of it
+#[derive(Component)]
+pub struct Music;
+
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MusicLayer {
+    Calm,
+    Intense,
+}
+
+// Music on its way out, despawned once silent
+#[derive(Component)]
+pub struct FadingOut;
+
+#[derive(Resource, Default)]
+pub struct MusicDirector {
+    // 0 plays only the calm layer, 1 brings in the intense one
+    pub intensity: f32,
+    // Set once a sting played, the level's layers are fading out by then
+    pub ended: bool,
+}
+
+#[derive(Resource)]
+pub struct MusicBank {
+    heartbeat: Handle<SynthSound>,
+    victory_sting: Handle<SynthSound>,
+    defeat_sting: Handle<SynthSound>,
+}
+
+impl FromWorld for MusicBank {
+    fn from_world(world: &mut World) -> Self {
+        let mut sounds = world.resource_mut::<Assets<SynthSound>>();
+
+        MusicBank {
+            heartbeat: sounds.add(SynthSound::heartbeat()),
+            victory_sting: sounds.add(SynthSound::victory_sting()),
+            defeat_sting: sounds.add(SynthSound::defeat_sting()),
+        }
+    }
+}
+
+// Plays the level's music while in the game
+pub struct MusicPlugin;
+
+impl Plugin for MusicPlugin {
+    fn build(&self, app: &mut App) {
+        assert!(app.is_plugin_added::<SfxPlugin>(), "MusicPlugin plays synthesized sounds, add SfxPlugin before it");
+
+        app.init_resource::<MusicDirector>()
+            .init_resource::<MusicBank>()
+            .add_systems(OnEnter(GameState::Game), start_music)
+            .add_systems(OnExit(GameState::Game), fade_out_music)
+            .add_systems(
+                Update,
+                (
+                    (direct_music.run_if(in_state(PauseState::Running)), play_music_stings)
+                        .run_if(in_state(GameState::Game)),
+                    fade_music,
+                )
+                .chain(),
+            );
+    }
+}
+
+// Both layers start together and keep looping, the director only changes how loud each one is
+pub fn start_music(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    music_bank: Res<MusicBank>,
+    current_level: Res<CurrentLevel>,
+    save_data: Res<SaveData>,
+    mut music_director: ResMut<MusicDirector>,
 ) {
-    commands.spawn(
+    let music = &current_level.level.music;
+    *music_director = MusicDirector::default();
+
+    let settings = |gain: f32| PlaybackSettings {
+        mode: PlaybackMode::Loop,
+        volume: Volume::new(save_data.settings.music_volume * gain),
+        speed: music.speed,
+        ..default()
+    };
+
+    commands.spawn((
         AudioBundle {
-            source: asset_server.load::<AudioSource>("game_song.ogg"),
+            source: asset_server.load::<AudioSource>(&music.calm),
+            settings: settings(1.),
+        },
+        Music,
+        MusicLayer::Calm,
+        OnAudioBus { bus: AudioBus::Music, gain: 1. },
+    ));
+
+    let intense = (Music, MusicLayer::Intense, OnAudioBus { bus: AudioBus::Music, gain: 0. });
+    match &music.intense {
+        Some(track) => commands.spawn((
+            AudioBundle {
+                source: asset_server.load::<AudioSource>(track),
+                settings: settings(0.),
+            },
+            intense,
+        )),
+        None => commands.spawn((
+            AudioSourceBundle {
+                source: music_bank.heartbeat.clone(),
+                settings: settings(0.),
+            },
+            intense,
+        )),
+    };
+}
+
+// How close the nearest threat is to any player still in the game, from 0 for none to 1 for right there
+fn danger(
+    player_query: &Query<(&Transform, &Player)>,
+    enemy_query: &Query<(&Transform, &Velocity, &Floor), With<Enemy>>,
+    current_floor: &CurrentFloor,
+) -> f32 {
+    let closeness = |distance: f32, range: f32| 1. - ((distance - DANGER_DISTANCE) / (range - DANGER_DISTANCE)).clamp(0., 1.);
+
+    let mut danger: f32 = 0.;
+
+    for (player_transform, _) in player_query.iter().filter(|(_, player)| player.alive) {
+        for (enemy_transform, enemy_velocity, _) in enemy_query.iter().filter(|(_, _, floor)| current_floor.contains(floor)) {
+            let offset = player_transform.translation.truncate() - enemy_transform.translation.truncate();
+            let distance = offset.length();
+
+            danger = danger.max(closeness(distance, CALM_DISTANCE));
+
+            let chasing = enemy_velocity.normalize_or_zero().dot(offset.normalize_or_zero()) > CHASE_ALIGNMENT;
+            if chasing {
+                danger = danger.max(closeness(distance, CHASE_DISTANCE));
+            }
+        }
+    }
+
+    danger
+}
+
+pub fn direct_music(
+    time: Res<Time>,
+    save_data: Res<SaveData>,
+    current_floor: Res<CurrentFloor>,
+    mut music_director: ResMut<MusicDirector>,
+    player_query: Query<(&Transform, &Player)>,
+    enemy_query: Query<(&Transform, &Velocity, &Floor), With<Enemy>>,
+    mut layer_query: Query<(&MusicLayer, &mut OnAudioBus, Option<&AudioSink>), Without<FadingOut>>,
+) {
+    let target = danger(&player_query, &enemy_query, &current_floor);
+    let step = time.delta_seconds() * CROSSFADE_SPEED;
+    music_director.intensity += (target - music_director.intensity).clamp(-step, step);
+
+    let intensity = music_director.intensity;
+
+    for (layer, mut bus, sink) in &mut layer_query {
+        bus.gain = match layer {
+            MusicLayer::Calm => 1. - (1. - INTENSE_CALM_GAIN) * intensity,
+            MusicLayer::Intense => intensity,
+        };
+
+        if let Some(sink) = sink {
+            sink.set_volume(bus.volume(&save_data.settings));
+        }
+    }
+}
+
+// Winning or losing the whole team fades out the level's music and plays a short sting over it
+#[allow(clippy::too_many_arguments)]
+pub fn play_music_stings(
+    mut commands: Commands,
+    mut hit_events: EventReader<PlayerHitEvent>,
+    mut finish_events: EventReader<FinishEvent>,
+    mut music_director: ResMut<MusicDirector>,
+    music_bank: Res<MusicBank>,
+    save_data: Res<SaveData>,
+    player_query: Query<&Player>,
+    layer_query: Query<Entity, (With<MusicLayer>, Without<FadingOut>)>,
+) {
+    let caught = hit_events.read().count() > 0;
+    let won = finish_events.read().count() > 0;
+
+    if music_director.ended { return };
+
+    let sting = if won {
+        music_bank.victory_sting.clone()
+    } else if caught && player_query.iter().all(|player| !player.alive) {
+        music_bank.defeat_sting.clone()
+    } else {
+        return;
+    };
+    music_director.ended = true;
+
+    for layer in &layer_query {
+        commands.entity(layer).insert(FadingOut);
+    }
+
+    commands.spawn((
+        AudioSourceBundle {
+            source: sting,
             settings: PlaybackSettings {
-                mode: PlaybackMode::Loop,
-                volume: Volume::new(1.),
+                mode: PlaybackMode::Despawn,
+                volume: Volume::new(save_data.settings.music_volume),
                 ..default()
             },
-        }
-    );
+        },
+        Music,
+        OnAudioBus { bus: AudioBus::Music, gain: 1. },
+    ));
 }
 
+// Leaving the game fades the music out instead of cutting it off, a restarted level starts its own meanwhile
+pub fn fade_out_music(
+    mut commands: Commands,
+    layer_query: Query<Entity, (With<MusicLayer>, Without<FadingOut>)>,
+) {
+    for layer in &layer_query {
+        commands.entity(layer).insert(FadingOut);
+    }
+}
+
+// Runs on real time, so music still fades out while the game is paused or slowed down
+pub fn fade_music(
+    mut commands: Commands,
+    real_time: Res<Time<Real>>,
+    save_data: Res<SaveData>,
+    mut fading_query: Query<(Entity, &mut OnAudioBus, Option<&AudioSink>), With<FadingOut>>,
+) {
+    for (entity, mut bus, sink) in &mut fading_query {
+        bus.gain -= real_time.delta_seconds() / FADE_OUT_SECONDS;
+
+        if bus.gain <= 0. {
+            commands.entity(entity).despawn();
+        } else if let Some(sink) = sink {
+            sink.set_volume(bus.volume(&save_data.settings));
+        }
+    }
+}
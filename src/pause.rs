@@ -0,0 +1,83 @@
+use bevy::{
+    audio::AudioSinkPlayback,
+    prelude::*,
+    window::WindowFocused,
+};
+
+use crate::game_state::PauseState;
+use crate::menu::MenuState;
+use crate::music::Music;
+
+pub fn toggle_pause(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_input: Res<ButtonInput<GamepadButton>>,
+    pause_state: Res<State<PauseState>>,
+    menu_state: Res<State<MenuState>>,
+    mut next_pause_state: ResMut<NextState<PauseState>>,
+) {
+    let start_pressed = gamepads
+        .iter()
+        .any(|gamepad| gamepad_input.just_pressed(GamepadButton::new(gamepad, GamepadButtonType::Start)));
+
+    if !keyboard_input.just_pressed(KeyCode::Escape) && !start_pressed { return };
+
+    match pause_state.get() {
+        PauseState::Running => next_pause_state.set(PauseState::Paused),
+        // Escape inside the settings pages belongs to them, only the pause menu itself resumes
+        PauseState::Paused if *menu_state.get() == MenuState::Pause => next_pause_state.set(PauseState::Running),
+        PauseState::Paused => {}
+    }
+}
+
+pub fn pause_on_focus_lost(
+    mut focus_events: EventReader<WindowFocused>,
+    pause_state: Res<State<PauseState>>,
+    mut next_pause_state: ResMut<NextState<PauseState>>,
+) {
+    let focus_lost = focus_events.read().any(|event| !event.focused);
+
+    if focus_lost && *pause_state.get() == PauseState::Running {
+        next_pause_state.set(PauseState::Paused);
+    }
+}
+
+pub fn pause_game(
+    mut time: ResMut<Time<Virtual>>,
+    music_query: Query<&AudioSink, With<Music>>,
+    spatial_query: Query<&SpatialAudioSink>,
+    mut menu_state: ResMut<NextState<MenuState>>,
+) {
+    time.pause();
+
+    for sink in &music_query {
+        sink.pause();
+    }
+
+    // Enemies fall silent along with everything else
+    for sink in &spatial_query {
+        sink.pause();
+    }
+
+    menu_state.set(MenuState::Pause);
+}
+
+// Also runs when leaving the game from the pause menu, so time never stays frozen
+pub fn resume_game(
+    mut time: ResMut<Time<Virtual>>,
+    music_query: Query<&AudioSink, With<Music>>,
+    spatial_query: Query<&SpatialAudioSink>,
+    mut menu_state: ResMut<NextState<MenuState>>,
+) {
+    time.unpause();
+
+    for sink in &music_query {
+        sink.play();
+    }
+
+    for sink in &spatial_query {
+        sink.play();
+    }
+
+    menu_state.set(MenuState::Disabled);
+}
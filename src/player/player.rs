@@ -1,16 +1,19 @@
-use bevy::{
-    math::bounding::Aabb2d,
-    prelude::*,
-};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
 use crate::collider::{Collider};
-use crate::finish_area::{FinishArea, finish_area_collision};
+use crate::game_state::OnGameScreen;
+use crate::save_data::SaveData;
+use crate::settings::ControlAction;
 
-const PLAYER_SIZE: Vec2 = Vec2::new(10.0, 10.0);
+pub const PLAYER_SIZE: Vec2 = Vec2::new(10.0, 10.0);
 pub const PLAYER_SPEED: f32 = 200.;
+pub const PLAYER_TEXTURE: &str = "player-texture-atlas.png";
+// Tint of the second player, so the two can be told apart
+pub const SECOND_PLAYER_COLOR: Color = Color::srgb(1., 0.55, 0.45);
 
-#[derive(PartialEq)]
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
 pub enum PlayerFacingDirection {
     Left,
     Right,
@@ -20,7 +23,13 @@ pub enum PlayerFacingDirection {
 
 #[derive(Component)]
 pub struct Player {
+    // 0 for the first player, 1 for the second one in co-op
+    pub index: usize,
     pub alive: bool,
+    // Set once the player reaches the finish area, the level ends when the co-op finish rule is met
+    pub finished: bool,
+    // Coins this player picked up, told apart from the shared score in competitive co-op
+    pub coins: usize,
     pub player_attack_cooldown_timer: Timer,
     pub player_facing_direction: PlayerFacingDirection,
 }
@@ -29,10 +38,11 @@ pub fn spawn_player(
     commands: &mut Commands,
     asset_server: &Res<AssetServer>,
     texture_atlas_layouts: &mut ResMut<Assets<TextureAtlasLayout>>,
+    position: Vec2,
+    index: usize,
 ) {
-    let texture = asset_server.load("player-texture-atlas.png");
-    let layout = TextureAtlasLayout::from_grid(UVec2::new(12, 15), 6, 1, None, None);
-    let texture_atlas_layout = texture_atlas_layouts.add(layout);
+    let texture = asset_server.load(PLAYER_TEXTURE);
+    let texture_atlas_layout = texture_atlas_layouts.add(player_texture_atlas_layout());
     let animation_config = AnimationConfig::new(0, 0, 10);
 
     // Player spawn
@@ -40,18 +50,22 @@ pub fn spawn_player(
         SpriteBundle {
             texture: texture.clone(),
             transform: Transform {
-                translation: Vec3::new(20., 10., 0.),
+                translation: position.extend(0.),
                 scale: PLAYER_SIZE.extend(1.0),
                 ..default()
             },
             sprite: Sprite {
                 custom_size: Some(Vec2::new(2., 2.)),
+                color: if index == 0 { Color::WHITE } else { SECOND_PLAYER_COLOR },
                 ..default()
             },
             ..default()
         },
         Player {
+            index,
             alive: true,
+            finished: false,
+            coins: 0,
             player_attack_cooldown_timer: Timer::new(Duration::from_millis(0), TimerMode::Once),
             player_facing_direction: PlayerFacingDirection::Down,
         },
@@ -61,9 +75,14 @@ pub fn spawn_player(
             index: animation_config.first_sprite_index,
         },
         animation_config,
+        OnGameScreen,
     ));
 }
 
+pub fn player_texture_atlas_layout() -> TextureAtlasLayout {
+    TextureAtlasLayout::from_grid(UVec2::new(12, 15), 6, 1, None, None)
+}
+
 #[derive(Component)]
 pub struct AnimationConfig {
     first_sprite_index: usize,
@@ -89,27 +108,30 @@ impl AnimationConfig {
 
 pub fn trigger_player_walking_animation(
     keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut player_query: Query<(&mut Player, &mut AnimationConfig, &mut TextureAtlas)>,
+    save_data: Res<SaveData>,
+    mut player_query: Query<(&Player, &mut AnimationConfig, &mut TextureAtlas)>,
 ) {
-    let (player, mut animation, mut atlas) = player_query.single_mut();
-
-    if !player.alive { return };
-
-    if atlas.index == 0 || atlas.index == 3 {
-        if keyboard_input.pressed(KeyCode::ArrowLeft)
-        || keyboard_input.pressed(KeyCode::ArrowRight)
-        || keyboard_input.pressed(KeyCode::ArrowDown)
-        {
-            atlas.index = 1;
-            animation.first_sprite_index = 1;
-            animation.last_sprite_index = 2;
-        } else if keyboard_input.pressed(KeyCode::ArrowUp) {
-            atlas.index = 4;
-            animation.first_sprite_index = 4;
-            animation.last_sprite_index = 5;
-        }
+    for (player, mut animation, mut atlas) in &mut player_query {
+        if !player.alive { continue };
+
+        let controls = save_data.settings.controls(player.index);
+
+        if atlas.index == 0 || atlas.index == 3 {
+            if controls.pressed(ControlAction::Left, &keyboard_input)
+            || controls.pressed(ControlAction::Right, &keyboard_input)
+            || controls.pressed(ControlAction::Down, &keyboard_input)
+            {
+                atlas.index = 1;
+                animation.first_sprite_index = 1;
+                animation.last_sprite_index = 2;
+            } else if controls.pressed(ControlAction::Up, &keyboard_input) {
+                atlas.index = 4;
+                animation.first_sprite_index = 4;
+                animation.last_sprite_index = 5;
+            }
 
-        animation.frame_timer = AnimationConfig::timer_from_fps(animation.fps);
+            animation.frame_timer = AnimationConfig::timer_from_fps(animation.fps);
+        }
     }
 }
 
@@ -136,55 +158,44 @@ pub fn execute_player_walking_animations(
 }
 
 pub fn move_player(
-    mut commands: Commands,
     keyboard_input: Res<ButtonInput<KeyCode>>,
+    save_data: Res<SaveData>,
     time: Res<Time>,
-    mut player_query: Query<(&mut Player, &mut Transform, &mut Sprite), With<Player>>,
-    finish_area_collider_query: Query<&Transform, (With<FinishArea>, Without<Player>)>,
+    mut player_query: Query<(&mut Player, &mut Transform, &mut Sprite)>,
 ) {
-    let (mut player, mut player_transform, mut player_sprite) = player_query.single_mut();
-
-    if !player.alive { return };
-
-    let mut direction_x = 0.0;
-    let mut direction_y = 0.0;
-
-    if keyboard_input.pressed(KeyCode::ArrowLeft) {
-        direction_x -= 1.0;
-        player.player_facing_direction = PlayerFacingDirection::Left;
-        player_sprite.flip_x = true;
-    }
+    for (mut player, mut player_transform, mut player_sprite) in &mut player_query {
+        if !player.alive { continue };
 
-    if keyboard_input.pressed(KeyCode::ArrowRight) {
-        direction_x += 1.0;
-        player.player_facing_direction = PlayerFacingDirection::Right;
-        player_sprite.flip_x = false;
-    }
+        let controls = save_data.settings.controls(player.index);
 
-    if keyboard_input.pressed(KeyCode::ArrowUp) {
-        direction_y += 1.0;
-        player.player_facing_direction = PlayerFacingDirection::Up;
-        player_sprite.flip_x = false;
-    }
+        let mut direction_x = 0.0;
+        let mut direction_y = 0.0;
 
-    if keyboard_input.pressed(KeyCode::ArrowDown) {
-        direction_y -= 1.0;
-        player.player_facing_direction = PlayerFacingDirection::Down;
-        player_sprite.flip_x = false;
-    }
+        if controls.pressed(ControlAction::Left, &keyboard_input) {
+            direction_x -= 1.0;
+            player.player_facing_direction = PlayerFacingDirection::Left;
+            player_sprite.flip_x = true;
+        }
 
-    let new_player_position_x = player_transform.translation.x + direction_x * PLAYER_SPEED * time.delta_seconds();
-    let new_player_position_y = player_transform.translation.y + direction_y * PLAYER_SPEED * time.delta_seconds();
+        if controls.pressed(ControlAction::Right, &keyboard_input) {
+            direction_x += 1.0;
+            player.player_facing_direction = PlayerFacingDirection::Right;
+            player_sprite.flip_x = false;
+        }
 
-    player_transform.translation.x = new_player_position_x;
-    player_transform.translation.y = new_player_position_y;
+        if controls.pressed(ControlAction::Up, &keyboard_input) {
+            direction_y += 1.0;
+            player.player_facing_direction = PlayerFacingDirection::Up;
+            player_sprite.flip_x = false;
+        }
 
-    let player_bounding_box = Aabb2d::new(
-        player_transform.translation.truncate(), 
-        player_transform.scale.truncate() / 2.,
-    );
+        if controls.pressed(ControlAction::Down, &keyboard_input) {
+            direction_y -= 1.0;
+            player.player_facing_direction = PlayerFacingDirection::Down;
+            player_sprite.flip_x = false;
+        }
 
-    // Start finish area collision detection
-    finish_area_collision(&mut commands, &player_bounding_box, &finish_area_collider_query);
+        player_transform.translation.x += direction_x * PLAYER_SPEED * time.delta_seconds();
+        player_transform.translation.y += direction_y * PLAYER_SPEED * time.delta_seconds();
+    }
 }
-
@@ -5,6 +5,10 @@ use bevy::{
 use std::time::Duration;
 
 use crate::enemy::Enemy;
+use crate::floors::{CurrentFloor, Floor};
+use crate::game_state::OnGameScreen;
+use crate::save_data::SaveData;
+use crate::settings::ControlAction;
 use crate::player::player::{
     Player,
     PlayerFacingDirection,
@@ -15,72 +19,90 @@ pub struct PlayerAttack {
     active_timer: Timer,
 }
 
+// Sent for every swing, whether or not it hits anything
+#[derive(Event, Default)]
+pub struct PlayerAttackEvent;
+
+#[derive(Event)]
+pub struct EnemyKilledEvent {
+    pub position: Vec2,
+}
+
+#[allow(clippy::approx_constant, clippy::needless_update)]
 pub fn player_attack(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut player_query: Query<(&mut Player, &Transform), With<Player>>,
+    save_data: Res<SaveData>,
+    mut player_query: Query<(&mut Player, &Transform)>,
+    mut attack_events: EventWriter<PlayerAttackEvent>,
 ) {
-    let (mut player, player_transform) = player_query.single_mut();
-    
-    if !player.alive { return };
-    
-    if keyboard_input.pressed(KeyCode::KeyX) && player.player_attack_cooldown_timer.finished() {
-        player.player_attack_cooldown_timer = Timer::new(Duration::from_millis(500), TimerMode::Once);
-
-        let attack_location = match player.player_facing_direction {
-            PlayerFacingDirection::Left => Vec2::new(
-                player_transform.translation.x - 30.,
-                player_transform.translation.y
-            ),
-            PlayerFacingDirection::Right => Vec2::new(
-                player_transform.translation.x + 30.,
-                player_transform.translation.y
-            ),
-            PlayerFacingDirection::Up => Vec2::new(
-                player_transform.translation.x,
-                player_transform.translation.y + 30.
-            ),
-            PlayerFacingDirection::Down => Vec2::new(
-                player_transform.translation.x,
-                player_transform.translation.y - 30.
-            ),
-        };
-
-        // Rotation in radians
-        let attack_rotation = match player.player_facing_direction {
-            PlayerFacingDirection::Left => Quat::from_rotation_z(-1.57),
-            PlayerFacingDirection::Right => Quat::from_rotation_z(1.57),
-            PlayerFacingDirection::Up => Quat::from_rotation_z(3.14),
-            PlayerFacingDirection::Down => Quat::from_rotation_z(0.),
-        };
-
-        commands.spawn((
-            SpriteBundle {
-                texture: asset_server.load("sword.png"),
-                transform: Transform {
-                    translation: attack_location.extend(-1.),
-                    scale: Vec3::new(20., 40., 1.),
-                    rotation: attack_rotation,
+    for (mut player, player_transform) in &mut player_query {
+        if !player.alive { continue };
+
+        let controls = save_data.settings.controls(player.index);
+
+        if controls.pressed(ControlAction::Attack, &keyboard_input) && player.player_attack_cooldown_timer.finished() {
+            player.player_attack_cooldown_timer = Timer::new(Duration::from_millis(500), TimerMode::Once);
+            attack_events.send_default();
+
+            let attack_location = match player.player_facing_direction {
+                PlayerFacingDirection::Left => Vec2::new(
+                    player_transform.translation.x - 30.,
+                    player_transform.translation.y
+                ),
+                PlayerFacingDirection::Right => Vec2::new(
+                    player_transform.translation.x + 30.,
+                    player_transform.translation.y
+                ),
+                PlayerFacingDirection::Up => Vec2::new(
+                    player_transform.translation.x,
+                    player_transform.translation.y + 30.
+                ),
+                PlayerFacingDirection::Down => Vec2::new(
+                    player_transform.translation.x,
+                    player_transform.translation.y - 30.
+                ),
+            };
+
+            // Rotation in radians
+            let attack_rotation = match player.player_facing_direction {
+                PlayerFacingDirection::Left => Quat::from_rotation_z(-1.57),
+                PlayerFacingDirection::Right => Quat::from_rotation_z(1.57),
+                PlayerFacingDirection::Up => Quat::from_rotation_z(3.14),
+                PlayerFacingDirection::Down => Quat::from_rotation_z(0.),
+            };
+
+            commands.spawn((
+                SpriteBundle {
+                    texture: asset_server.load("sword.png"),
+                    transform: Transform {
+                        translation: attack_location.extend(-1.),
+                        scale: Vec3::new(20., 40., 1.),
+                        rotation: attack_rotation,
+                        ..default()
+                    },
+                    sprite: Sprite {
+                        custom_size: Some(Vec2::new(1., 1.)),
+                        ..default()
+                    },
                     ..default()
                 },
-                sprite: Sprite {
-                    custom_size: Some(Vec2::new(1., 1.)),
-                    ..default()
+                PlayerAttack {
+                    active_timer: Timer::new(Duration::from_millis(100), TimerMode::Once),
                 },
-                ..default()
-            },
-            PlayerAttack {
-                active_timer: Timer::new(Duration::from_millis(100), TimerMode::Once),
-            }
-        ));
+                OnGameScreen,
+            ));
+        }
     }
 }
 
 pub fn player_attack_check_for_enemy_collisions(
     mut commands: Commands,
     player_attack_query: Query<&Transform, With<PlayerAttack>>,
-    enemies_query: Query<(Entity, &Transform), With<Enemy>>,
+    enemies_query: Query<(Entity, &Transform, &Floor), With<Enemy>>,
+    current_floor: Res<CurrentFloor>,
+    mut killed_events: EventWriter<EnemyKilledEvent>,
 ) {
     for player_attack_transform in &player_attack_query {
         let player_attack_bounding_box = Aabb2d::new(
@@ -88,14 +110,15 @@ pub fn player_attack_check_for_enemy_collisions(
             player_attack_transform.scale.truncate() / 2.,
         );
 
-        for (enemy_entity, enemy_transform) in &enemies_query {
+        for (enemy_entity, enemy_transform, _) in enemies_query.iter().filter(|(_, _, floor)| current_floor.contains(floor)) {
             let enemy_bounding_box = Aabb2d::new(
                 enemy_transform.translation.truncate(),
                 enemy_transform.scale.truncate() / 2.,
             );
 
             if player_attack_bounding_box.intersects(&enemy_bounding_box) {
-                commands.entity(enemy_entity).despawn();
+                commands.entity(enemy_entity).despawn_recursive();
+                killed_events.send(EnemyKilledEvent { position: enemy_transform.translation.truncate() });
             }
         }
     }
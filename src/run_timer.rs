@@ -0,0 +1,230 @@
+use bevy::{
+    prelude::*,
+    time::Stopwatch,
+};
+use std::time::Duration;
+
+use crate::finish_area::FinishEvent;
+use crate::game_state::OnGameScreen;
+use crate::level::{CurrentLevel, Level, Levels};
+use crate::player::player::{
+    Player,
+    PLAYER_SPEED,
+};
+
+// Slack on top of the perfect shortest-path time, since turning corners costs time
+const PAR_TIME_FACTOR: f32 = 1.25;
+const TIMER_COLOR: Color = Color::srgb(80., 80., 80.);
+const UNDER_PAR_COLOR: Color = Color::srgb(0., 120., 0.);
+const OVER_PAR_COLOR: Color = Color::srgb(120., 0., 0.);
+
+#[derive(Resource, Default)]
+pub struct RunTimer {
+    pub stopwatch: Stopwatch,
+    pub running: bool,
+    pub par: Duration,
+    // Time spent on each completed level of the run, in completion order
+    pub splits: Vec<Duration>,
+    level_started: Duration,
+    // Last level completed in this run, the run goes on when its campaign successor is played next
+    finished_level: Option<String>,
+}
+
+impl RunTimer {
+    pub fn elapsed(&self) -> Duration {
+        self.stopwatch.elapsed()
+    }
+
+    pub fn level_elapsed(&self) -> Duration {
+        self.elapsed() - self.level_started
+    }
+
+    pub fn set_level_elapsed(&mut self, elapsed: Duration) {
+        let level_started = self.level_started;
+        self.stopwatch.set_elapsed(level_started + elapsed);
+    }
+}
+
+#[derive(Component)]
+pub struct RunTimerUi;
+
+pub fn par_time(level: &Level) -> Option<Duration> {
+    let path = level.solution()?;
+
+    // Measured between cell centers, since hex and polar cells are not all the same distance apart.
+    // Taking the stairs keeps the position, so it adds nothing.
+    let path_length: f32 = path.windows(2).map(|step| step[0].1.distance(step[1].1)).sum();
+
+    Some(Duration::from_secs_f32(path_length / PLAYER_SPEED * PAR_TIME_FACTOR))
+}
+
+// Playing the next campaign level keeps the run and its splits going, anything else starts a new run
+pub fn start_run_timer(
+    mut run_timer: ResMut<RunTimer>,
+    current_level: Res<CurrentLevel>,
+    levels: Res<Levels>,
+) {
+    let continues_run = run_timer
+        .finished_level
+        .as_deref()
+        .and_then(|id| levels.next_campaign_level(id))
+        .is_some_and(|next_level| next_level.id == current_level.id);
+
+    if !continues_run {
+        *run_timer = RunTimer::default();
+    }
+
+    run_timer.level_started = run_timer.elapsed();
+    run_timer.running = true;
+    run_timer.par = par_time(&current_level.level).unwrap_or_default();
+}
+
+pub fn tick_run_timer(
+    mut run_timer: ResMut<RunTimer>,
+    player_query: Query<&Player>,
+    time: Res<Time>,
+) {
+    if !run_timer.running { return };
+
+    // Stop counting once the player has died
+    if player_query.iter().all(|player| !player.alive) {
+        run_timer.running = false;
+        return;
+    }
+
+    run_timer.stopwatch.tick(time.delta());
+}
+
+pub fn stop_run_timer(
+    mut finish_events: EventReader<FinishEvent>,
+    mut run_timer: ResMut<RunTimer>,
+    current_level: Res<CurrentLevel>,
+) {
+    if finish_events.is_empty() { return };
+    finish_events.clear();
+
+    if !run_timer.running { return };
+
+    let split = run_timer.level_elapsed();
+    run_timer.splits.push(split);
+    run_timer.finished_level = Some(current_level.id.clone());
+    run_timer.running = false;
+}
+
+pub fn spawn_run_timer_ui(commands: &mut Commands) {
+    let text_style = TextStyle {
+        font_size: 20.,
+        color: TIMER_COLOR,
+        ..default()
+    };
+
+    commands.spawn((
+        RunTimerUi,
+        OnGameScreen,
+        TextBundle::from_sections([
+            TextSection::new("Time: ", text_style.clone()),
+            TextSection::from_style(text_style.clone()),
+            TextSection::new("  Par: ", text_style.clone()),
+            TextSection::from_style(text_style.clone()),
+            TextSection::from_style(text_style),
+        ])
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(5.),
+            left: Val::Px(120.),
+            ..default()
+        }),
+    ));
+}
+
+pub fn update_run_timer_ui(
+    run_timer: Res<RunTimer>,
+    mut query: Query<&mut Text, With<RunTimerUi>>,
+) {
+    for mut text in &mut query {
+        text.sections[1].value = format!("{:.2}", run_timer.level_elapsed().as_secs_f32());
+        text.sections[3].value = format!("{:.2}", run_timer.par.as_secs_f32());
+
+        if run_timer.running { continue };
+
+        let Some(split) = run_timer.splits.last() else {
+            continue;
+        };
+
+        let difference = split.as_secs_f32() - run_timer.par.as_secs_f32();
+        text.sections[1].value = format!("{:.2}", split.as_secs_f32());
+        text.sections[4].value = format!("  Split {}: {:+.2}", run_timer.splits.len(), difference);
+        text.sections[4].style.color = if difference <= 0. {
+            UNDER_PAR_COLOR
+        } else {
+            OVER_PAR_COLOR
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+
+    use crate::level::LevelEntry;
+    use crate::maze::{MazeGrid, MAZE_CELL_SIZE};
+
+    // Three cells in a row, from the left one to the right one
+    fn corridor() -> Level {
+        let mut grid = MazeGrid::new(3, 1);
+        grid.close_border();
+        Level::from_grid("corridor", &grid, UVec2::new(0, 0), UVec2::new(2, 0))
+    }
+
+    fn entry(id: &str, campaign: bool) -> LevelEntry {
+        LevelEntry { id: id.to_string(), level: corridor(), campaign }
+    }
+
+    fn play(world: &mut World, id: &str, seconds: u64) {
+        world.insert_resource(CurrentLevel { id: id.to_string(), level: corridor() });
+        world.run_system_once(start_run_timer);
+        world.resource_mut::<RunTimer>().stopwatch.tick(Duration::from_secs(seconds));
+        world.send_event_default::<FinishEvent>();
+        world.run_system_once(stop_run_timer);
+    }
+
+    fn run_world() -> World {
+        let mut world = World::new();
+        world.init_resource::<RunTimer>();
+        world.init_resource::<Events<FinishEvent>>();
+        world.insert_resource(Levels(vec![entry("first", true), entry("second", true), entry("custom", false)]));
+        world
+    }
+
+    #[test]
+    fn par_time_of_corridor() {
+        let par = par_time(&corridor()).unwrap().as_secs_f32();
+        let expected = 2. * MAZE_CELL_SIZE / PLAYER_SPEED * PAR_TIME_FACTOR;
+
+        assert!((par - expected).abs() < 0.01, "{par} is not {expected}");
+    }
+
+    #[test]
+    fn splits_carry_over_to_next_campaign_level() {
+        let mut world = run_world();
+        play(&mut world, "first", 3);
+        play(&mut world, "second", 5);
+
+        let run_timer = world.resource::<RunTimer>();
+        assert_eq!(run_timer.splits, vec![Duration::from_secs(3), Duration::from_secs(5)]);
+        assert_eq!(run_timer.elapsed(), Duration::from_secs(8));
+        assert_eq!(run_timer.level_elapsed(), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn other_level_starts_new_run() {
+        let mut world = run_world();
+        play(&mut world, "first", 3);
+        play(&mut world, "custom", 5);
+
+        let run_timer = world.resource::<RunTimer>();
+        assert_eq!(run_timer.splits, vec![Duration::from_secs(5)]);
+        assert_eq!(run_timer.elapsed(), Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,348 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::coins::Score;
+use crate::finish_area::FinishEvent;
+use crate::level::{CurrentLevel, Levels, campaign_start_id};
+use crate::run_timer::RunTimer;
+use crate::settings::Settings;
+
+// Bump when the layout of SaveData changes, keep the old layout as its own struct and convert it in migrate_save_data
+pub const SAVE_DATA_VERSION: u32 = 1;
+
+#[derive(Resource, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct SaveData {
+    pub version: u32,
+    pub best_scores: HashMap<String, usize>,
+    // Best level completion times in seconds
+    pub best_times: HashMap<String, f32>,
+    pub unlocked_levels: Vec<String>,
+    pub settings: Settings,
+    // Set when a save file could not be read nor backed up, so it is never written over
+    #[serde(skip)]
+    pub read_only: bool,
+}
+
+impl Default for SaveData {
+    fn default() -> Self {
+        SaveData {
+            version: SAVE_DATA_VERSION,
+            best_scores: HashMap::new(),
+            best_times: HashMap::new(),
+            unlocked_levels: vec![campaign_start_id().to_string()],
+            settings: Settings::default(),
+            read_only: false,
+        }
+    }
+}
+
+// Only the version is read first, so any schema can be identified before it is parsed in full
+#[derive(Deserialize)]
+struct SaveDataHeader {
+    #[serde(default)]
+    version: u32,
+}
+
+// Saves from before versioning, they had no unlocked levels list
+#[derive(Deserialize)]
+#[serde(default)]
+struct SaveDataV0 {
+    best_scores: HashMap<String, usize>,
+    best_times: HashMap<String, f32>,
+    settings: Settings,
+}
+
+impl Default for SaveDataV0 {
+    fn default() -> Self {
+        let SaveData { best_scores, best_times, settings, .. } = SaveData::default();
+        SaveDataV0 { best_scores, best_times, settings }
+    }
+}
+
+impl From<SaveDataV0> for SaveData {
+    fn from(save_data: SaveDataV0) -> Self {
+        SaveData {
+            best_scores: save_data.best_scores,
+            best_times: save_data.best_times,
+            settings: save_data.settings,
+            ..default()
+        }
+    }
+}
+
+impl SaveData {
+    pub fn load() -> SaveData {
+        match storage::read(SAVE_DATA_NAME) {
+            Some(contents) => SaveData::from_stored(&contents, |contents| storage::write(SAVE_DATA_BACKUP_NAME, contents)),
+            None => SaveData::default(),
+        }
+    }
+
+    // An unreadable save is backed up before starting fresh, or left alone entirely when that fails
+    fn from_stored(contents: &str, backup: impl FnOnce(&str) -> Result<(), String>) -> SaveData {
+        let error = match SaveData::from_ron(contents) {
+            Ok(save_data) => return save_data,
+            Err(error) => error,
+        };
+
+        match backup(contents) {
+            Ok(()) => {
+                warn!("Could not read save data, starting fresh and keeping the old one as {SAVE_DATA_BACKUP_NAME}: {error}");
+                SaveData::default()
+            }
+            Err(backup_error) => {
+                warn!("Could not read save data nor back it up, progress will not be saved: {error}, {backup_error}");
+                SaveData { read_only: true, ..default() }
+            }
+        }
+    }
+
+    pub fn from_ron(contents: &str) -> Result<SaveData, ron::error::SpannedError> {
+        let header: SaveDataHeader = ron::from_str(contents)?;
+
+        if header.version > SAVE_DATA_VERSION {
+            warn!("Save data version {} is newer than this build, progress will not be saved", header.version);
+            // The newer layout may not parse at all, its version is enough to keep it from being written over
+            let save_data = ron::from_str(contents).unwrap_or_default();
+            return Ok(SaveData { version: header.version, ..save_data });
+        }
+
+        migrate_save_data(contents, header.version)
+    }
+
+    pub fn to_ron(&self) -> Result<String, ron::Error> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+    }
+
+    // Never overwrite progress written by a newer build with fields this build does not know about
+    pub fn writable(&self) -> bool {
+        !self.read_only && self.version <= SAVE_DATA_VERSION
+    }
+
+    pub fn save(&self) {
+        if !self.writable() { return };
+
+        let result = self
+            .to_ron()
+            .map_err(|error| error.to_string())
+            .and_then(|contents| storage::write(SAVE_DATA_NAME, &contents));
+
+        if let Err(error) = result {
+            warn!("Could not write save data: {error}");
+        }
+    }
+
+    pub fn is_level_unlocked(&self, level_id: &str) -> bool {
+        self.unlocked_levels.iter().any(|unlocked| unlocked == level_id)
+    }
+
+    pub fn unlock_level(&mut self, level_id: &str) {
+        if !self.is_level_unlocked(level_id) {
+            self.unlocked_levels.push(level_id.to_string());
+        }
+    }
+}
+
+// Each version is read with its own layout, so a save from any older build reaches the current one
+fn migrate_save_data(contents: &str, version: u32) -> Result<SaveData, ron::error::SpannedError> {
+    let save_data = match version {
+        0 => SaveData::from(ron::from_str::<SaveDataV0>(contents)?),
+        _ => ron::from_str::<SaveData>(contents)?,
+    };
+
+    Ok(SaveData { version: SAVE_DATA_VERSION, ..save_data })
+}
+
+const SAVE_DATA_NAME: &str = "save";
+const SAVE_DATA_BACKUP_NAME: &str = "save_backup";
+
+// Named blobs of text, stored as files natively and in localStorage in the browser
+#[cfg(not(target_arch = "wasm32"))]
+pub mod storage {
+    use std::{fs, path::PathBuf};
+
+    fn storage_directory() -> Option<PathBuf> {
+        dirs::config_dir().map(|config_dir| config_dir.join("labyrinth_bevy"))
+    }
+
+    fn file_path(name: &str) -> Option<PathBuf> {
+        storage_directory().map(|directory| directory.join(format!("{name}.ron")))
+    }
+
+    // Names of the entries stored under a directory, without the directory prefix
+    pub fn list(directory: &str) -> Vec<String> {
+        let Some(entries) = storage_directory().and_then(|path| fs::read_dir(path.join(directory)).ok()) else {
+            return Vec::new();
+        };
+
+        let mut names: Vec<String> = entries
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                if path.extension()? != "ron" {
+                    return None;
+                }
+                Some(path.file_stem()?.to_str()?.to_string())
+            })
+            .collect();
+
+        names.sort();
+        names
+    }
+
+    pub fn read(name: &str) -> Option<String> {
+        fs::read_to_string(file_path(name)?).ok()
+    }
+
+    pub fn write(name: &str, contents: &str) -> Result<(), String> {
+        let path = file_path(name).ok_or("No config directory available")?;
+
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory).map_err(|error| error.to_string())?;
+        }
+
+        fs::write(path, contents).map_err(|error| error.to_string())
+    }
+
+    pub fn remove(name: &str) {
+        if let Some(path) = file_path(name) {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    // Writes a file meant for the player under exports/, returning where it ended up
+    pub fn export(file_name: &str, contents: &[u8]) -> Result<String, String> {
+        let directory = storage_directory().ok_or("No config directory available")?.join("exports");
+        fs::create_dir_all(&directory).map_err(|error| error.to_string())?;
+
+        let path = directory.join(file_name);
+        fs::write(&path, contents).map_err(|error| error.to_string())?;
+
+        Ok(path.display().to_string())
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub mod storage {
+    fn local_storage() -> Option<web_sys::Storage> {
+        web_sys::window()?.local_storage().ok()?
+    }
+
+    const STORAGE_KEY_PREFIX: &str = "labyrinth_bevy_";
+
+    fn storage_key(name: &str) -> String {
+        format!("{STORAGE_KEY_PREFIX}{name}")
+    }
+
+    // Names of the entries stored under a directory, without the directory prefix
+    pub fn list(directory: &str) -> Vec<String> {
+        let Some(local_storage) = local_storage() else {
+            return Vec::new();
+        };
+
+        let prefix = storage_key(&format!("{directory}/"));
+        let length = local_storage.length().unwrap_or(0);
+
+        let mut names: Vec<String> = (0..length)
+            .filter_map(|index| local_storage.key(index).ok()?)
+            .filter_map(|key| key.strip_prefix(&prefix).map(str::to_string))
+            .collect();
+
+        names.sort();
+        names
+    }
+
+    pub fn read(name: &str) -> Option<String> {
+        local_storage()?.get_item(&storage_key(name)).ok()?
+    }
+
+    pub fn write(name: &str, contents: &str) -> Result<(), String> {
+        local_storage()
+            .ok_or("No local storage available")?
+            .set_item(&storage_key(name), contents)
+            .map_err(|error| format!("{error:?}"))
+    }
+
+    pub fn remove(name: &str) {
+        if let Some(local_storage) = local_storage() {
+            let _ = local_storage.remove_item(&storage_key(name));
+        }
+    }
+
+    pub fn export(_file_name: &str, _contents: &[u8]) -> Result<String, String> {
+        Err("Exporting files is not supported in the browser".to_string())
+    }
+}
+
+// Runs before stop_run_timer, so a running timer means this is the first frame on the finish area
+pub fn record_level_results(
+    mut finish_events: EventReader<FinishEvent>,
+    run_timer: Res<RunTimer>,
+    score: Res<Score>,
+    current_level: Res<CurrentLevel>,
+    levels: Res<Levels>,
+    mut save_data: ResMut<SaveData>,
+) {
+    if finish_events.is_empty() { return };
+    finish_events.clear();
+
+    if !run_timer.running { return };
+
+    let level_time = run_timer.level_elapsed().as_secs_f32();
+
+    let best_time = save_data.best_times.entry(current_level.id.clone()).or_insert(level_time);
+    *best_time = best_time.min(level_time);
+
+    let best_score = save_data.best_scores.entry(current_level.id.clone()).or_insert(**score);
+    *best_score = (*best_score).max(**score);
+
+    if let Some(next_level) = levels.next_campaign_level(&current_level.id) {
+        save_data.unlock_level(&next_level.id);
+    }
+
+    save_data.save();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unversioned_save_migrates_to_current_version() {
+        let save_data = SaveData::from_ron(r#"(best_scores: {"labyrinth": 3})"#).unwrap();
+
+        assert_eq!(save_data.version, SAVE_DATA_VERSION);
+        assert_eq!(save_data.best_scores.get("labyrinth"), Some(&3));
+        assert_eq!(save_data.unlocked_levels, vec![campaign_start_id().to_string()]);
+    }
+
+    #[test]
+    fn newer_save_with_changed_layout_is_not_written_over() {
+        let contents = format!(r#"(version: {}, best_scores: "kept in a new format")"#, SAVE_DATA_VERSION + 1);
+        let save_data = SaveData::from_stored(&contents, |_| panic!("a newer save is not backed up"));
+
+        assert_eq!(save_data.version, SAVE_DATA_VERSION + 1);
+        assert!(!save_data.writable());
+    }
+
+    #[test]
+    fn unreadable_save_is_backed_up_before_starting_fresh() {
+        let mut backed_up = None;
+        let save_data = SaveData::from_stored("not a save", |contents| {
+            backed_up = Some(contents.to_string());
+            Ok(())
+        });
+
+        assert_eq!(backed_up.as_deref(), Some("not a save"));
+        assert_eq!(save_data, SaveData::default());
+        assert!(save_data.writable());
+    }
+
+    #[test]
+    fn unreadable_save_is_not_written_over_without_backup() {
+        let save_data = SaveData::from_stored("not a save", |_| Err("storage is full".to_string()));
+
+        assert!(!save_data.writable());
+    }
+}
@@ -0,0 +1,364 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::coins::{Coin, Score, spawn_coin};
+use crate::doors::{Door, Gate, HeldKeys, Key, KeyColor, PressureSwitch};
+use crate::enemy::{Enemy, Velocity, spawn_enemy};
+use crate::finish_area::FinishEvent;
+use crate::floors::{CurrentFloor, Floor};
+use crate::ghost::Ghost;
+use crate::level::{CurrentLevel, Level, Levels, campaign_start_id};
+use crate::player::player::{Player, PlayerFacingDirection};
+use crate::run_timer::RunTimer;
+use crate::save_data::storage;
+
+const SAVE_SLOT_NAME: &str = "save_slot";
+
+// Everything needed to rebuild a level in progress on top of a freshly spawned one
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LevelSnapshot {
+    // Slots written before there was more than one level belong to the first campaign level
+    #[serde(default = "default_level_id")]
+    pub level_id: String,
+    // The level as it was played, so generated mazes that are in no level list can be resumed as well
+    #[serde(default)]
+    pub level: Option<Level>,
+    pub player: PlayerSnapshot,
+    // Only saved from co-op games
+    #[serde(default)]
+    pub second_player: Option<PlayerSnapshot>,
+    pub enemies: Vec<EnemySnapshot>,
+    pub coins: Vec<Vec2>,
+    // Floor of each coin, empty in slots written before levels had more than one floor
+    #[serde(default)]
+    pub coin_floors: Vec<usize>,
+    // Floor the player is on
+    #[serde(default)]
+    pub floor: usize,
+    // Keys, doors and gates by their index in the level
+    #[serde(default)]
+    pub keys: Vec<usize>,
+    #[serde(default)]
+    pub held_keys: Vec<KeyColor>,
+    #[serde(default)]
+    pub doors: Vec<usize>,
+    #[serde(default)]
+    pub open_gates: Vec<usize>,
+    #[serde(default)]
+    pub pressed_switches: Vec<usize>,
+    pub score: usize,
+    pub elapsed_seconds: f32,
+}
+
+fn default_level_id() -> String {
+    campaign_start_id().to_string()
+}
+
+impl LevelSnapshot {
+    // Slots written before the level was stored along look it up by id instead
+    pub fn current_level(&self, levels: &Levels) -> Option<CurrentLevel> {
+        match &self.level {
+            Some(level) => Some(CurrentLevel { id: self.level_id.clone(), level: level.clone() }),
+            None => levels.get(&self.level_id).map(CurrentLevel::from),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PlayerSnapshot {
+    pub position: Vec2,
+    pub facing_direction: PlayerFacingDirection,
+    pub flip_x: bool,
+    pub attack_cooldown_seconds: f32,
+    #[serde(default = "default_alive")]
+    pub alive: bool,
+    #[serde(default)]
+    pub finished: bool,
+    #[serde(default)]
+    pub coins: usize,
+}
+
+fn default_alive() -> bool {
+    true
+}
+
+impl PlayerSnapshot {
+    fn new(player: &Player, player_transform: &Transform, player_sprite: &Sprite) -> PlayerSnapshot {
+        PlayerSnapshot {
+            position: player_transform.translation.truncate(),
+            facing_direction: player.player_facing_direction,
+            flip_x: player_sprite.flip_x,
+            attack_cooldown_seconds: player.player_attack_cooldown_timer.remaining_secs(),
+            alive: player.alive,
+            finished: player.finished,
+            coins: player.coins,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EnemySnapshot {
+    pub position: Vec2,
+    pub velocity: Vec2,
+    #[serde(default)]
+    pub floor: usize,
+}
+
+#[derive(Resource, Default)]
+pub struct SaveSlot {
+    pub snapshot: Option<LevelSnapshot>,
+    // Set by the Continue menu option, consumed once the level has been spawned
+    pub resume: bool,
+}
+
+impl SaveSlot {
+    pub fn load() -> SaveSlot {
+        let snapshot = storage::read(SAVE_SLOT_NAME).and_then(|contents| {
+            ron::from_str(&contents)
+                .map_err(|error| warn!("Could not read save slot: {error}"))
+                .ok()
+        });
+
+        SaveSlot { snapshot, resume: false }
+    }
+
+    pub fn store(&mut self, snapshot: LevelSnapshot) {
+        let result = ron::ser::to_string_pretty(&snapshot, ron::ser::PrettyConfig::default())
+            .map_err(|error| error.to_string())
+            .and_then(|contents| storage::write(SAVE_SLOT_NAME, &contents));
+
+        if let Err(error) = result {
+            warn!("Could not write save slot: {error}");
+        }
+
+        self.snapshot = Some(snapshot);
+    }
+
+    pub fn clear(&mut self) {
+        storage::remove(SAVE_SLOT_NAME);
+        self.snapshot = None;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn save_level(
+    mut save_slot: ResMut<SaveSlot>,
+    player_query: Query<(&Player, &Transform, &Sprite)>,
+    enemies_query: Query<(&Transform, &Velocity, &Floor), With<Enemy>>,
+    coins_query: Query<(&Transform, &Floor), With<Coin>>,
+    keys_query: Query<&Key>,
+    doors_query: Query<&Door>,
+    gates_query: Query<&Gate>,
+    switches_query: Query<&PressureSwitch>,
+    held_keys: Res<HeldKeys>,
+    score: Res<Score>,
+    run_timer: Res<RunTimer>,
+    current_level: Res<CurrentLevel>,
+    current_floor: Res<CurrentFloor>,
+) {
+    let snapshot = |index: usize| {
+        player_query
+            .iter()
+            .find(|(player, _, _)| player.index == index)
+            .map(|(player, player_transform, player_sprite)| PlayerSnapshot::new(player, player_transform, player_sprite))
+    };
+
+    let Some(player) = snapshot(0) else {
+        return;
+    };
+
+    // A finished or lost level has nothing left to resume
+    if !player_query.iter().any(|(player, _, _)| player.alive) || !run_timer.running { return };
+
+    save_slot.store(LevelSnapshot {
+        level_id: current_level.id.clone(),
+        level: Some(current_level.level.clone()),
+        player,
+        second_player: snapshot(1),
+        enemies: enemies_query
+            .iter()
+            .map(|(enemy_transform, enemy_velocity, enemy_floor)| EnemySnapshot {
+                position: enemy_transform.translation.truncate(),
+                velocity: **enemy_velocity,
+                floor: **enemy_floor,
+            })
+            .collect(),
+        coins: coins_query
+            .iter()
+            .map(|(coin_transform, _)| coin_transform.translation.truncate())
+            .collect(),
+        coin_floors: coins_query.iter().map(|(_, coin_floor)| **coin_floor).collect(),
+        floor: current_floor.floor,
+        keys: keys_query.iter().map(|key| key.index).collect(),
+        held_keys: held_keys.0.clone(),
+        doors: doors_query.iter().map(|door| door.index).collect(),
+        open_gates: gates_query.iter().filter(|gate| gate.open).map(|gate| gate.index).collect(),
+        pressed_switches: switches_query.iter().filter(|switch| switch.pressed).map(|switch| switch.index).collect(),
+        score: **score,
+        elapsed_seconds: run_timer.level_elapsed().as_secs_f32(),
+    });
+}
+
+// Runs after game_setup has spawned the level, replacing its starting state with the saved one
+#[allow(clippy::too_many_arguments)]
+pub fn resume_level(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut save_slot: ResMut<SaveSlot>,
+    mut player_query: Query<(&mut Player, &mut Transform, &mut Sprite)>,
+    mut ghost_query: Query<&mut Ghost>,
+    enemies_query: Query<Entity, With<Enemy>>,
+    coins_query: Query<Entity, With<Coin>>,
+    keys_query: Query<(Entity, &Key)>,
+    doors_query: Query<(Entity, &Door)>,
+    mut gates_query: Query<&mut Gate>,
+    mut switches_query: Query<&mut PressureSwitch>,
+    mut held_keys: ResMut<HeldKeys>,
+    mut score: ResMut<Score>,
+    mut run_timer: ResMut<RunTimer>,
+    mut current_floor: ResMut<CurrentFloor>,
+) {
+    if !save_slot.resume { return };
+    save_slot.resume = false;
+
+    let Some(snapshot) = save_slot.snapshot.clone() else {
+        return;
+    };
+
+    // A second player missing from the slot keeps their starting state
+    for (mut player, mut player_transform, mut player_sprite) in &mut player_query {
+        let player_snapshot = match player.index {
+            0 => &snapshot.player,
+            _ => match &snapshot.second_player {
+                Some(player_snapshot) => player_snapshot,
+                None => continue,
+            },
+        };
+
+        player_transform.translation = player_snapshot.position.extend(player_transform.translation.z);
+        player.player_facing_direction = player_snapshot.facing_direction;
+        player.player_attack_cooldown_timer = Timer::from_seconds(player_snapshot.attack_cooldown_seconds, TimerMode::Once);
+        player.alive = player_snapshot.alive;
+        player.finished = player_snapshot.finished;
+        player.coins = player_snapshot.coins;
+        player_sprite.flip_x = player_snapshot.flip_x;
+    }
+
+    for enemy_entity in &enemies_query {
+        commands.entity(enemy_entity).despawn_recursive();
+    }
+
+    for enemy in &snapshot.enemies {
+        spawn_enemy(&mut commands, &asset_server, enemy.position.x, enemy.position.y, enemy.velocity, enemy.floor);
+    }
+
+    for coin_entity in &coins_query {
+        commands.entity(coin_entity).despawn();
+    }
+
+    for (index, coin) in snapshot.coins.iter().enumerate() {
+        let floor = snapshot.coin_floors.get(index).copied().unwrap_or_default();
+        spawn_coin(&mut commands, coin.x, coin.y, floor);
+    }
+
+    current_floor.floor = snapshot.floor;
+
+    for (key_entity, key) in &keys_query {
+        if !snapshot.keys.contains(&key.index) {
+            commands.entity(key_entity).despawn();
+        }
+    }
+
+    for (door_entity, door) in &doors_query {
+        if !snapshot.doors.contains(&door.index) {
+            commands.entity(door_entity).despawn();
+        }
+    }
+
+    for mut gate in &mut gates_query {
+        gate.open = snapshot.open_gates.contains(&gate.index);
+    }
+
+    // A switch the player was standing on stays down instead of toggling again
+    for mut switch in &mut switches_query {
+        switch.pressed = snapshot.pressed_switches.contains(&switch.index);
+    }
+
+    held_keys.0.clone_from(&snapshot.held_keys);
+
+    **score = snapshot.score;
+    run_timer.set_level_elapsed(Duration::from_secs_f32(snapshot.elapsed_seconds));
+
+    for mut ghost in &mut ghost_query {
+        ghost.elapsed = snapshot.elapsed_seconds;
+    }
+}
+
+pub fn clear_finished_save_slot(
+    mut finish_events: EventReader<FinishEvent>,
+    mut save_slot: ResMut<SaveSlot>,
+) {
+    if finish_events.is_empty() { return };
+    finish_events.clear();
+
+    if save_slot.snapshot.is_some() {
+        save_slot.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maze_generator::{MazeSettings, generate_level};
+
+    fn snapshot(level_id: &str, level: Option<Level>) -> LevelSnapshot {
+        LevelSnapshot {
+            level_id: level_id.to_string(),
+            level,
+            player: PlayerSnapshot {
+                position: Vec2::new(16., 16.),
+                facing_direction: PlayerFacingDirection::Down,
+                flip_x: false,
+                attack_cooldown_seconds: 0.,
+                alive: true,
+                finished: false,
+                coins: 0,
+            },
+            second_player: None,
+            enemies: Vec::new(),
+            coins: Vec::new(),
+            coin_floors: Vec::new(),
+            floor: 0,
+            keys: Vec::new(),
+            held_keys: Vec::new(),
+            doors: Vec::new(),
+            open_gates: Vec::new(),
+            pressed_switches: Vec::new(),
+            score: 0,
+            elapsed_seconds: 1.,
+        }
+    }
+
+    #[test]
+    fn generated_level_resumes_from_its_slot() {
+        let settings = MazeSettings::default();
+        let level = generate_level(&settings);
+
+        let stored = ron::ser::to_string_pretty(&snapshot(&settings.level_id(), Some(level.clone())), ron::ser::PrettyConfig::default()).unwrap();
+        let resumed: LevelSnapshot = ron::from_str(&stored).unwrap();
+        let current_level = resumed.current_level(&Levels(Vec::new())).unwrap();
+
+        assert_eq!(current_level.id, settings.level_id());
+        assert_eq!(current_level.level, level);
+    }
+
+    #[test]
+    fn slot_without_level_looks_it_up_by_id() {
+        let levels = Levels::load();
+        let current_level = snapshot(campaign_start_id(), None).current_level(&levels).unwrap();
+
+        assert_eq!(current_level.level, levels.first().level);
+        assert!(snapshot("generated_missing", None).current_level(&levels).is_none());
+    }
+}
@@ -0,0 +1,605 @@
+use bevy::{
+    prelude::*,
+    window::{PresentMode, PrimaryWindow, WindowMode},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::coins::{Coin, COIN_COLOR};
+use crate::doors::{Door, Gate};
+use crate::finish_area::{FinishArea, FINISH_AREA_COLOR};
+use crate::maze_generator::{
+    MazeAlgorithm,
+    MazeSettings,
+    BRAID_FACTORS,
+    COIN_DENSITIES,
+    ENEMY_DENSITIES,
+    FLOOR_COUNTS,
+    LOCK_COUNTS,
+    MAZE_SIZES,
+};
+use crate::maze::MazeTopology;
+use crate::audio::OnAudioBus;
+use crate::save_data::SaveData;
+use crate::walls::{Wall, WALL_COLOR};
+
+const VOLUME_STEP: f32 = 0.1;
+pub const RESOLUTIONS: [(u32, u32); 4] = [(1280, 720), (1600, 900), (1920, 1080), (2560, 1440)];
+
+// Okabe-Ito colors, which stay distinguishable for the common forms of color blindness
+const COLORBLIND_WALL_COLOR: Color = Color::srgb(0.9, 0.9, 0.9);
+const COLORBLIND_COIN_COLOR: Color = Color::srgb(0.9, 0.62, 0.);
+const COLORBLIND_FINISH_AREA_COLOR: Color = Color::srgb(0., 0.45, 0.7);
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct Settings {
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+    pub window_mode: WindowMode,
+    pub resolution: (u32, u32),
+    pub vsync: bool,
+    pub controls: Controls,
+    pub second_player_controls: Controls,
+    pub colorblind_palette: bool,
+    pub reduced_motion: bool,
+    pub fog_of_war: bool,
+    pub minimap: bool,
+    pub minimap_finish: bool,
+    pub minimap_coins: bool,
+    pub two_players: bool,
+    pub split_screen: SplitScreen,
+    pub coop_scoring: CoopScoring,
+    pub coop_finish: CoopFinish,
+    pub maze: MazeSettings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            music_volume: 1.,
+            sfx_volume: 1.,
+            window_mode: WindowMode::Windowed,
+            resolution: RESOLUTIONS[0],
+            vsync: true,
+            controls: Controls::default(),
+            second_player_controls: Controls::second_player(),
+            colorblind_palette: false,
+            reduced_motion: false,
+            fog_of_war: false,
+            minimap: true,
+            minimap_finish: true,
+            minimap_coins: false,
+            two_players: false,
+            split_screen: SplitScreen::default(),
+            coop_scoring: CoopScoring::default(),
+            coop_finish: CoopFinish::default(),
+            maze: MazeSettings::default(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn player_count(&self) -> usize {
+        if self.two_players { 2 } else { 1 }
+    }
+
+    // Keys of the first player for index 0, of the second player for anything else
+    pub fn controls(&self, player: usize) -> &Controls {
+        if player == 0 { &self.controls } else { &self.second_player_controls }
+    }
+
+    pub fn controls_mut(&mut self, player: usize) -> &mut Controls {
+        if player == 0 { &mut self.controls } else { &mut self.second_player_controls }
+    }
+
+    // Binds the key, handing the control's old key to whichever control of either player had it before
+    pub fn rebind(&mut self, player: usize, action: ControlAction, key: KeyCode) -> Rebind {
+        if let Some((_, purpose)) = RESERVED_KEYS.iter().find(|(reserved, _)| *reserved == key) {
+            return Rebind::Reserved(purpose);
+        }
+
+        let old_key = self.controls(player).key(action);
+        let holder = (0..2)
+            .flat_map(|other_player| ControlAction::ALL.map(|other_action| (other_player, other_action)))
+            .find(|(other_player, other_action)| {
+                (*other_player, *other_action) != (player, action) && self.controls(*other_player).key(*other_action) == key
+            });
+
+        self.controls_mut(player).set_key(action, key);
+
+        match holder {
+            Some((other_player, other_action)) => {
+                self.controls_mut(other_player).set_key(other_action, old_key);
+                Rebind::Swapped(other_player, other_action)
+            }
+            None => Rebind::Bound,
+        }
+    }
+}
+
+// Keys the game handles itself, with what they do. Escape cancels rebinding instead.
+pub const RESERVED_KEYS: [(KeyCode, &str); 8] = [
+    (KeyCode::KeyR, "restarting"),
+    (KeyCode::F5, "saving"),
+    (KeyCode::KeyM, "the minimap"),
+    (KeyCode::Tab, "the camera"),
+    (KeyCode::Equal, "zooming"),
+    (KeyCode::NumpadAdd, "zooming"),
+    (KeyCode::Minus, "zooming"),
+    (KeyCode::NumpadSubtract, "zooming"),
+];
+
+// What became of a key pressed while rebinding
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Rebind {
+    Bound,
+    // The player and control that had the key now have the old one
+    Swapped(usize, ControlAction),
+    // Left unbound, the key is reserved for what it names
+    Reserved(&'static str),
+}
+
+// How the window is shared between two players
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SplitScreen {
+    // Side by side
+    #[default]
+    Vertical,
+    // One above the other
+    Horizontal,
+}
+
+impl SplitScreen {
+    pub const ALL: [SplitScreen; 2] = [SplitScreen::Vertical, SplitScreen::Horizontal];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SplitScreen::Vertical => "Side by side",
+            SplitScreen::Horizontal => "Top and bottom",
+        }
+    }
+}
+
+// Whether both players add to one score or race each other for coins
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CoopScoring {
+    #[default]
+    Shared,
+    Competitive,
+}
+
+impl CoopScoring {
+    pub const ALL: [CoopScoring; 2] = [CoopScoring::Shared, CoopScoring::Competitive];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CoopScoring::Shared => "Shared",
+            CoopScoring::Competitive => "Competitive",
+        }
+    }
+}
+
+// Who has to reach the finish area before the level counts as finished
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CoopFinish {
+    // Every player still alive
+    #[default]
+    Both,
+    First,
+}
+
+impl CoopFinish {
+    pub const ALL: [CoopFinish; 2] = [CoopFinish::Both, CoopFinish::First];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CoopFinish::Both => "Both players",
+            CoopFinish::First => "First player",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ControlAction {
+    Left,
+    Right,
+    Up,
+    Down,
+    Attack,
+}
+
+impl ControlAction {
+    pub const ALL: [ControlAction; 5] = [
+        ControlAction::Left,
+        ControlAction::Right,
+        ControlAction::Up,
+        ControlAction::Down,
+        ControlAction::Attack,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ControlAction::Left => "Left",
+            ControlAction::Right => "Right",
+            ControlAction::Up => "Up",
+            ControlAction::Down => "Down",
+            ControlAction::Attack => "Attack",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct Controls {
+    pub left: KeyCode,
+    pub right: KeyCode,
+    pub up: KeyCode,
+    pub down: KeyCode,
+    pub attack: KeyCode,
+}
+
+impl Default for Controls {
+    fn default() -> Self {
+        Controls {
+            left: KeyCode::ArrowLeft,
+            right: KeyCode::ArrowRight,
+            up: KeyCode::ArrowUp,
+            down: KeyCode::ArrowDown,
+            attack: KeyCode::KeyX,
+        }
+    }
+}
+
+impl Controls {
+    // Left hand keys, out of the way of the first player's arrow keys
+    pub fn second_player() -> Self {
+        Controls {
+            left: KeyCode::KeyA,
+            right: KeyCode::KeyD,
+            up: KeyCode::KeyW,
+            down: KeyCode::KeyS,
+            attack: KeyCode::Space,
+        }
+    }
+
+    pub fn key(&self, action: ControlAction) -> KeyCode {
+        match action {
+            ControlAction::Left => self.left,
+            ControlAction::Right => self.right,
+            ControlAction::Up => self.up,
+            ControlAction::Down => self.down,
+            ControlAction::Attack => self.attack,
+        }
+    }
+
+    pub fn set_key(&mut self, action: ControlAction, key: KeyCode) {
+        match action {
+            ControlAction::Left => self.left = key,
+            ControlAction::Right => self.right = key,
+            ControlAction::Up => self.up = key,
+            ControlAction::Down => self.down = key,
+            ControlAction::Attack => self.attack = key,
+        }
+    }
+
+    pub fn pressed(&self, action: ControlAction, keyboard_input: &ButtonInput<KeyCode>) -> bool {
+        keyboard_input.pressed(self.key(action))
+    }
+}
+
+// Readable key name, e.g. "X" for KeyCode::KeyX
+pub fn key_name(key: KeyCode) -> String {
+    let name = format!("{key:?}");
+
+    name.strip_prefix("Key")
+        .or_else(|| name.strip_prefix("Digit"))
+        .unwrap_or(&name)
+        .to_string()
+}
+
+#[derive(Component, Clone, Copy, PartialEq, Debug)]
+pub enum SettingAction {
+    MusicVolume,
+    SfxVolume,
+    WindowMode,
+    Resolution,
+    Vsync,
+    // Player index and the control to rebind for them
+    Rebind(usize, ControlAction),
+    ColorblindPalette,
+    ReducedMotion,
+    FogOfWar,
+    Minimap,
+    MinimapFinish,
+    MinimapCoins,
+    TwoPlayers,
+    SplitScreen,
+    CoopScoring,
+    CoopFinish,
+    MazeTopology,
+    MazeWidth,
+    MazeHeight,
+    MazeFloors,
+    MazeLocks,
+    MazeAlgorithm,
+    MazeBraid,
+    EnemyDensity,
+    CoinDensity,
+    MazeSeed,
+}
+
+impl SettingAction {
+    // Every setting is a single button that cycles to its next value when pressed
+    pub fn apply(&self, settings: &mut Settings) {
+        match self {
+            SettingAction::MusicVolume => settings.music_volume = next_volume(settings.music_volume),
+            SettingAction::SfxVolume => settings.sfx_volume = next_volume(settings.sfx_volume),
+            SettingAction::WindowMode => {
+                settings.window_mode = match settings.window_mode {
+                    WindowMode::Windowed => WindowMode::BorderlessFullscreen,
+                    WindowMode::BorderlessFullscreen => WindowMode::Fullscreen,
+                    _ => WindowMode::Windowed,
+                };
+            }
+            SettingAction::Resolution => {
+                let current = RESOLUTIONS.iter().position(|resolution| *resolution == settings.resolution);
+                let next = current.map_or(0, |index| (index + 1) % RESOLUTIONS.len());
+                settings.resolution = RESOLUTIONS[next];
+            }
+            SettingAction::Vsync => settings.vsync = !settings.vsync,
+            // Rebinding waits for the next key press instead, see rebind_control
+            SettingAction::Rebind(..) => {}
+            SettingAction::ColorblindPalette => settings.colorblind_palette = !settings.colorblind_palette,
+            SettingAction::ReducedMotion => settings.reduced_motion = !settings.reduced_motion,
+            SettingAction::FogOfWar => settings.fog_of_war = !settings.fog_of_war,
+            SettingAction::Minimap => settings.minimap = !settings.minimap,
+            SettingAction::MinimapFinish => settings.minimap_finish = !settings.minimap_finish,
+            SettingAction::MinimapCoins => settings.minimap_coins = !settings.minimap_coins,
+            SettingAction::TwoPlayers => settings.two_players = !settings.two_players,
+            SettingAction::SplitScreen => settings.split_screen = next_value(&SplitScreen::ALL, settings.split_screen),
+            SettingAction::CoopScoring => settings.coop_scoring = next_value(&CoopScoring::ALL, settings.coop_scoring),
+            SettingAction::CoopFinish => settings.coop_finish = next_value(&CoopFinish::ALL, settings.coop_finish),
+            SettingAction::MazeTopology => {
+                settings.maze.topology = next_value(&MazeTopology::ALL, settings.maze.topology);
+            }
+            SettingAction::MazeWidth => settings.maze.width = next_value(&MAZE_SIZES, settings.maze.width),
+            SettingAction::MazeHeight => settings.maze.height = next_value(&MAZE_SIZES, settings.maze.height),
+            SettingAction::MazeFloors => settings.maze.floors = next_value(&FLOOR_COUNTS, settings.maze.floors),
+            SettingAction::MazeLocks => settings.maze.locks = next_value(&LOCK_COUNTS, settings.maze.locks),
+            SettingAction::MazeAlgorithm => {
+                settings.maze.algorithm = next_value(&MazeAlgorithm::ALL, settings.maze.algorithm);
+            }
+            SettingAction::MazeBraid => settings.maze.braid = next_value(&BRAID_FACTORS, settings.maze.braid),
+            SettingAction::EnemyDensity => {
+                settings.maze.enemy_density = next_value(&ENEMY_DENSITIES, settings.maze.enemy_density);
+            }
+            SettingAction::CoinDensity => {
+                settings.maze.coin_density = next_value(&COIN_DENSITIES, settings.maze.coin_density);
+            }
+            // Seeds are not ordered in any meaningful way, so pressing rolls a new one
+            SettingAction::MazeSeed => settings.maze.seed = rand::random::<u32>() as u64,
+        }
+    }
+
+    pub fn label(&self, settings: &Settings, rebinding: &RebindingControl) -> String {
+        match self {
+            SettingAction::MusicVolume => format!("Music volume: {:.0}%", settings.music_volume * 100.),
+            SettingAction::SfxVolume => format!("Effects volume: {:.0}%", settings.sfx_volume * 100.),
+            SettingAction::WindowMode => format!("Window: {}", match settings.window_mode {
+                WindowMode::Windowed => "Windowed",
+                WindowMode::BorderlessFullscreen => "Borderless",
+                _ => "Fullscreen",
+            }),
+            SettingAction::Resolution => format!("Resolution: {}x{}", settings.resolution.0, settings.resolution.1),
+            SettingAction::Vsync => format!("Vsync: {}", on_off(settings.vsync)),
+            SettingAction::Rebind(player, action) => {
+                let control = Some((*player, *action));
+                let notice = rebinding.notice.as_ref().filter(|(notice_control, _)| Some(*notice_control) == control);
+
+                match (rebinding.control == control, notice) {
+                    (true, Some((_, notice))) => format!("{}: {notice}, press another key", action.name()),
+                    (true, None) => format!("{}: press a key", action.name()),
+                    (false, Some((_, notice))) => {
+                        format!("{}: {}, {notice}", action.name(), key_name(settings.controls(*player).key(*action)))
+                    }
+                    (false, None) => format!("{}: {}", action.name(), key_name(settings.controls(*player).key(*action))),
+                }
+            }
+            SettingAction::ColorblindPalette => format!("Colorblind palette: {}", on_off(settings.colorblind_palette)),
+            SettingAction::ReducedMotion => format!("Reduced motion: {}", on_off(settings.reduced_motion)),
+            SettingAction::FogOfWar => format!("Fog of war: {}", on_off(settings.fog_of_war)),
+            SettingAction::Minimap => format!("Minimap: {}", on_off(settings.minimap)),
+            SettingAction::MinimapFinish => format!("Finish on minimap: {}", on_off(settings.minimap_finish)),
+            SettingAction::MinimapCoins => format!("Coins on minimap: {}", on_off(settings.minimap_coins)),
+            SettingAction::TwoPlayers => format!("Two players: {}", on_off(settings.two_players)),
+            SettingAction::SplitScreen => format!("Split screen: {}", settings.split_screen.name()),
+            SettingAction::CoopScoring => format!("Scoring: {}", settings.coop_scoring.name()),
+            SettingAction::CoopFinish => format!("Finish: {}", settings.coop_finish.name()),
+            SettingAction::MazeTopology => format!("Shape: {}", settings.maze.topology.name()),
+            SettingAction::MazeWidth if settings.maze.topology == MazeTopology::Polar => {
+                format!("Rings: {}", settings.maze.width)
+            }
+            SettingAction::MazeWidth => format!("Width: {}", settings.maze.width),
+            SettingAction::MazeHeight => format!("Height: {}", settings.maze.height),
+            SettingAction::MazeFloors => format!("Floors: {}", settings.maze.floors),
+            SettingAction::MazeLocks => format!("Locks: {}", settings.maze.locks),
+            SettingAction::MazeAlgorithm => format!("Algorithm: {}", settings.maze.algorithm.name()),
+            SettingAction::MazeBraid => format!("Braid: {:.0}%", settings.maze.braid * 100.),
+            SettingAction::EnemyDensity => format!("Enemies: {:.0}% of cells", settings.maze.enemy_density * 100.),
+            SettingAction::CoinDensity => format!("Coins: {:.0}% of cells", settings.maze.coin_density * 100.),
+            SettingAction::MazeSeed => format!("Seed: {}", settings.maze.seed),
+        }
+    }
+}
+
+// The value after the current one, wrapping around, or the first one if the current value is not listed
+fn next_value<T: PartialEq + Copy>(values: &[T], current: T) -> T {
+    let index = values.iter().position(|value| *value == current);
+    values[index.map_or(0, |index| (index + 1) % values.len())]
+}
+
+fn next_volume(volume: f32) -> f32 {
+    let next = ((volume + VOLUME_STEP) / VOLUME_STEP).round() * VOLUME_STEP;
+    if next > 1. + f32::EPSILON { 0. } else { next }
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value { "On" } else { "Off" }
+}
+
+#[derive(Resource, Default)]
+pub struct RebindingControl {
+    // The player and control waiting for a new key, set while rebinding in the controls menu
+    pub control: Option<(usize, ControlAction)>,
+    // Why the last key was refused or what it was swapped with, shown next to that control
+    pub notice: Option<((usize, ControlAction), String)>,
+}
+
+pub fn movement_pressed(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    save_data: Res<SaveData>,
+) -> bool {
+    let settings = &save_data.settings;
+
+    (0..settings.player_count()).any(|player| {
+        [ControlAction::Left, ControlAction::Right, ControlAction::Up, ControlAction::Down]
+            .into_iter()
+            .any(|action| settings.controls(player).pressed(action, &keyboard_input))
+    })
+}
+
+pub fn rebind_control(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut rebinding: ResMut<RebindingControl>,
+    mut save_data: ResMut<SaveData>,
+) {
+    let Some((player, action)) = rebinding.control else {
+        return;
+    };
+
+    let Some(key) = keyboard_input.get_just_pressed().next() else {
+        return;
+    };
+
+    // Escape cancels, leaving the old binding in place
+    if *key == KeyCode::Escape {
+        *rebinding = RebindingControl::default();
+        return;
+    }
+
+    let notice = match save_data.settings.rebind(player, action, *key) {
+        // Keeps waiting for a key that can be used
+        Rebind::Reserved(purpose) => {
+            rebinding.notice = Some(((player, action), format!("{} is for {purpose}", key_name(*key))));
+            return;
+        }
+        Rebind::Swapped(other_player, other_action) if other_player != player => {
+            Some(format!("swapped with player {} {}", other_player + 1, other_action.name()))
+        }
+        Rebind::Swapped(_, other_action) => Some(format!("swapped with {}", other_action.name())),
+        Rebind::Bound => None,
+    };
+    save_data.save();
+
+    *rebinding = RebindingControl {
+        control: None,
+        notice: notice.map(|notice| ((player, action), notice)),
+    };
+}
+
+pub fn apply_audio_settings(
+    save_data: Res<SaveData>,
+    sink_query: Query<(&AudioSink, &OnAudioBus)>,
+) {
+    for (sink, bus) in &sink_query {
+        sink.set_volume(bus.volume(&save_data.settings));
+    }
+}
+
+// Window mode, resolution and vsync as last handed to the window
+type DisplayMode = (WindowMode, (u32, u32), bool);
+
+pub fn apply_display_settings(
+    save_data: Res<SaveData>,
+    mut window_query: Query<&mut Window, With<PrimaryWindow>>,
+    mut applied: Local<Option<DisplayMode>>,
+) {
+    let settings = &save_data.settings;
+
+    // Save data also changes on level completion, which must not undo a manual window resize
+    let display = (settings.window_mode, settings.resolution, settings.vsync);
+    if *applied == Some(display) { return };
+    *applied = Some(display);
+
+    for mut window in &mut window_query {
+        window.mode = settings.window_mode;
+        window.resolution.set(settings.resolution.0 as f32, settings.resolution.1 as f32);
+        window.present_mode = if settings.vsync {
+            PresentMode::AutoVsync
+        } else {
+            PresentMode::AutoNoVsync
+        };
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn apply_color_palette(
+    save_data: Res<SaveData>,
+    mut sprite_query: Query<
+        (&mut Sprite, Has<Wall>, Has<Coin>, Has<FinishArea>),
+        // Doors and closed gates are walls too, but keep their own colors
+        (Or<(With<Wall>, With<Coin>, With<FinishArea>)>, Without<Door>, Without<Gate>),
+    >,
+    added_query: Query<(), Or<(Added<Wall>, Added<Coin>, Added<FinishArea>)>>,
+) {
+    if !save_data.is_changed() && added_query.is_empty() { return };
+
+    let (wall_color, coin_color, finish_area_color) = if save_data.settings.colorblind_palette {
+        (COLORBLIND_WALL_COLOR, COLORBLIND_COIN_COLOR, COLORBLIND_FINISH_AREA_COLOR)
+    } else {
+        (WALL_COLOR, COIN_COLOR, FINISH_AREA_COLOR)
+    };
+
+    for (mut sprite, is_wall, is_coin, is_finish_area) in &mut sprite_query {
+        if is_wall {
+            sprite.color = wall_color;
+        } else if is_coin {
+            sprite.color = coin_color;
+        } else if is_finish_area {
+            sprite.color = finish_area_color;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_of_other_player_is_swapped() {
+        let mut settings = Settings::default();
+        let old_key = settings.controls(0).key(ControlAction::Attack);
+        let key = settings.controls(1).key(ControlAction::Up);
+
+        assert_eq!(settings.rebind(0, ControlAction::Attack, key), Rebind::Swapped(1, ControlAction::Up));
+        assert_eq!(settings.controls(0).key(ControlAction::Attack), key);
+        assert_eq!(settings.controls(1).key(ControlAction::Up), old_key);
+    }
+
+    #[test]
+    fn reserved_key_is_refused() {
+        let mut settings = Settings::default();
+        let old_key = settings.controls(0).key(ControlAction::Attack);
+
+        assert_eq!(settings.rebind(0, ControlAction::Attack, KeyCode::KeyR), Rebind::Reserved("restarting"));
+        assert_eq!(settings.controls(0).key(ControlAction::Attack), old_key);
+    }
+
+    #[test]
+    fn free_key_is_bound() {
+        let mut settings = Settings::default();
+
+        assert_eq!(settings.rebind(0, ControlAction::Attack, KeyCode::KeyJ), Rebind::Bound);
+        assert_eq!(settings.controls(0).key(ControlAction::Attack), KeyCode::KeyJ);
+    }
+}
@@ -1,16 +1,19 @@
 use bevy::{
-    math::bounding::{Aabb2d, IntersectsVolume},
+    math::bounding::{Aabb2d, BoundingVolume, IntersectsVolume},
     prelude::*,
+    utils::HashSet,
 };
 
 use crate::collider::{Collider, Collision, CollisionEvent};
-use crate::maze::draw_maze;
+use crate::floors::{CurrentFloor, Floor};
+use crate::game_state::OnGameScreen;
+use crate::maze::{MazeTopology, draw_maze};
 use crate::player::player::{
     Player,
     PLAYER_SPEED,
 };
 
-const WALL_COLOR: Color = Color::srgb(120.0, 120.0, 120.0);
+pub const WALL_COLOR: Color = Color::srgb(120.0, 120.0, 120.0);
 const WALL_THICKNESS: f32 = 5.;
 
 #[derive(Bundle)]
@@ -18,6 +21,8 @@ pub struct WallBundle {
     sprite_bundle: SpriteBundle,
     collider: Collider,
     wall: Wall,
+    floor: Floor,
+    on_game_screen: OnGameScreen,
 }
 
 #[derive(Component)]
@@ -25,39 +30,16 @@ pub struct Wall;
 
 impl WallBundle {
     pub fn new(
+        topology: MazeTopology,
         location_start_x: f32,
         location_start_y: f32,
         location_end_x: f32,
         location_end_y: f32,
+        floor: usize,
     ) -> WallBundle {
-
-        let size_x: f32 = if location_start_x == location_end_x {
-            WALL_THICKNESS
-        } else {
-            (location_end_x - location_start_x).abs()
-        };
-
-        let size_y: f32 = if location_start_y == location_end_y {
-            WALL_THICKNESS
-        } else {
-            (location_end_y - location_start_y).abs()
-        };
-        
-        /*
-         * Adjust start points since scaling starts at the centers
-         * Draws rectangle by starting in the bottom left corner,
-         * then drawing right and up.
-         */
-        let start_x: f32 = location_start_x + size_x / 2.;
-        let start_y: f32 = location_start_y + size_y / 2.;
-
         WallBundle {
             sprite_bundle: SpriteBundle {
-                transform: Transform {
-                    translation: Vec2::new(start_x, start_y).extend(0.0),
-                    scale: Vec2::new(size_x, size_y).extend(0.0),
-                    ..default()
-                },
+                transform: wall_transform(topology, location_start_x, location_start_y, location_end_x, location_end_y),
                 sprite: Sprite {
                     color: WALL_COLOR,
                     ..default()
@@ -65,11 +47,76 @@ impl WallBundle {
                 ..default()
             },
             wall: Wall,
+            floor: Floor(floor),
             collider: Collider,
+            on_game_screen: OnGameScreen,
         }
     }
 }
 
+/*
+ * Walls of rectangular mazes keep growing right and up from their segment like before.
+ * Walls of hex and polar mazes are centered on their segment and rotated, even the ones that
+ * happen to be axis aligned, a wall thickness longer so the corners between neighboring walls stay closed.
+ */
+pub fn wall_transform(
+    topology: MazeTopology,
+    location_start_x: f32,
+    location_start_y: f32,
+    location_end_x: f32,
+    location_end_y: f32,
+) -> Transform {
+    if topology == MazeTopology::Rectangular {
+        let bounds = wall_bounds(location_start_x, location_start_y, location_end_x, location_end_y);
+
+        return Transform {
+            translation: bounds.center().extend(0.0),
+            scale: (bounds.half_size() * 2.).extend(0.0),
+            ..default()
+        };
+    }
+
+    let start = Vec2::new(location_start_x, location_start_y);
+    let end = Vec2::new(location_end_x, location_end_y);
+    let direction = end - start;
+
+    Transform {
+        translation: start.midpoint(end).extend(0.0),
+        rotation: Quat::from_rotation_z(direction.to_angle()),
+        scale: Vec3::new(direction.length() + WALL_THICKNESS, WALL_THICKNESS, 0.0),
+    }
+}
+
+// Area an axis aligned wall segment covers once drawn with its thickness
+pub fn wall_bounds(
+    location_start_x: f32,
+    location_start_y: f32,
+    location_end_x: f32,
+    location_end_y: f32,
+) -> Aabb2d {
+    let size_x: f32 = if location_start_x == location_end_x {
+        WALL_THICKNESS
+    } else {
+        (location_end_x - location_start_x).abs()
+    };
+
+    let size_y: f32 = if location_start_y == location_end_y {
+        WALL_THICKNESS
+    } else {
+        (location_end_y - location_start_y).abs()
+    };
+
+    /*
+     * Adjust start points since scaling starts at the centers
+     * Draws rectangle by starting in the bottom left corner,
+     * then drawing right and up.
+     */
+    let start_x: f32 = location_start_x + size_x / 2.;
+    let start_y: f32 = location_start_y + size_y / 2.;
+
+    Aabb2d::new(Vec2::new(start_x, start_y), Vec2::new(size_x, size_y) / 2.)
+}
+
 pub fn wall_collision(player_bounding_box: &Aabb2d, wall: Aabb2d) -> Option<Collision> {
     if !player_bounding_box.intersects(&wall) {
         return None;
@@ -77,7 +124,13 @@ pub fn wall_collision(player_bounding_box: &Aabb2d, wall: Aabb2d) -> Option<Coll
 
     let closest = wall.closest_point(player_bounding_box.bounding_circle().center);
     let offset = player_bounding_box.bounding_circle().center - closest;
-    let side = if offset.x.abs() > offset.y.abs() {
+
+    Some(collision_side(offset))
+}
+
+// Side of the wall a box touches, judged by the direction pointing from the wall to the box
+pub fn collision_side(offset: Vec2) -> Collision {
+    if offset.x.abs() > offset.y.abs() {
         if offset.x < 0. {
             Collision::Left
         } else {
@@ -87,58 +140,109 @@ pub fn wall_collision(player_bounding_box: &Aabb2d, wall: Aabb2d) -> Option<Coll
         Collision::Top
     } else {
         Collision::Bottom
-    };
+    }
+}
+
+/*
+ * Unit direction pushing a box out of a wall, None when they do not touch.
+ * Rotated walls are checked against the circle inside the box, so it slides along them smoothly.
+ */
+pub fn wall_push_direction(bounding_box: &Aabb2d, wall: &Transform) -> Option<Vec2> {
+    let wall_center = wall.translation.truncate();
+    let wall_half_size = wall.scale.truncate() / 2.;
+
+    if wall.rotation == Quat::IDENTITY {
+        let collision = wall_collision(bounding_box, Aabb2d::new(wall_center, wall_half_size))?;
+
+        return Some(match collision {
+            Collision::Left => Vec2::NEG_X,
+            Collision::Right => Vec2::X,
+            Collision::Top => Vec2::Y,
+            Collision::Bottom => Vec2::NEG_Y,
+        });
+    }
+
+    let center = bounding_box.center();
+    let radius = bounding_box.half_size().min_element();
+    let local = (wall.rotation.inverse() * (center - wall_center).extend(0.)).truncate();
+    let offset = local - local.clamp(-wall_half_size, wall_half_size);
 
-    Some(side)
+    if offset.length() >= radius {
+        return None;
+    }
+
+    // A center inside the wall leaves towards the nearer long side
+    let local_direction = offset.try_normalize().unwrap_or(if local.y < 0. { Vec2::NEG_Y } else { Vec2::Y });
+
+    Some((wall.rotation * local_direction.extend(0.)).truncate())
 }
 
+// Sent once when a player runs into a wall, not for every step spent pushing against it
+#[derive(Event, Default)]
+pub struct WallBumpEvent;
+
+#[allow(clippy::type_complexity)]
 pub fn player_wall_collistions(
-    mut player_query: Query<&mut Transform, With<Player>>,
-    wall_collider_query: Query<&Transform, (With<Wall>, Without<Player>)>,
+    mut player_query: Query<(Entity, &mut Transform), With<Player>>,
+    wall_collider_query: Query<(&Transform, &Floor), (With<Wall>, Without<Player>)>,
+    current_floor: Res<CurrentFloor>,
     time: Res<Time>,
     mut collision_events: EventWriter<CollisionEvent>,
+    mut bump_events: EventWriter<WallBumpEvent>,
+    mut bumping: Local<HashSet<Entity>>,
 ) {
-    let mut player_transform = player_query.single_mut();
-
-    let player_bounding_box = Aabb2d::new(
-        player_transform.translation.truncate(), 
-        player_transform.scale.truncate() / 2.,
-    );
-
-    for wall_transform in &wall_collider_query {
-        let collision = wall_collision(
-            &player_bounding_box,
-            Aabb2d::new(
-                wall_transform.translation.truncate(),
-                wall_transform.scale.truncate() / 2.,
-            ),
+    for (player, mut player_transform) in &mut player_query {
+        let player_bounding_box = Aabb2d::new(
+            player_transform.translation.truncate(), 
+            player_transform.scale.truncate() / 2.,
         );
+        let mut bumped = false;
 
-        if let Some(collision) = collision {
-            collision_events.send_default();
-
-            let mut reflect_x = 0.;
-            let mut reflect_y = 0.;
+        for (wall_transform, _) in wall_collider_query.iter().filter(|(_, floor)| current_floor.contains(floor)) {
+            if let Some(direction) = wall_push_direction(&player_bounding_box, wall_transform) {
+                collision_events.send_default();
+                bumped = true;
 
-            match collision {
-                Collision::Left => reflect_x = -1.,
-                Collision::Right => reflect_x = 1.,
-                Collision::Top => reflect_y = 1.,
-                Collision::Bottom => reflect_y = -1.,
-            }
-
-            if reflect_x != 0. {
-                player_transform.translation.x = player_transform.translation.x + reflect_x * PLAYER_SPEED * time.delta_seconds();
+                player_transform.translation += (direction * PLAYER_SPEED * time.delta_seconds()).extend(0.);
             }
+        }
 
-            if reflect_y != 0. {
-                player_transform.translation.y = player_transform.translation.y + reflect_y * PLAYER_SPEED * time.delta_seconds();
-            }
+        if !bumped {
+            bumping.remove(&player);
+        } else if bumping.insert(player) {
+            bump_events.send_default();
         }
     }
 }
 
-pub fn spawn_walls(commands: &mut Commands) {
-    draw_maze(commands);
+pub fn spawn_walls(commands: &mut Commands, topology: MazeTopology, walls: &[[f32; 4]], floor: usize) {
+    draw_maze(commands, topology, walls, floor);
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_4;
+
+    #[test]
+    fn rotated_wall_pushes_out_along_its_normal() {
+        let wall = Transform {
+            rotation: Quat::from_rotation_z(FRAC_PI_4),
+            scale: Vec3::new(100., 4., 1.),
+            ..default()
+        };
+        let normal = Vec2::from_angle(FRAC_PI_4).perp();
+
+        for side in [1., -1.] {
+            // Overlapping the wall a little, away from its ends
+            let center = normal * side * 5. + Vec2::from_angle(FRAC_PI_4) * 20.;
+            let direction = wall_push_direction(&Aabb2d::new(center, Vec2::splat(4.)), &wall).unwrap();
+
+            assert!(direction.distance(normal * side) < 0.001, "{direction} on side {side}");
+        }
+
+        let clear = Aabb2d::new(normal * 10., Vec2::splat(4.));
+        assert_eq!(wall_push_direction(&clear, &wall), None);
+    }
+}
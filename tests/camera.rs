@@ -0,0 +1,146 @@
+use bevy::{prelude::*, state::app::StatesPlugin};
+
+use labyrinth_bevy::camera::{CameraPlugin, GameCamera};
+use labyrinth_bevy::game_state::{GameState, PauseState};
+use labyrinth_bevy::level::{CurrentLevel, Level};
+use labyrinth_bevy::maze::MazeGrid;
+use labyrinth_bevy::save_data::SaveData;
+
+// Headless app with only the camera and the states it reacts to, starting in the menu like the game
+fn camera_app() -> App {
+    let mut grid = MazeGrid::new(4, 3);
+    grid.close_border();
+
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, StatesPlugin, CameraPlugin))
+        .init_state::<GameState>()
+        .add_sub_state::<PauseState>()
+        .init_resource::<ButtonInput<KeyCode>>()
+        .insert_resource(SaveData::default())
+        .insert_resource(CurrentLevel {
+            id: "test".to_string(),
+            level: Level::from_grid("test", &grid, UVec2::ZERO, UVec2::new(3, 2)),
+        });
+    app.update();
+    app
+}
+
+fn enter(app: &mut App, state: GameState) {
+    app.world_mut().resource_mut::<NextState<GameState>>().set(state);
+    app.update();
+}
+
+fn follow_cameras(app: &mut App) -> usize {
+    app.world_mut()
+        .query_filtered::<(), (With<GameCamera>, With<Camera2d>)>()
+        .iter(app.world())
+        .count()
+}
+
+fn cameras(app: &mut App) -> usize {
+    app.world_mut().query::<&Camera>().iter(app.world()).count()
+}
+
+fn assert_single_camera(app: &mut App) {
+    assert_eq!(follow_cameras(app), 1, "exactly one follow camera in {:?}", app.world().resource::<State<GameState>>().get());
+    assert_eq!(cameras(app), 1, "no other camera in {:?}", app.world().resource::<State<GameState>>().get());
+}
+
+#[test]
+fn one_camera_in_the_menu() {
+    let mut app = camera_app();
+
+    assert_eq!(*app.world().resource::<State<GameState>>().get(), GameState::Menu);
+    assert_single_camera(&mut app);
+}
+
+#[test]
+fn one_camera_in_the_game() {
+    let mut app = camera_app();
+
+    enter(&mut app, GameState::Game);
+    assert_single_camera(&mut app);
+
+    app.world_mut().resource_mut::<NextState<PauseState>>().set(PauseState::Paused);
+    app.update();
+    assert_single_camera(&mut app);
+}
+
+#[test]
+fn one_camera_in_the_editor() {
+    let mut app = camera_app();
+
+    enter(&mut app, GameState::Editor);
+    assert_single_camera(&mut app);
+}
+
+#[test]
+fn one_camera_across_state_changes() {
+    let mut app = camera_app();
+
+    for state in [
+        GameState::Game,
+        GameState::Restart,
+        GameState::Game,
+        GameState::Menu,
+        GameState::Editor,
+        GameState::Menu,
+        GameState::Game,
+        GameState::Editor,
+    ] {
+        enter(&mut app, state);
+        assert_single_camera(&mut app);
+    }
+}
+
+#[test]
+fn the_camera_draws_the_ui() {
+    let mut app = camera_app();
+
+    let ui_cameras = app
+        .world_mut()
+        .query_filtered::<(), (With<GameCamera>, With<IsDefaultUiCamera>)>()
+        .iter(app.world())
+        .count();
+    assert_eq!(ui_cameras, 1);
+}
+
+#[test]
+fn the_editor_undoes_the_game_zoom() {
+    let mut app = camera_app();
+
+    enter(&mut app, GameState::Game);
+    let camera = app.world_mut().query_filtered::<Entity, With<GameCamera>>().single(app.world());
+    app.world_mut().get_mut::<OrthographicProjection>(camera).unwrap().scale = 2.;
+    app.world_mut().get_mut::<Transform>(camera).unwrap().rotation = Quat::from_rotation_z(0.1);
+
+    enter(&mut app, GameState::Editor);
+
+    let (min, max) = app.world().resource::<CurrentLevel>().level.bounds();
+    let camera_transform = app.world().get::<Transform>(camera).unwrap();
+    assert_eq!(app.world().get::<OrthographicProjection>(camera).unwrap().scale, 1.);
+    assert_eq!(camera_transform.rotation, Quat::IDENTITY);
+    assert_eq!(camera_transform.translation.truncate(), min.midpoint(max));
+}
+
+#[test]
+fn one_camera_per_player_in_co_op() {
+    let mut app = camera_app();
+    app.world_mut().resource_mut::<SaveData>().settings.two_players = true;
+
+    enter(&mut app, GameState::Game);
+    assert_eq!(follow_cameras(&mut app), 2);
+
+    let ui_cameras = app.world_mut().query_filtered::<(), With<IsDefaultUiCamera>>().iter(app.world()).count();
+    assert_eq!(ui_cameras, 1);
+
+    enter(&mut app, GameState::Menu);
+    assert_single_camera(&mut app);
+
+    let ui_cameras = app
+        .world_mut()
+        .query_filtered::<(), (With<GameCamera>, With<IsDefaultUiCamera>)>()
+        .iter(app.world())
+        .count();
+    assert_eq!(ui_cameras, 1);
+}
@@ -0,0 +1,211 @@
+use bevy::{
+    ecs::system::RunSystemOnce,
+    prelude::*,
+    time::TimeUpdateStrategy,
+};
+use std::time::Duration;
+
+use labyrinth_bevy::coins::{COIN_SIZE, Coin, CoinCollectedEvent, Score, ScoreboardUi, check_for_player_collisions_with_coins, spawn_scoreboard, update_scoreboard};
+use labyrinth_bevy::collider::CollisionEvent;
+use labyrinth_bevy::enemy::{ENEMY_SIZE, Enemy, PlayerHitEvent, check_for_player_collisions_with_enemies};
+use labyrinth_bevy::floors::{CurrentFloor, Floor};
+use labyrinth_bevy::player::player::{PLAYER_SIZE, Player, PlayerFacingDirection, move_player};
+use labyrinth_bevy::player::player_attack::{EnemyKilledEvent, PlayerAttack, PlayerAttackEvent, cooldown_player_attack_timer, player_attack};
+use labyrinth_bevy::save_data::SaveData;
+use labyrinth_bevy::settings::CoopScoring;
+use labyrinth_bevy::walls::{WallBumpEvent, player_wall_collistions};
+
+// Headless app running the gameplay systems that used to expect exactly one player and one scoreboard
+fn gameplay_app() -> App {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+        .init_asset::<Image>()
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(16)))
+        .init_resource::<ButtonInput<KeyCode>>()
+        .init_resource::<CurrentFloor>()
+        .insert_resource(SaveData::default())
+        .insert_resource(Score(0))
+        .add_event::<CollisionEvent>()
+        .add_event::<WallBumpEvent>()
+        .add_event::<PlayerHitEvent>()
+        .add_event::<EnemyKilledEvent>()
+        .add_event::<PlayerAttackEvent>()
+        .add_event::<CoinCollectedEvent>()
+        .add_systems(
+            Update,
+            (
+                move_player,
+                player_wall_collistions,
+                check_for_player_collisions_with_enemies,
+                player_attack,
+                cooldown_player_attack_timer,
+                update_scoreboard,
+                check_for_player_collisions_with_coins,
+            )
+            .chain(),
+        );
+    app
+}
+
+fn spawn_player(app: &mut App, index: usize, position: Vec2) -> Entity {
+    app.world_mut()
+        .spawn((
+            Player {
+                index,
+                alive: true,
+                finished: false,
+                coins: 0,
+                player_attack_cooldown_timer: Timer::new(Duration::ZERO, TimerMode::Once),
+                player_facing_direction: PlayerFacingDirection::Down,
+            },
+            Transform::from_translation(position.extend(0.)).with_scale(PLAYER_SIZE.extend(1.)),
+            Sprite::default(),
+        ))
+        .id()
+}
+
+fn spawn_scoreboard_ui(app: &mut App) {
+    app.world_mut().run_system_once(|mut commands: Commands| spawn_scoreboard(&mut commands));
+}
+
+fn press(app: &mut App, keys: &[KeyCode]) {
+    let mut keyboard_input = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+    for key in keys {
+        keyboard_input.press(*key);
+    }
+}
+
+// Looks up the player with the given index and reads something off it
+fn player<T>(app: &mut App, index: usize, read: impl Fn(&Player, &Transform) -> T) -> T {
+    app.world_mut()
+        .query::<(&Player, &Transform)>()
+        .iter(app.world())
+        .find(|(player, _)| player.index == index)
+        .map(|(player, transform)| read(player, transform))
+        .expect("player is spawned")
+}
+
+fn position(app: &mut App, index: usize) -> Vec2 {
+    player(app, index, |_, transform| transform.translation.truncate())
+}
+
+fn scoreboard(app: &mut App) -> String {
+    app.world_mut()
+        .query_filtered::<&Text, With<ScoreboardUi>>()
+        .single(app.world())
+        .sections[1]
+        .value
+        .clone()
+}
+
+#[test]
+fn no_player_and_no_scoreboard() {
+    let mut app = gameplay_app();
+    press(&mut app, &[KeyCode::ArrowLeft, KeyCode::KeyX]);
+
+    for _ in 0..3 {
+        app.update();
+    }
+}
+
+#[test]
+fn despawned_player() {
+    let mut app = gameplay_app();
+    spawn_scoreboard_ui(&mut app);
+    let player = spawn_player(&mut app, 0, Vec2::ZERO);
+    app.update();
+
+    app.world_mut().despawn(player);
+    press(&mut app, &[KeyCode::ArrowLeft, KeyCode::KeyX]);
+    app.update();
+    app.update();
+
+    assert_eq!(scoreboard(&mut app), "0");
+}
+
+#[test]
+fn two_players_move_with_their_own_controls() {
+    let mut app = gameplay_app();
+    spawn_player(&mut app, 0, Vec2::ZERO);
+    spawn_player(&mut app, 1, Vec2::new(100., 0.));
+
+    press(&mut app, &[KeyCode::ArrowLeft, KeyCode::KeyD]);
+    app.update();
+    app.update();
+
+    assert!(position(&mut app, 0).x < 0.);
+    assert!(position(&mut app, 1).x > 100.);
+    assert_eq!(player(&mut app, 0, |player, _| player.player_facing_direction), PlayerFacingDirection::Left);
+    assert_eq!(player(&mut app, 1, |player, _| player.player_facing_direction), PlayerFacingDirection::Right);
+}
+
+#[test]
+fn two_players_attack_with_their_own_controls() {
+    let mut app = gameplay_app();
+    spawn_player(&mut app, 0, Vec2::ZERO);
+    spawn_player(&mut app, 1, Vec2::new(100., 0.));
+
+    press(&mut app, &[KeyCode::Space]);
+    app.update();
+    app.update();
+
+    let attacks: Vec<Vec2> = app
+        .world_mut()
+        .query_filtered::<&Transform, With<PlayerAttack>>()
+        .iter(app.world())
+        .map(|transform| transform.translation.truncate())
+        .collect();
+    assert_eq!(attacks, vec![Vec2::new(100., -30.)]);
+}
+
+#[test]
+fn one_of_two_players_caught() {
+    let mut app = gameplay_app();
+    spawn_player(&mut app, 0, Vec2::ZERO);
+    spawn_player(&mut app, 1, Vec2::new(100., 0.));
+    app.world_mut().spawn((
+        Enemy,
+        Transform::from_translation(Vec3::new(100., 0., 0.)).with_scale(ENEMY_SIZE.extend(1.)),
+        Floor(0),
+    ));
+
+    app.update();
+    app.update();
+
+    assert!(player(&mut app, 0, |player, _| player.alive));
+    assert!(!player(&mut app, 1, |player, _| player.alive));
+    assert_eq!(app.world().resource::<Events<PlayerHitEvent>>().len(), 1);
+
+    // The player left standing can still move, the caught one stays put
+    press(&mut app, &[KeyCode::ArrowLeft, KeyCode::KeyA]);
+    app.update();
+    assert!(position(&mut app, 0).x < 0.);
+    assert_eq!(position(&mut app, 1), Vec2::new(100., 0.));
+}
+
+#[test]
+fn both_players_collect_coins() {
+    let mut app = gameplay_app();
+    spawn_scoreboard_ui(&mut app);
+    spawn_player(&mut app, 0, Vec2::ZERO);
+    spawn_player(&mut app, 1, Vec2::new(100., 0.));
+    for position in [Vec2::ZERO, Vec2::new(100., 0.), Vec2::new(102., 0.)] {
+        app.world_mut().spawn((
+            Coin,
+            Transform::from_translation(position.extend(0.)).with_scale(COIN_SIZE.extend(1.)),
+            Floor(0),
+        ));
+    }
+
+    app.update();
+    app.update();
+
+    assert_eq!(**app.world().resource::<Score>(), 3);
+    assert_eq!(player(&mut app, 0, |player, _| player.coins), 1);
+    assert_eq!(player(&mut app, 1, |player, _| player.coins), 2);
+    assert_eq!(scoreboard(&mut app), "3");
+
+    app.world_mut().resource_mut::<SaveData>().settings.coop_scoring = CoopScoring::Competitive;
+    app.update();
+    assert_eq!(scoreboard(&mut app), "P1 1  P2 2");
+}